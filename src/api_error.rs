@@ -0,0 +1,120 @@
+use reqwest::StatusCode;
+use serde_json::Value;
+use std::{
+    error::Error,
+    fmt::{self, Display},
+};
+
+// Longest part of an unexpected response body shown in an error.
+const MAX_BODY_LENGTH: usize = 200;
+
+// An error response of Salesforce, in place of the requested resource.
+#[derive(Debug, PartialEq)]
+pub enum ApiError {
+    // the errorCode and message of the REST API, e.g. MALFORMED_QUERY
+    Api(String, String),
+    // the error and error_description of the OAuth endpoints, e.g. invalid_grant
+    Auth(String, String),
+    // a body that is not JSON, e.g. the HTML page of a proxy or maintenance, with its
+    // HTTP status
+    Unexpected(u16, String),
+}
+
+impl Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiError::Api(code, message) => write!(f, "{}: {}", code, message),
+            ApiError::Auth(error, description) => {
+                write!(f, "Login failed: {} ({})", description, error)
+            }
+            ApiError::Unexpected(status, body) => {
+                let mut body: String = body.split_whitespace().collect::<Vec<&str>>().join(" ");
+                if body.chars().count() > MAX_BODY_LENGTH {
+                    body = format!(
+                        "{}...",
+                        body.chars().take(MAX_BODY_LENGTH).collect::<String>()
+                    );
+                }
+                write!(
+                    f,
+                    "Unexpected response from Salesforce (HTTP {}): {}",
+                    status, body
+                )
+            }
+        }
+    }
+}
+
+impl Error for ApiError {}
+
+impl ApiError {
+    // The error a JSON response stands for, if it is one.
+    pub fn from_value(value: &Value) -> Option<ApiError> {
+        if let Some(message) = value[0]["message"].as_str() {
+            let code = value[0]["errorCode"].as_str().unwrap_or("ERROR");
+            return Some(ApiError::Api(code.to_string(), message.to_string()));
+        }
+        let error = value["error"].as_str()?;
+        let description = value["error_description"].as_str().unwrap_or(error);
+        Some(ApiError::Auth(error.to_string(), description.to_string()))
+    }
+}
+
+// Parses a response body as JSON without judging its content.
+pub fn parse_json(status: StatusCode, body: &str) -> Result<Value, ApiError> {
+    serde_json::from_str(body).map_err(|_| ApiError::Unexpected(status.as_u16(), body.to_string()))
+}
+
+// Parses a response body, turning Salesforce's error responses into errors.
+pub fn parse_response(status: StatusCode, body: &str) -> Result<Value, ApiError> {
+    let value = parse_json(status, body)?;
+    match ApiError::from_value(&value) {
+        Some(error) => Err(error),
+        None => Ok(value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_response() {
+        assert_eq!(
+            parse_response(
+                StatusCode::BAD_REQUEST,
+                r#"[{"message":"\nSELECT Nme FROM Account\n       ^\nERROR at Row:1:Column:8\nNo such column 'Nme' on entity 'Account'.","errorCode":"INVALID_FIELD"}]"#
+            ),
+            Err(ApiError::Api(
+                String::from("INVALID_FIELD"),
+                String::from("\nSELECT Nme FROM Account\n       ^\nERROR at Row:1:Column:8\nNo such column 'Nme' on entity 'Account'.")
+            ))
+        );
+        assert_eq!(
+            parse_response(
+                StatusCode::BAD_REQUEST,
+                r#"{"error":"invalid_grant","error_description":"authentication failure"}"#
+            )
+            .unwrap_err()
+            .to_string(),
+            "Login failed: authentication failure (invalid_grant)"
+        );
+        assert_eq!(
+            parse_response(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "<html>\n  <body>Down for maintenance</body>\n</html>"
+            )
+            .unwrap_err()
+            .to_string(),
+            "Unexpected response from Salesforce (HTTP 503): <html> <body>Down for maintenance</body> </html>"
+        );
+        assert_eq!(
+            parse_response(
+                StatusCode::OK,
+                r#"{"totalSize":0,"done":true,"records":[]}"#
+            )
+            .unwrap()["totalSize"],
+            0
+        );
+    }
+}
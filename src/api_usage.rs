@@ -0,0 +1,71 @@
+use std::fmt::{self, Display};
+
+// The API requests the org has made in the last 24 hours, out of its daily limit, as
+// Salesforce reports them on every response.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ApiUsage {
+    pub used: u64,
+    pub max: u64,
+}
+
+impl ApiUsage {
+    // Reads the api-usage item of a Sforce-Limit-Info header, e.g.
+    // `api-usage=18/5000; per-app-api-usage=17/250(appName=sample-app)`.
+    pub fn parse(header: &str) -> Option<ApiUsage> {
+        let usage = header
+            .split(';')
+            .find_map(|item| item.trim().strip_prefix("api-usage="))?;
+        let (used, max) = usage.split_once('/')?;
+        Some(ApiUsage {
+            used: used.trim().parse().ok()?,
+            max: max.trim().parse().ok()?,
+        })
+    }
+
+    pub fn percent(&self) -> f64 {
+        if self.max == 0 {
+            return 0.0;
+        }
+        self.used as f64 / self.max as f64 * 100.0
+    }
+}
+
+impl Display for ApiUsage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}/{} API requests in the last 24 hours ({:.0}%)",
+            self.used,
+            self.max,
+            self.percent()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let usage =
+            ApiUsage::parse("api-usage=4500/5000; per-app-api-usage=17/250(appName=sample-app)")
+                .unwrap();
+        assert_eq!(
+            usage,
+            ApiUsage {
+                used: 4500,
+                max: 5000
+            }
+        );
+        assert_eq!(
+            usage.to_string(),
+            "4500/5000 API requests in the last 24 hours (90%)"
+        );
+        assert_eq!(
+            ApiUsage::parse("per-app-api-usage=17/250(appName=sample-app)"),
+            None
+        );
+        assert_eq!(ApiUsage::parse("api-usage=abc"), None);
+    }
+}
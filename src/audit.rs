@@ -0,0 +1,95 @@
+use crate::helper::DynError;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+// An executed query as written to the audit log, one JSON object per line.
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+pub struct AuditEntry {
+    // RFC 3339
+    pub time: String,
+    pub user_id: Option<String>,
+    // the method chain as entered
+    pub query: String,
+    pub soql: Vec<String>,
+    // the reason given with \why
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub why: Option<String>,
+}
+
+pub struct AuditLog {
+    path: PathBuf,
+}
+
+impl AuditLog {
+    pub fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+        }
+    }
+
+    pub fn append(&self, entry: &AuditEntry) -> Result<(), DynError> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{}", serde_json::to_string(entry)?)?;
+        Ok(())
+    }
+
+    // Sets the reason of the last logged query, replacing an earlier one.
+    pub fn annotate_last(&self, why: &str) -> Result<(), DynError> {
+        let content = fs::read_to_string(&self.path).unwrap_or_default();
+        let mut lines: Vec<&str> = content.lines().collect();
+        let last = lines.pop().ok_or("No query has been run yet")?;
+
+        let mut entry: AuditEntry = serde_json::from_str(last)?;
+        entry.why = Some(why.to_string());
+        let last = serde_json::to_string(&entry)?;
+        lines.push(&last);
+
+        fs::write(&self.path, format!("{}\n", lines.join("\n")))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(query: &str) -> AuditEntry {
+        AuditEntry {
+            time: String::from("2024-01-01T09:00:00+00:00"),
+            user_id: Some(String::from("0055g00000AbCdE")),
+            query: query.to_string(),
+            soql: vec![format!("SELECT Id FROM {}", query)],
+            why: None,
+        }
+    }
+
+    #[test]
+    fn test_annotate_last() {
+        let path = std::env::temp_dir().join("soql_generator_test_annotate_last.jsonl");
+        let _ = fs::remove_file(&path);
+        let log = AuditLog::new(&path);
+        assert!(log.annotate_last("too early").is_err());
+
+        log.append(&entry("Lead")).unwrap();
+        log.append(&entry("Account")).unwrap();
+        log.annotate_last("checking duplicate leads for INC-1234")
+            .unwrap();
+
+        let entries: Vec<AuditEntry> = fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(entries[0], entry("Lead"));
+        assert_eq!(
+            entries[1].why.as_deref(),
+            Some("checking duplicate leads for INC-1234")
+        );
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,579 @@
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use base64::Engine;
+use keyring::Entry;
+use openssl::hash::MessageDigest;
+use openssl::pkey::PKey;
+use openssl::sign::Signer;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use termion::input::TermRead;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use urlencoding::{decode, encode};
+
+use crate::api_error::parse_json;
+use crate::cache::CacheKey;
+use crate::config::Profile;
+use crate::helper::DynError;
+use crate::salesforce::{http_client, login_url, oauth_url};
+
+// The callback URL of the connected app, the same one the Salesforce CLI uses
+const REDIRECT_URI: &str = "http://localhost:1717/OauthRedirect";
+const CALLBACK_ADDRESS: &str = "127.0.0.1:1717";
+const SCOPE: &str = "api refresh_token";
+const KEYRING_SERVICE: &str = "soql-generator";
+const SESSION_ENTRY: &str = "session";
+const PASSWORD_ENTRY: &str = "password";
+const CLIENT_SECRET_ENTRY: &str = "client secret";
+const CACHE_KEY_ENTRY: &str = "cache key";
+// seconds a JWT bearer assertion is valid for, Salesforce accepts at most 3 minutes
+const JWT_LIFETIME_SECS: u64 = 180;
+
+// What is kept between sessions after `soql-generator login`.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct StoredToken {
+    pub refresh_token: String,
+    pub instance_url: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TokenResponse {
+    pub access_token: String,
+    #[serde(default)]
+    pub refresh_token: Option<String>,
+    pub instance_url: String,
+    // identity URL ending in /<org id>/<user id>
+    #[serde(default)]
+    pub id: String,
+}
+
+// The username and password stored by `soql-generator login --password`.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct StoredPassword {
+    pub username: String,
+    pub password: String,
+}
+
+// Secrets are kept in the keychain of the OS, one entry per kind of secret and org,
+// e.g. `uat/password`.
+fn keyring_entry(profile: &Profile, name: &str) -> Result<Entry, DynError> {
+    let user = match &profile.name {
+        Some(org) => format!("{}/{}", org, name),
+        None => name.to_string(),
+    };
+    Ok(Entry::new(KEYRING_SERVICE, &user)?)
+}
+
+fn load_secret<T: DeserializeOwned>(profile: &Profile, name: &str) -> Result<Option<T>, DynError> {
+    match keyring_entry(profile, name)?.get_password() {
+        Ok(secret) => Ok(Some(serde_json::from_str(&secret)?)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(format!("Failed to read the {} from the keyring: {}", name, e).into()),
+    }
+}
+
+fn save_secret<T: Serialize>(profile: &Profile, name: &str, secret: &T) -> Result<(), DynError> {
+    keyring_entry(profile, name)?
+        .set_password(&serde_json::to_string(secret)?)
+        .map_err(|e| format!("Failed to store the {} in the keyring: {}", name, e).into())
+}
+
+pub fn load_token(profile: &Profile) -> Result<Option<StoredToken>, DynError> {
+    load_secret(profile, SESSION_ENTRY)
+}
+
+pub fn load_password(profile: &Profile) -> Result<Option<StoredPassword>, DynError> {
+    load_secret(profile, PASSWORD_ENTRY)
+}
+
+// The key the metadata cache of the org is encrypted with when encrypt_cache is set,
+// generated the first time. Losing it only means describing the org again.
+pub fn cache_key(profile: &Profile) -> Result<CacheKey, DynError> {
+    let stored: Option<String> = load_secret(profile, CACHE_KEY_ENTRY)?;
+    if let Some(key) = stored.and_then(|key| STANDARD.decode(key).ok()) {
+        if let Ok(key) = CacheKey::try_from(key) {
+            return Ok(key);
+        }
+    }
+    let key: CacheKey = rand::thread_rng().gen();
+    save_secret(profile, CACHE_KEY_ENTRY, &STANDARD.encode(key))?;
+    Ok(key)
+}
+
+// The consumer key of the org's profile, or SFDC_CLIENT_ID.
+pub fn client_id(profile: &Profile) -> Result<String, DynError> {
+    match &profile.client_id {
+        Some(client_id) => Ok(client_id.clone()),
+        None => env::var("SFDC_CLIENT_ID").map_err(|_| "Set SFDC_CLIENT_ID".into()),
+    }
+}
+
+// SFDC_CLIENT_SECRET, or the consumer secret stored by `soql-generator login --password`.
+pub fn client_secret(profile: &Profile) -> Result<Option<String>, DynError> {
+    match env::var("SFDC_CLIENT_SECRET") {
+        Ok(secret) if !secret.is_empty() => Ok(Some(secret)),
+        _ => load_secret(profile, CLIENT_SECRET_ENTRY),
+    }
+}
+
+// Asks for the username, password and consumer secret of the password flow and stores
+// them in the keyring, so they do not have to be exported in the shell.
+pub fn store_password(profile: &Profile) -> Result<(), DynError> {
+    let username = match &profile.username {
+        Some(username) => username.clone(),
+        None => prompt("Username: ", false)?,
+    };
+    let password = prompt(
+        "Password (with the security token appended if required): ",
+        true,
+    )?;
+    if username.is_empty() || password.is_empty() {
+        return Err("The username and password are required".into());
+    }
+    save_secret(
+        profile,
+        PASSWORD_ENTRY,
+        &StoredPassword { username, password },
+    )?;
+
+    if env::var("SFDC_CLIENT_SECRET").is_err() {
+        let client_secret = prompt("Consumer secret of the connected app: ", true)?;
+        if !client_secret.is_empty() {
+            save_secret(profile, CLIENT_SECRET_ENTRY, &client_secret)?;
+        }
+    }
+    Ok(())
+}
+
+// Removes every secret stored by `soql-generator login` for the org.
+pub fn logout(profile: &Profile) -> Result<(), DynError> {
+    for name in [SESSION_ENTRY, PASSWORD_ENTRY, CLIENT_SECRET_ENTRY] {
+        match keyring_entry(profile, name)?.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => {}
+            Err(e) => {
+                return Err(format!("Failed to remove the {} from the keyring: {}", name, e).into())
+            }
+        }
+    }
+    Ok(())
+}
+
+fn prompt(question: &str, hidden: bool) -> Result<String, DynError> {
+    let mut stdout = io::stdout();
+    write!(stdout, "{}", question)?;
+    stdout.flush()?;
+    let answer = if hidden {
+        let answer = io::stdin().read_passwd(&mut stdout)?;
+        writeln!(stdout)?;
+        answer
+    } else {
+        TermRead::read_line(&mut io::stdin())?
+    };
+    Ok(answer.unwrap_or_default().trim().to_string())
+}
+
+// Logs in through the browser with the authorization code flow and PKCE, and stores
+// the refresh token for later sessions.
+pub async fn login_with_browser(profile: &Profile) -> Result<TokenResponse, DynError> {
+    let client_id = client_id(profile)?;
+    let verifier = random_string(64);
+    let state = random_string(16);
+    let url = format!(
+        "{}?response_type=code&client_id={}&redirect_uri={}&scope={}&code_challenge={}&code_challenge_method=S256&state={}",
+        oauth_url(profile, "authorize"),
+        encode(&client_id),
+        encode(REDIRECT_URI),
+        encode(SCOPE),
+        code_challenge(&verifier),
+        state
+    );
+
+    let listener = TcpListener::bind(CALLBACK_ADDRESS)
+        .await
+        .map_err(|e| format!("Failed to listen on {}: {}", CALLBACK_ADDRESS, e))?;
+    println!("Log in at {}", url);
+    let _ = webbrowser::open(&url);
+
+    let params = loop {
+        let (mut stream, _) = listener.accept().await?;
+        let mut buffer = [0; 8192];
+        let length = stream.read(&mut buffer).await?;
+        let request = String::from_utf8_lossy(&buffer[..length]);
+        // browsers also ask for a favicon, and a callback of another login is not ours to
+        // act on, not even when it reports an error
+        let params = match callback_params(&request, &state) {
+            Some(params) => params,
+            None => {
+                stream
+                    .write_all(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n")
+                    .await?;
+                continue;
+            }
+        };
+        let body = match params.get("error") {
+            Some(_) => "Login failed, see the terminal for details.",
+            None => "Logged in to soql-generator, you can close this window.",
+        };
+        stream
+            .write_all(
+                format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: {}\r\n\r\n{}",
+                    body.len(),
+                    body
+                )
+                .as_bytes(),
+            )
+            .await?;
+        break params;
+    };
+
+    if let Some(error) = params.get("error") {
+        return Err(format!(
+            "Login failed: {}",
+            params.get("error_description").unwrap_or(error)
+        )
+        .into());
+    }
+    let code = params
+        .get("code")
+        .ok_or("Login failed: no code was returned")?;
+
+    let mut form = vec![
+        ("grant_type", "authorization_code"),
+        ("code", code),
+        ("client_id", &client_id),
+        ("redirect_uri", REDIRECT_URI),
+        ("code_verifier", &verifier),
+    ];
+    let client_secret = client_secret(profile)?;
+    if let Some(client_secret) = &client_secret {
+        form.push(("client_secret", client_secret));
+    }
+    let token = request_token(profile, &form).await?;
+    store(profile, &token)?;
+    Ok(token)
+}
+
+// Logs in with the device flow, for machines without a browser: the code shown is
+// entered on another device, and the session is polled for until it is approved.
+pub async fn login_with_device(profile: &Profile) -> Result<TokenResponse, DynError> {
+    let client_id = client_id(profile)?;
+    let response = post_form(
+        profile,
+        &[
+            ("response_type", "device_code"),
+            ("client_id", &client_id),
+            ("scope", SCOPE),
+        ],
+    )
+    .await?;
+    if let Some(message) = token_error(&response) {
+        return Err(format!("Login failed: {}", message).into());
+    }
+    let device_code = response["device_code"]
+        .as_str()
+        .ok_or("Login failed: no device code was returned")?;
+    println!(
+        "Open {} and enter the code {}",
+        response["verification_uri"].as_str().unwrap_or_default(),
+        response["user_code"].as_str().unwrap_or_default()
+    );
+
+    let mut interval = response["interval"].as_u64().unwrap_or(5);
+    loop {
+        tokio::time::sleep(Duration::from_secs(interval)).await;
+        let response = post_form(
+            profile,
+            &[
+                ("grant_type", "device"),
+                ("client_id", &client_id),
+                ("code", device_code),
+            ],
+        )
+        .await?;
+        match response["error"].as_str() {
+            Some("authorization_pending") => continue,
+            Some("slow_down") => interval += 5,
+            Some(_) => {
+                let message = token_error(&response).unwrap_or_default();
+                return Err(format!("Login failed: {}", message).into());
+            }
+            None => {
+                let token: TokenResponse = serde_json::from_value(response)?;
+                store(profile, &token)?;
+                return Ok(token);
+            }
+        }
+    }
+}
+
+// Logs in as the username of the org with an assertion signed by the key of the connected
+// app, whose audience is the login URL of the org. Nothing is stored, every session signs
+// a new assertion.
+pub async fn login_with_jwt(profile: &Profile) -> Result<TokenResponse, DynError> {
+    let key_path = profile
+        .jwt_key
+        .as_ref()
+        .ok_or("No jwt_key is set for the org")?;
+    let key = fs::read(key_path)
+        .map_err(|e| format!("Failed to read the JWT key {}: {}", key_path.display(), e))?;
+    // SFDC_USERNAME belongs to the org without a profile
+    let username = match (&profile.username, &profile.name) {
+        (Some(username), _) => username.clone(),
+        (None, None) => env::var("SFDC_USERNAME")
+            .map_err(|_| "Set SFDC_USERNAME to log in with the JWT bearer flow")?,
+        (None, Some(org)) => {
+            return Err(format!("Set the username of {} to log in with its jwt_key", org).into())
+        }
+    };
+    let expires_at = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() + JWT_LIFETIME_SECS;
+    let assertion = jwt_assertion(
+        &client_id(profile)?,
+        &username,
+        login_url(profile),
+        expires_at,
+        &key,
+    )?;
+    request_token(
+        profile,
+        &[
+            ("grant_type", "urn:ietf:params:oauth:grant-type:jwt-bearer"),
+            ("assertion", &assertion),
+        ],
+    )
+    .await
+}
+
+// A JWT signed with RS256, e.g. header.claims.signature in base64url.
+fn jwt_assertion(
+    client_id: &str,
+    username: &str,
+    audience: &str,
+    expires_at: u64,
+    key_pem: &[u8],
+) -> Result<String, DynError> {
+    let header = URL_SAFE_NO_PAD.encode(r#"{"alg":"RS256"}"#);
+    let claims = serde_json::json!({
+        "iss": client_id,
+        "sub": username,
+        "aud": audience,
+        "exp": expires_at,
+    });
+    let claims = URL_SAFE_NO_PAD.encode(claims.to_string());
+    let signing_input = format!("{}.{}", header, claims);
+
+    let key = PKey::private_key_from_pem(key_pem)
+        .map_err(|e| format!("The JWT key is not a PEM private key: {}", e))?;
+    let mut signer = Signer::new(MessageDigest::sha256(), &key)?;
+    signer.update(signing_input.as_bytes())?;
+    let signature = URL_SAFE_NO_PAD.encode(signer.sign_to_vec()?);
+    Ok(format!("{}.{}", signing_input, signature))
+}
+
+// A new access token for the session kept by `soql-generator login`.
+pub async fn refresh(profile: &Profile, stored: &StoredToken) -> Result<TokenResponse, DynError> {
+    let client_id = client_id(profile)?;
+    let mut form = vec![
+        ("grant_type", "refresh_token"),
+        ("client_id", &client_id),
+        ("refresh_token", &stored.refresh_token),
+    ];
+    let client_secret = client_secret(profile)?;
+    if let Some(client_secret) = &client_secret {
+        form.push(("client_secret", client_secret));
+    }
+    request_token(profile, &form)
+        .await
+        .map_err(|e| format!("{}, run `{}` again", e, login_command(profile)).into())
+}
+
+// How to log in to the org, for error messages.
+pub fn login_command(profile: &Profile) -> String {
+    match &profile.name {
+        Some(org) => format!("soql-generator --org {} login", org),
+        None => String::from("soql-generator login"),
+    }
+}
+
+fn store(profile: &Profile, token: &TokenResponse) -> Result<(), DynError> {
+    let refresh_token = token
+        .refresh_token
+        .clone()
+        .ok_or("Login failed: no refresh token was returned, add the refresh_token scope to the connected app")?;
+    save_secret(
+        profile,
+        SESSION_ENTRY,
+        &StoredToken {
+            refresh_token,
+            instance_url: token.instance_url.clone(),
+        },
+    )
+}
+
+async fn request_token(
+    profile: &Profile,
+    form: &[(&str, &str)],
+) -> Result<TokenResponse, DynError> {
+    let response = post_form(profile, form).await?;
+    match token_error(&response) {
+        Some(message) => Err(format!("Login failed: {}", message).into()),
+        None => Ok(serde_json::from_value(response)?),
+    }
+}
+
+async fn post_form(profile: &Profile, form: &[(&str, &str)]) -> Result<Value, DynError> {
+    let response = http_client(profile)?
+        .post(oauth_url(profile, "token"))
+        .form(form)
+        .send()
+        .await?;
+    let status = response.status();
+    // the error responses are read by the callers, e.g. authorization_pending
+    Ok(parse_json(status, &response.text().await?)?)
+}
+
+fn token_error(response: &Value) -> Option<String> {
+    let error = response["error"].as_str()?;
+    Some(
+        response["error_description"]
+            .as_str()
+            .unwrap_or(error)
+            .to_string(),
+    )
+}
+
+fn random_string(length: usize) -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(length)
+        .map(char::from)
+        .collect()
+}
+
+// The S256 code challenge of RFC 7636 for a code verifier.
+fn code_challenge(verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()))
+}
+
+// The query parameters of a request to the callback URL carrying the state of this login,
+// e.g. `GET /OauthRedirect?code=aPrx...&state=abc HTTP/1.1`.
+fn callback_params(request: &str, state: &str) -> Option<HashMap<String, String>> {
+    let target = request.lines().next()?.split_whitespace().nth(1)?;
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let authority = REDIRECT_URI
+        .split_once("://")
+        .map_or(REDIRECT_URI, |(_, rest)| rest);
+    let redirect_path = authority.find('/').map_or("/", |idx| &authority[idx..]);
+    if path != redirect_path {
+        return None;
+    }
+    let params: HashMap<String, String> = query
+        .split('&')
+        .filter_map(|param| param.split_once('='))
+        .filter_map(|(name, value)| {
+            let value = decode(&value.replace('+', " ")).ok()?.into_owned();
+            Some((name.to_string(), value))
+        })
+        .collect();
+    if params.get("state").map(String::as_str) != Some(state) {
+        return None;
+    }
+    Some(params)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use openssl::rsa::Rsa;
+    use openssl::sign::Verifier;
+
+    #[test]
+    fn test_jwt_assertion() {
+        let rsa = Rsa::generate(2048).unwrap();
+        let key = PKey::from_rsa(rsa).unwrap();
+        let assertion = jwt_assertion(
+            "3MVG9",
+            "me@example.com",
+            "https://test.salesforce.com",
+            1700000180,
+            &key.private_key_to_pem_pkcs8().unwrap(),
+        )
+        .unwrap();
+
+        let parts: Vec<&str> = assertion.split('.').collect();
+        assert_eq!(parts.len(), 3);
+        let claims: Value =
+            serde_json::from_slice(&URL_SAFE_NO_PAD.decode(parts[1]).unwrap()).unwrap();
+        assert_eq!(
+            claims,
+            serde_json::json!({
+                "iss": "3MVG9",
+                "sub": "me@example.com",
+                "aud": "https://test.salesforce.com",
+                "exp": 1700000180,
+            })
+        );
+        let mut verifier = Verifier::new(MessageDigest::sha256(), &key).unwrap();
+        verifier
+            .update(format!("{}.{}", parts[0], parts[1]).as_bytes())
+            .unwrap();
+        assert!(verifier
+            .verify(&URL_SAFE_NO_PAD.decode(parts[2]).unwrap())
+            .unwrap());
+
+        assert!(jwt_assertion("3MVG9", "me", "aud", 0, b"not a key").is_err());
+    }
+
+    #[test]
+    fn test_code_challenge() {
+        // base64url without padding of the SHA-256 digest
+        assert_eq!(
+            code_challenge("dBjftJeZ4CVP-mJ92K1kqGZJdCnr6oA_7a4DCAMH3i8"),
+            "7njStJ7S3IdgKocnZWHDmAUfFib8goP6Kd5B5Ud6EVY"
+        );
+    }
+
+    #[test]
+    fn test_callback_params() {
+        let params = callback_params(
+            "GET /OauthRedirect?code=aPrx%3D%3D&state=abc HTTP/1.1\r\nHost: localhost:1717\r\n\r\n",
+            "abc",
+        )
+        .unwrap();
+        assert_eq!(params["code"], "aPrx==");
+        assert_eq!(params["state"], "abc");
+
+        let params = callback_params(
+            "GET /OauthRedirect?error=access_denied&error_description=end-user+denied+authorization&state=abc HTTP/1.1\r\n",
+            "abc",
+        )
+        .unwrap();
+        assert_eq!(params["error_description"], "end-user denied authorization");
+
+        // an error without the state of this login does not abort it
+        assert!(
+            callback_params("GET /OauthRedirect?error=access_denied HTTP/1.1\r\n", "abc").is_none()
+        );
+        assert!(callback_params(
+            "GET /OauthRedirect?error=access_denied&state=xyz HTTP/1.1\r\n",
+            "abc"
+        )
+        .is_none());
+        // only the path of the redirect URI itself
+        for path in ["/", "/Redirect", "/OauthRedirect/favicon.ico"] {
+            let request = format!("GET {}?code=a&state=abc HTTP/1.1\r\n", path);
+            assert!(callback_params(&request, "abc").is_none(), "{}", path);
+        }
+
+        assert!(callback_params("GET /favicon.ico HTTP/1.1\r\n", "abc").is_none());
+    }
+}
@@ -1,5 +1,7 @@
 use crate::helper::DynError;
 
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Nonce};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -8,31 +10,283 @@ use std::path::{Path, PathBuf};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CacheData {
-    pub objects: Vec<String>,
-    pub object_fields: HashMap<String, Vec<String>>,
+    pub objects: Vec<SObject>,
+    pub object_fields: HashMap<String, Vec<Field>>,
+    pub child_relationships: HashMap<String, Vec<ChildRelationship>>,
+    // the org and the API version the metadata was described from
+    pub org_id: Option<String>,
+    pub api_version: String,
     pub last_cached: DateTime<Utc>,
 }
 
-const CACHE_EXPIRATION_DAYS: i64 = 7;
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SObject {
+    pub name: String,
+    #[serde(default)]
+    pub label: String,
+    #[serde(default)]
+    pub key_prefix: Option<String>,
+    // false for objects SOQL cannot select from, e.g. feeds, share tables and histories
+    pub queryable: bool,
+    // custom objects, platform events, custom metadata types and the like
+    pub custom: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Field {
+    pub name: String,
+    #[serde(default)]
+    pub label: String,
+    #[serde(rename = "type")]
+    pub field_type: String,
+    #[serde(default)]
+    pub picklist_values: Vec<PicklistValue>,
+    #[serde(default)]
+    pub reference_to: Vec<String>,
+    // the name of the referenced record in field paths, e.g. Owner for OwnerId
+    #[serde(default)]
+    pub relationship_name: Option<String>,
+    #[serde(default)]
+    pub createable: bool,
+    #[serde(default)]
+    pub updateable: bool,
+    // required so that a cache written before they were kept is described again
+    pub length: u64,
+    pub nillable: bool,
+    pub filterable: bool,
+    pub sortable: bool,
+}
+
+// The child records of an object that a subquery selects, e.g. Contacts of Account.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChildRelationship {
+    #[serde(rename = "childSObject")]
+    pub child_object: String,
+    pub field: String,
+    // None for relationships that cannot be queried
+    pub relationship_name: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PicklistValue {
+    pub value: String,
+    // shown in the UI in place of the value, in the language of the user
+    #[serde(default)]
+    pub label: Option<String>,
+    pub active: bool,
+}
+
+pub const CACHE_EXPIRATION_DAYS: i64 = 7;
+
+// Length of the AES-GCM nonce written before an encrypted cache.
+const NONCE_LENGTH: usize = 12;
+
+// The AES-256 key of an encrypted cache, kept in the keyring.
+pub type CacheKey = [u8; 32];
+
+// The object a relationship of `object` points to, e.g. User for Owner of Account. A
+// polymorphic relationship is taken to point to the first object it can refer to.
+pub fn parent_object<'a>(
+    object_fields: &'a HashMap<String, Vec<Field>>,
+    object: &str,
+    relationship: &str,
+) -> Option<&'a str> {
+    object_fields
+        .get(object)?
+        .iter()
+        .find(|field| {
+            field
+                .relationship_name
+                .as_deref()
+                .is_some_and(|name| name.eq_ignore_ascii_case(relationship))
+        })?
+        .reference_to
+        .first()
+        .map(String::as_str)
+}
 
+// With a key the cache is written encrypted, as the nonce followed by the ciphertext.
 pub fn save_cache_to_file(
     cache_data: &CacheData,
     cache_data_path: &PathBuf,
+    key: Option<&CacheKey>,
 ) -> Result<(), DynError> {
-    let json = serde_json::to_string(cache_data)?;
-    fs::write(cache_data_path, json)?;
+    let json = serde_json::to_vec(cache_data)?;
+    let bytes = match key {
+        Some(key) => {
+            let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+            let ciphertext = Aes256Gcm::new(key.into())
+                .encrypt(&nonce, json.as_slice())
+                .map_err(|_| "Failed to encrypt the cache")?;
+            [nonce.as_slice(), &ciphertext].concat()
+        }
+        None => json,
+    };
+    fs::write(cache_data_path, bytes)?;
     Ok(())
 }
 
-pub fn load_cache_from_file(cache_data_path: &PathBuf) -> Result<Option<CacheData>, DynError> {
+// None when the bytes were not encrypted with the key, e.g. a cache written before
+// encrypt_cache was turned on.
+fn decrypt(bytes: &[u8], key: &CacheKey) -> Option<Vec<u8>> {
+    if bytes.len() < NONCE_LENGTH {
+        return None;
+    }
+    let (nonce, ciphertext) = bytes.split_at(NONCE_LENGTH);
+    Aes256Gcm::new(key.into())
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .ok()
+}
+
+fn read_cache(path: &Path, key: Option<&CacheKey>) -> Result<Option<CacheData>, DynError> {
+    let bytes = fs::read(path)?;
+    let json = match key {
+        Some(key) => match decrypt(&bytes, key) {
+            Some(json) => json,
+            None => return Ok(None),
+        },
+        None => bytes,
+    };
+    Ok(serde_json::from_slice(&json).ok())
+}
+
+impl CacheData {
+    pub fn is_expired(&self) -> bool {
+        (Utc::now() - self.last_cached).num_days() > CACHE_EXPIRATION_DAYS
+    }
+}
+
+// Returns an expired cache as well, see CacheData::is_expired.
+pub fn load_cache_from_file(
+    cache_data_path: &PathBuf,
+    key: Option<&CacheKey>,
+) -> Result<Option<CacheData>, DynError> {
     if Path::new(&cache_data_path).exists() {
-        let json = fs::read_to_string(cache_data_path)?;
-        let cache_data: CacheData = serde_json::from_str(&json)?;
+        // a cache written by an older version or with another key is rebuilt rather
+        // than rejected
+        return read_cache(cache_data_path, key);
+    }
+    Ok(None)
+}
+
+// The schema bundle written by `\schema pull` does not expire, it is what offline mode
+// works from.
+pub fn load_schema_bundle(path: &Path, key: Option<&CacheKey>) -> Result<CacheData, DynError> {
+    if !path.exists() {
+        return Err(format!(
+            "No schema bundle at {}, run \\schema pull while online first",
+            path.display()
+        )
+        .into());
+    }
+    read_cache(path, key)?.ok_or_else(|| {
+        format!(
+            "Invalid schema bundle {}, run \\schema pull again",
+            path.display()
+        )
+        .into()
+    })
+}
+
+// Objects and fields are sorted by name so that exports of an unchanged org are identical.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SchemaSnapshot<'a> {
+    last_cached: DateTime<Utc>,
+    objects: Vec<ObjectSchema<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ObjectSchema<'a> {
+    name: &'a str,
+    key_prefix: Option<&'a str>,
+    fields: Vec<&'a Field>,
+    child_relationships: Vec<&'a ChildRelationship>,
+}
+
+pub fn export_schema(cache_data: &CacheData, out: &Path) -> Result<(), DynError> {
+    let mut objects: Vec<ObjectSchema> = cache_data
+        .objects
+        .iter()
+        .map(|object| {
+            let mut fields: Vec<&Field> = cache_data
+                .object_fields
+                .get(&object.name)
+                .map_or_else(Vec::new, |fields| fields.iter().collect());
+            fields.sort_by(|a, b| a.name.cmp(&b.name));
+            let mut child_relationships: Vec<&ChildRelationship> = cache_data
+                .child_relationships
+                .get(&object.name)
+                .map_or_else(Vec::new, |relationships| relationships.iter().collect());
+            child_relationships.sort_by(|a, b| {
+                (&a.relationship_name, &a.child_object, &a.field).cmp(&(
+                    &b.relationship_name,
+                    &b.child_object,
+                    &b.field,
+                ))
+            });
+
+            ObjectSchema {
+                name: &object.name,
+                key_prefix: object.key_prefix.as_deref(),
+                fields,
+                child_relationships,
+            }
+        })
+        .collect();
+    objects.sort_by(|a, b| a.name.cmp(b.name));
 
-        let now = Utc::now();
-        if (now - cache_data.last_cached).num_days() <= CACHE_EXPIRATION_DAYS {
-            return Ok(Some(cache_data));
+    let snapshot = SchemaSnapshot {
+        last_cached: cache_data.last_cached,
+        objects,
+    };
+    fs::write(out, serde_json::to_string_pretty(&snapshot)?)?;
+    Ok(())
+}
+
+// Describes what changed between two snapshots of the org metadata, one line per change.
+pub fn diff_schema(old: &CacheData, new: &CacheData) -> Vec<String> {
+    let mut changes = Vec::new();
+    let no_fields = Vec::new();
+
+    for object in &new.objects {
+        if !old.objects.iter().any(|o| o.name == object.name) {
+            changes.push(format!("{} added", object.name));
         }
     }
-    Ok(None)
+    for object in &old.objects {
+        if !new.objects.iter().any(|o| o.name == object.name) {
+            changes.push(format!("{} removed", object.name));
+            continue;
+        }
+
+        let old_fields = old.object_fields.get(&object.name).unwrap_or(&no_fields);
+        let new_fields = new.object_fields.get(&object.name).unwrap_or(&no_fields);
+        for field in new_fields {
+            match old_fields.iter().find(|f| f.name == field.name) {
+                None => changes.push(format!("{}.{} added", object.name, field.name)),
+                Some(old_field) if old_field.field_type != field.field_type => {
+                    changes.push(format!(
+                        "{}.{} type changed: {} -> {}",
+                        object.name, field.name, old_field.field_type, field.field_type
+                    ))
+                }
+                Some(old_field) if old_field != field => {
+                    changes.push(format!("{}.{} changed", object.name, field.name))
+                }
+                Some(_) => {}
+            }
+        }
+        for field in old_fields {
+            if !new_fields.iter().any(|f| f.name == field.name) {
+                changes.push(format!("{}.{} removed", object.name, field.name));
+            }
+        }
+    }
+
+    changes
 }
@@ -0,0 +1,105 @@
+use crate::output::value_at;
+use crate::result_schema::{infer_columns, ColumnType};
+use serde_json::Value;
+use std::collections::HashMap;
+use unicode_width::UnicodeWidthStr;
+
+const BAR: &str = "█";
+
+// Renders a grouped result as horizontal bars, one per record, scaled to fit `width`
+// terminal columns. The value is the last numeric column, usually the aggregate, and
+// the other columns label the bar, e.g. select(StageName, count(Id)).groupby(StageName).
+pub fn render_bar_chart(
+    records: &[Value],
+    fields: &[String],
+    width: usize,
+) -> Result<String, String> {
+    let columns = infer_columns(records, &HashMap::new(), fields);
+    let value_column = columns
+        .iter()
+        .rposition(|c| matches!(c.column_type, ColumnType::Integer | ColumnType::Decimal))
+        .filter(|_| columns.len() > 1)
+        .ok_or("\\chart needs a grouped result with one aggregate, e.g. Opportunity.select(StageName, count(Id)).groupby(StageName)")?;
+
+    let bars: Vec<(String, f64)> = records
+        .iter()
+        .map(|record| {
+            let label = columns
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| *i != value_column)
+                .map(|(_, column)| match value_at(record, &column.name) {
+                    Some(Value::String(s)) => s.clone(),
+                    None | Some(Value::Null) => String::from("(null)"),
+                    Some(value) => value.to_string(),
+                })
+                .collect::<Vec<String>>()
+                .join(" / ");
+            let value = value_at(record, &columns[value_column].name)
+                .and_then(Value::as_f64)
+                .unwrap_or(0.0);
+            (label, value)
+        })
+        .collect();
+
+    let label_width = bars
+        .iter()
+        .map(|(label, _)| label.width())
+        .max()
+        .unwrap_or(0);
+    let value_width = bars
+        .iter()
+        .map(|(_, value)| value.to_string().len())
+        .max()
+        .unwrap_or(0);
+    let bar_width = width.saturating_sub(label_width + value_width + 4).max(1);
+    let max = bars.iter().map(|(_, value)| *value).fold(0.0, f64::max);
+
+    let lines: Vec<String> = bars
+        .iter()
+        .map(|(label, value)| {
+            let length = if max > 0.0 {
+                (value.max(0.0) / max * bar_width as f64).round() as usize
+            } else {
+                0
+            };
+            format!(
+                "{}{} | {} {}",
+                label,
+                " ".repeat(label_width - label.width()),
+                BAR.repeat(length),
+                value
+            )
+        })
+        .collect();
+
+    Ok(lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_render_bar_chart() {
+        let records = vec![
+            json!({ "attributes": { "type": "AggregateResult" }, "StageName": "Prospecting", "expr0": 10 }),
+            json!({ "attributes": { "type": "AggregateResult" }, "StageName": "Closed Won", "expr0": 5 }),
+            json!({ "attributes": { "type": "AggregateResult" }, "StageName": null, "expr0": 0 }),
+        ];
+
+        assert_eq!(
+            render_bar_chart(&records, &[String::from("StageName")], 27).unwrap(),
+            [
+                "Prospecting | ██████████ 10",
+                "Closed Won  | █████ 5",
+                "(null)      |  0",
+            ]
+            .join("\n")
+        );
+
+        let records = vec![json!({ "attributes": { "type": "Account" }, "Name": "Acme" })];
+        assert!(render_bar_chart(&records, &[], 30).is_err());
+    }
+}
@@ -1,22 +1,187 @@
 mod ast;
+mod autocorrect;
+mod graphql;
+mod help;
+mod labels;
 mod lexer;
 mod parse;
 mod querygen;
+mod reverse;
 mod token;
+mod validate;
 
+use crate::cache::{Field, SObject};
+pub use crate::engine::ast::Dml;
+use crate::engine::autocorrect::autocorrect;
+pub use crate::engine::graphql::GraphqlQuery;
+pub use crate::engine::help::help;
+use crate::engine::labels::resolve_labels;
+pub use crate::engine::labels::{find_fields, find_labels, find_objects};
+pub use crate::engine::lexer::date_literals;
 use crate::engine::lexer::tokenize;
-use crate::engine::parse::Parser;
-use crate::engine::querygen::Query;
+pub use crate::engine::parse::{Incomplete, PartialProgram, UserIds};
+use crate::engine::parse::{ParseError, Parser, SourceError};
+pub use crate::engine::querygen::{Evaluation, Query};
+use crate::engine::token::{Token, TokenKind};
+use crate::engine::validate::{find_field, validate};
 use crate::helper::DynError;
+use chrono_tz::Tz;
+use std::collections::{HashMap, HashSet};
 
-pub fn build_query(expr: &str) -> Result<(String, bool), DynError> {
-    let tokens = tokenize(expr);
+pub fn build_query(
+    expr: &str,
+    objects: &[SObject],
+    object_fields: &HashMap<String, Vec<Field>>,
+    user_ids: &UserIds,
+    timezone: Tz,
+    autocorrect_methods: bool,
+) -> Result<(Query, Evaluation), DynError> {
+    let tokens = tokenize(expr).map_err(|e| SourceError::new(&e, expr, e.offset()))?;
+    let tokens = autocorrect(tokens, autocorrect_methods)
+        .map_err(|e| SourceError::new(&e, expr, e.offset))?;
+    let tokens = resolve_labels(tokens, objects, object_fields);
+    let date_fields = on_date_fields(&tokens, object_fields);
     let mut parser = Parser::new(tokens);
-    let program = parser.parse()?;
+    parser.user_ids = user_ids.clone();
+    parser.timezone = timezone;
+    parser.date_fields = date_fields;
+    let program = parser.parse().map_err(|e| located(e, expr))?;
+    let validation_warnings = validate(&program, objects, object_fields)?;
 
     let mut query = Query::default();
-    query.evaluate(program)?;
-    let generated_code = query.generate();
+    let mut evaluation = query.evaluate(program, object_fields)?;
+    evaluation.warnings.splice(0..0, validation_warnings);
 
-    Ok((generated_code, query.open_browser))
+    Ok((query, evaluation))
+}
+
+// The fields passed to on() that are date fields of the queried object or of its parents,
+// e.g. CloseDate, rather than datetime fields.
+fn on_date_fields(
+    tokens: &[Token],
+    object_fields: &HashMap<String, Vec<Field>>,
+) -> HashSet<String> {
+    let object = match tokens.first() {
+        Some(token) if token.kind == TokenKind::Identifire => token.literal(),
+        _ => return HashSet::new(),
+    };
+    tokens
+        .iter()
+        .enumerate()
+        .filter(|(_, token)| token.kind == TokenKind::On)
+        .map(|(i, _)| {
+            // the field is written between the opening paren and the comma
+            tokens[i + 1..]
+                .iter()
+                .skip(1)
+                .take_while(|token| !matches!(token.kind, TokenKind::Comma | TokenKind::Eof))
+                .map(|token| token.literal())
+                .collect::<String>()
+        })
+        .filter(|path| {
+            find_field(object_fields, &object, path)
+                .is_some_and(|(_, field)| field.field_type == "date")
+        })
+        .collect()
+}
+
+// Returns the aliases passed to ownedby()/createdby() so they can be looked up
+// before the query is built.
+pub fn user_aliases(expr: &str) -> Vec<String> {
+    // a query that does not lex is reported by build_query
+    tokenize(expr)
+        .unwrap_or_default()
+        .windows(3)
+        .filter(|w| {
+            matches!(w[0].kind, TokenKind::Ownedby | TokenKind::Createdby)
+                && w[2].kind == TokenKind::StringObject
+        })
+        .map(|w| w[2].literal())
+        .collect()
+}
+
+// Best-effort parse of the line being typed, for completion. None when the line
+// cannot be parsed even as an incomplete query.
+pub fn parse_partial(line: &str) -> Option<PartialProgram> {
+    let tokens = tokenize(line).ok()?;
+    Parser::new(tokens).parse_partial().ok()
+}
+
+// Converts SOQL into the DSL and checks that the result parses back.
+pub fn from_soql(soql: &str) -> Result<String, DynError> {
+    let dsl = reverse::to_dsl(soql)?;
+
+    let tokens = tokenize(&dsl).map_err(|e| format!("Unsupported SOQL: {} ({})", e, dsl))?;
+    let mut parser = Parser::new(tokens);
+    // mine() only needs a user to parse, the one running the query fills it in later
+    parser.user_ids.current = Some(String::new());
+    if let Err(e) = parser.parse() {
+        return Err(format!("Unsupported SOQL: {} ({})", e, dsl).into());
+    }
+
+    Ok(dsl)
+}
+
+fn located(error: ParseError, expr: &str) -> DynError {
+    match error.offset() {
+        Some(offset) => Box::new(SourceError::new(&error, expr, offset)),
+        None => Box::new(error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_on_date_fields() {
+        let field = |name: &str, field_type: &str| Field {
+            name: name.to_string(),
+            field_type: field_type.to_string(),
+            ..Default::default()
+        };
+        let mut account = field("AccountId", "reference");
+        account.reference_to = vec![String::from("Account")];
+        account.relationship_name = Some(String::from("Account"));
+        let object_fields = HashMap::from([
+            (
+                String::from("Opportunity"),
+                vec![
+                    field("CloseDate", "date"),
+                    field("CreatedDate", "datetime"),
+                    account,
+                ],
+            ),
+            (
+                String::from("Account"),
+                vec![field("LastActivityDate", "date")],
+            ),
+        ]);
+        let build = |expr: &str| {
+            let timezone = "Asia/Tokyo".parse().unwrap();
+            let (query, _) = build_query(
+                expr,
+                &[],
+                &object_fields,
+                &UserIds::default(),
+                timezone,
+                false,
+            )
+            .unwrap();
+            query.generate()
+        };
+
+        assert_eq!(
+            build("Opportunity.on(CloseDate, 2024-05-01)"),
+            "SELECT Id FROM Opportunity WHERE CloseDate = 2024-05-01"
+        );
+        assert_eq!(
+            build("Opportunity.on(Account.LastActivityDate, 2024-05-01)"),
+            "SELECT Id FROM Opportunity WHERE Account.LastActivityDate = 2024-05-01"
+        );
+        assert_eq!(
+            build("Opportunity.on(CreatedDate, 2024-05-01)"),
+            "SELECT Id FROM Opportunity WHERE (CreatedDate >= 2024-04-30T15:00:00Z AND CreatedDate < 2024-05-01T15:00:00Z)"
+        );
+    }
 }
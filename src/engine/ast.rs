@@ -1,22 +1,36 @@
 use crate::engine::token::{Token, TokenKind};
 use core::fmt::Debug;
+use serde_json::Map;
 use std::any::Any;
 
 pub enum NodeType {
     Program,
     Table,
     SelectStatement,
+    SelectAllStatement,
     WhereStatement,
     GroupByStatement,
     OrderByStatement,
     LimitStatement,
     OpenStatement,
+    OpenQueryStatement,
+    OutStatement,
+    DistinctStatement,
+    AllStatement,
+    BulkStatement,
+    ExplainStatement,
+    CountStatement,
+    DmlStatement,
+    WithCountStatement,
+    ListViewStatement,
     FieldLiteral,
     OrderByOptionLiteral,
     IntegerLiteral,
-    StringLiteral,
     BooleanLiteral,
     Value,
+    InList,
+    ValueList,
+    LikePattern,
     PrefixExpression,
     InfixExpression,
     Condition,
@@ -30,11 +44,85 @@ pub trait Node: Any {
 }
 
 pub trait Statement: Node + Debug {
+    #[allow(dead_code)]
     fn statement_node(&self);
+
+    fn conditions(&self) -> Vec<&Condition> {
+        Vec::new()
+    }
+
+    // The selected fields in the order they were written.
+    fn fields(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    fn dml(&self) -> Option<Dml> {
+        None
+    }
+
+    fn graphql_filter(&self) -> Result<serde_json::Value, String> {
+        Err(format!(
+            "{} is not supported by the GraphQL backend",
+            self.string()
+        ))
+    }
+}
+
+// A change to records asked for by insert(), updateAll(), upsert() or delete() in place
+// of a query.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Dml {
+    Insert(Map<String, serde_json::Value>),
+    // the fields set on every record the query matches
+    Update(Map<String, serde_json::Value>),
+    // the external ID field and its value, and the other fields to set
+    Upsert(String, String, Map<String, serde_json::Value>),
+    Delete,
+}
+
+impl Dml {
+    pub fn method(&self) -> &str {
+        match self {
+            Dml::Insert(_) => "insert()",
+            Dml::Update(_) => "updateAll()",
+            Dml::Upsert(_, _, _) => "upsert()",
+            Dml::Delete => "delete()",
+        }
+    }
 }
 
 pub trait Expression: Node + Debug {
+    #[allow(dead_code)]
     fn expression_node(&self);
+
+    fn conditions(&self) -> Vec<&Condition> {
+        Vec::new()
+    }
+
+    fn value_kind(&self) -> Option<TokenKind> {
+        None
+    }
+
+    // The path of an in_file() list, read when the query is run.
+    fn in_file(&self) -> Option<String> {
+        None
+    }
+
+    // The condition as a filter of the GraphQL API, e.g. { Name: { eq: "Acme" } }.
+    fn graphql_filter(&self) -> Result<serde_json::Value, String> {
+        Err(format!(
+            "{} is not supported by the GraphQL backend",
+            self.string()
+        ))
+    }
+
+    // The value as a filter of the GraphQL API takes it.
+    fn graphql_value(&self) -> Result<serde_json::Value, String> {
+        Err(format!(
+            "{} is not supported by the GraphQL backend",
+            self.string()
+        ))
+    }
 }
 
 #[derive(Debug)]
@@ -82,7 +170,7 @@ pub struct Table {
 
 impl Node for Table {
     fn token_literal(&self) -> String {
-        self.table_name.clone()
+        self.token.literal()
     }
 
     fn string(&self) -> String {
@@ -121,6 +209,34 @@ impl Node for SelectStatement {
 
 impl Statement for SelectStatement {
     fn statement_node(&self) {}
+
+    fn fields(&self) -> Vec<String> {
+        self.fields.iter().map(|f| f.string()).collect()
+    }
+}
+
+#[derive(Debug)]
+pub struct SelectAllStatement {
+    pub token: Token,
+    pub filter: Option<String>,
+}
+
+impl Node for SelectAllStatement {
+    fn token_literal(&self) -> String {
+        self.token.literal()
+    }
+
+    fn string(&self) -> String {
+        self.filter.clone().unwrap_or_default()
+    }
+
+    fn node_type(&self) -> NodeType {
+        NodeType::SelectAllStatement
+    }
+}
+
+impl Statement for SelectAllStatement {
+    fn statement_node(&self) {}
 }
 
 #[derive(Debug)]
@@ -145,6 +261,14 @@ impl Node for WhereStatement {
 
 impl Statement for WhereStatement {
     fn statement_node(&self) {}
+
+    fn conditions(&self) -> Vec<&Condition> {
+        self.expression.conditions()
+    }
+
+    fn graphql_filter(&self) -> Result<serde_json::Value, String> {
+        self.expression.graphql_filter()
+    }
 }
 
 #[derive(Debug)]
@@ -244,36 +368,37 @@ impl Statement for OpenStatement {
 }
 
 #[derive(Debug)]
-pub struct IntegerLiteral {
+pub struct OutStatement {
     pub token: Token,
-    pub value: i64,
+    pub path: String,
 }
 
-impl Node for IntegerLiteral {
+impl Node for OutStatement {
     fn token_literal(&self) -> String {
         self.token.literal()
     }
 
     fn string(&self) -> String {
-        self.value.to_string()
+        self.path.clone()
     }
 
     fn node_type(&self) -> NodeType {
-        NodeType::IntegerLiteral
+        NodeType::OutStatement
     }
 }
 
-impl Expression for IntegerLiteral {
-    fn expression_node(&self) {}
+impl Statement for OutStatement {
+    fn statement_node(&self) {}
 }
 
 #[derive(Debug)]
-pub struct FieldLiteral {
+pub struct ListViewStatement {
     pub token: Token,
+    // the label or API name of the list view
     pub name: String,
 }
 
-impl Node for FieldLiteral {
+impl Node for ListViewStatement {
     fn token_literal(&self) -> String {
         self.token.literal()
     }
@@ -283,21 +408,261 @@ impl Node for FieldLiteral {
     }
 
     fn node_type(&self) -> NodeType {
-        NodeType::FieldLiteral
+        NodeType::ListViewStatement
     }
 }
 
-impl Expression for FieldLiteral {
-    fn expression_node(&self) {}
+impl Statement for ListViewStatement {
+    fn statement_node(&self) {}
 }
 
 #[derive(Debug)]
-pub struct OrderByOptionLiteral {
+pub struct DistinctStatement {
+    pub token: Token,
+}
+
+impl Node for DistinctStatement {
+    fn token_literal(&self) -> String {
+        self.token.literal()
+    }
+
+    fn string(&self) -> String {
+        self.token_literal()
+    }
+
+    fn node_type(&self) -> NodeType {
+        NodeType::DistinctStatement
+    }
+}
+
+impl Statement for DistinctStatement {
+    fn statement_node(&self) {}
+}
+
+#[derive(Debug)]
+pub struct AllStatement {
+    pub token: Token,
+}
+
+impl Node for AllStatement {
+    fn token_literal(&self) -> String {
+        self.token.literal()
+    }
+
+    fn string(&self) -> String {
+        self.token_literal()
+    }
+
+    fn node_type(&self) -> NodeType {
+        NodeType::AllStatement
+    }
+}
+
+impl Statement for AllStatement {
+    fn statement_node(&self) {}
+}
+
+#[derive(Debug)]
+pub struct BulkStatement {
+    pub token: Token,
+}
+
+impl Node for BulkStatement {
+    fn token_literal(&self) -> String {
+        self.token.literal()
+    }
+
+    fn string(&self) -> String {
+        self.token_literal()
+    }
+
+    fn node_type(&self) -> NodeType {
+        NodeType::BulkStatement
+    }
+}
+
+impl Statement for BulkStatement {
+    fn statement_node(&self) {}
+}
+
+#[derive(Debug)]
+pub struct OpenQueryStatement {
+    pub token: Token,
+}
+
+impl Node for OpenQueryStatement {
+    fn token_literal(&self) -> String {
+        self.token.literal()
+    }
+
+    fn string(&self) -> String {
+        self.token_literal()
+    }
+
+    fn node_type(&self) -> NodeType {
+        NodeType::OpenQueryStatement
+    }
+}
+
+impl Statement for OpenQueryStatement {
+    fn statement_node(&self) {}
+}
+
+#[derive(Debug)]
+pub struct ExplainStatement {
+    pub token: Token,
+}
+
+impl Node for ExplainStatement {
+    fn token_literal(&self) -> String {
+        self.token.literal()
+    }
+
+    fn string(&self) -> String {
+        self.token_literal()
+    }
+
+    fn node_type(&self) -> NodeType {
+        NodeType::ExplainStatement
+    }
+}
+
+impl Statement for ExplainStatement {
+    fn statement_node(&self) {}
+}
+
+#[derive(Debug)]
+pub struct CountStatement {
+    pub token: Token,
+}
+
+impl Node for CountStatement {
+    fn token_literal(&self) -> String {
+        self.token.literal()
+    }
+
+    fn string(&self) -> String {
+        self.token_literal()
+    }
+
+    fn node_type(&self) -> NodeType {
+        NodeType::CountStatement
+    }
+}
+
+impl Statement for CountStatement {
+    fn statement_node(&self) {}
+}
+
+// insert(), updateAll(), upsert() or delete(), told apart by the token. The
+// assignments are in the order they were written, the external ID first for upsert().
+#[derive(Debug)]
+pub struct DmlStatement {
+    pub token: Token,
+    pub assignments: Vec<(String, serde_json::Value)>,
+}
+
+impl Node for DmlStatement {
+    fn token_literal(&self) -> String {
+        self.token.literal()
+    }
+
+    fn string(&self) -> String {
+        self.assignments
+            .iter()
+            .map(|(field, value)| format!("{} = {}", field, value))
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+
+    fn node_type(&self) -> NodeType {
+        NodeType::DmlStatement
+    }
+}
+
+impl Statement for DmlStatement {
+    fn statement_node(&self) {}
+
+    fn dml(&self) -> Option<Dml> {
+        let fields = |assignments: &[(String, serde_json::Value)]| {
+            assignments
+                .iter()
+                .cloned()
+                .collect::<Map<String, serde_json::Value>>()
+        };
+        match self.token.kind {
+            TokenKind::Insert => Some(Dml::Insert(fields(&self.assignments))),
+            TokenKind::UpdateAll => Some(Dml::Update(fields(&self.assignments))),
+            TokenKind::Upsert => {
+                let ((field, value), rest) = self.assignments.split_first()?;
+                let value = match value {
+                    serde_json::Value::String(value) => value.clone(),
+                    value => value.to_string(),
+                };
+                Some(Dml::Upsert(field.clone(), value, fields(rest)))
+            }
+            TokenKind::Delete => Some(Dml::Delete),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct WithCountStatement {
+    pub token: Token,
+    pub relationships: Vec<FieldLiteral>,
+}
+
+impl Node for WithCountStatement {
+    fn token_literal(&self) -> String {
+        self.token.literal()
+    }
+
+    fn string(&self) -> String {
+        let params: Vec<String> = self.relationships.iter().map(|f| f.string()).collect();
+        params.join(", ")
+    }
+
+    fn node_type(&self) -> NodeType {
+        NodeType::WithCountStatement
+    }
+}
+
+impl Statement for WithCountStatement {
+    fn statement_node(&self) {}
+}
+
+#[derive(Debug)]
+pub struct IntegerLiteral {
+    pub token: Token,
+    pub value: i64,
+}
+
+impl Node for IntegerLiteral {
+    fn token_literal(&self) -> String {
+        self.token.literal()
+    }
+
+    fn string(&self) -> String {
+        self.value.to_string()
+    }
+
+    fn node_type(&self) -> NodeType {
+        NodeType::IntegerLiteral
+    }
+}
+
+impl Expression for IntegerLiteral {
+    fn expression_node(&self) {}
+}
+
+#[derive(Debug, Clone)]
+pub struct FieldLiteral {
     pub token: Token,
     pub name: String,
 }
 
-impl Node for OrderByOptionLiteral {
+impl Node for FieldLiteral {
     fn token_literal(&self) -> String {
         self.token.literal()
     }
@@ -307,35 +672,35 @@ impl Node for OrderByOptionLiteral {
     }
 
     fn node_type(&self) -> NodeType {
-        NodeType::OrderByOptionLiteral
+        NodeType::FieldLiteral
     }
 }
 
-impl Expression for OrderByOptionLiteral {
+impl Expression for FieldLiteral {
     fn expression_node(&self) {}
 }
 
 #[derive(Debug)]
-pub struct StringLiteral {
+pub struct OrderByOptionLiteral {
     pub token: Token,
-    pub value: String,
+    pub name: String,
 }
 
-impl Node for StringLiteral {
+impl Node for OrderByOptionLiteral {
     fn token_literal(&self) -> String {
         self.token.literal()
     }
 
     fn string(&self) -> String {
-        self.value.clone()
+        self.name.clone()
     }
 
     fn node_type(&self) -> NodeType {
-        NodeType::StringLiteral
+        NodeType::OrderByOptionLiteral
     }
 }
 
-impl Expression for StringLiteral {
+impl Expression for OrderByOptionLiteral {
     fn expression_node(&self) {}
 }
 
@@ -361,6 +726,14 @@ impl Node for BooleanLiteral {
 
 impl Expression for BooleanLiteral {
     fn expression_node(&self) {}
+
+    fn value_kind(&self) -> Option<TokenKind> {
+        Some(self.token.kind.clone())
+    }
+
+    fn graphql_value(&self) -> Result<serde_json::Value, String> {
+        Ok(serde_json::Value::Bool(self.value))
+    }
 }
 
 #[derive(Debug)]
@@ -400,7 +773,11 @@ impl Node for Value {
 
     fn string(&self) -> String {
         match self.token.kind {
-            TokenKind::Identifire | TokenKind::StringObject => format!("\'{}\'", self.value),
+            TokenKind::Identifire | TokenKind::StringObject => {
+                format!("\'{}\'", escape_string(&self.value))
+            }
+            // raw('...') is passed through as already escaped SOQL
+            TokenKind::Raw => format!("\'{}\'", self.value),
             _ => self.value.clone(),
         }
     }
@@ -412,6 +789,196 @@ impl Node for Value {
 
 impl Expression for Value {
     fn expression_node(&self) {}
+
+    fn value_kind(&self) -> Option<TokenKind> {
+        match self.token.kind {
+            TokenKind::Raw => Some(TokenKind::StringObject),
+            _ => Some(self.token.kind.clone()),
+        }
+    }
+
+    // Dates are compared as { value: "2020-01-01" } and date literals as { literal: TODAY }
+    // or { range: { last_n_fiscal_years: 2 } }.
+    fn graphql_value(&self) -> Result<serde_json::Value, String> {
+        match self.token.kind {
+            TokenKind::Integer => serde_json::from_str(&self.value)
+                .map_err(|_| format!("Invalid number: {}", self.value)),
+            TokenKind::Null => Ok(serde_json::Value::Null),
+            TokenKind::Date => Ok(serde_json::json!({ "value": self.value })),
+            TokenKind::DateLiteral => match self.value.split_once(':') {
+                Some((range, n)) => {
+                    let n: u64 = n
+                        .parse()
+                        .map_err(|_| format!("Invalid date literal: {}", self.value))?;
+                    Ok(serde_json::json!({ "range": { range.to_lowercase(): n } }))
+                }
+                None => Ok(serde_json::json!({ "literal": self.value.to_uppercase() })),
+            },
+            _ => Ok(serde_json::Value::String(self.value.clone())),
+        }
+    }
+}
+
+fn escape_string(value: &str) -> String {
+    let mut escaped = String::new();
+    for c in value.chars() {
+        match c {
+            '\\' | '\'' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// The list of an in_file() condition. Until the file is read it stands in the query as
+// its placeholder, which is then replaced with the values as a SOQL IN list.
+#[derive(Debug)]
+pub struct InList {
+    pub token: Token,
+}
+
+impl InList {
+    pub fn render(values: &[String]) -> String {
+        let values: Vec<String> = values
+            .iter()
+            .map(|v| format!("\'{}\'", escape_string(v)))
+            .collect();
+        format!("({})", values.join(", "))
+    }
+
+    pub fn placeholder(path: &str) -> String {
+        format!("in_file('{}')", escape_string(path))
+    }
+}
+
+impl Node for InList {
+    fn token_literal(&self) -> String {
+        self.token.literal()
+    }
+
+    fn string(&self) -> String {
+        InList::placeholder(&self.token.literal())
+    }
+
+    fn node_type(&self) -> NodeType {
+        NodeType::InList
+    }
+}
+
+impl Expression for InList {
+    fn expression_node(&self) {}
+
+    fn value_kind(&self) -> Option<TokenKind> {
+        Some(TokenKind::StringObject)
+    }
+
+    fn in_file(&self) -> Option<String> {
+        Some(self.token.literal())
+    }
+
+    fn graphql_value(&self) -> Result<serde_json::Value, String> {
+        Ok(serde_json::Value::String(self.string()))
+    }
+}
+
+// The values of an IN, NOT IN or INCLUDES condition, e.g. ('Hot', 'Warm').
+#[derive(Debug)]
+pub struct ValueList {
+    pub values: Vec<Box<dyn Expression>>,
+}
+
+impl Node for ValueList {
+    // the first value, the one an ID prefix is checked on
+    fn token_literal(&self) -> String {
+        self.values
+            .first()
+            .map(|value| value.token_literal())
+            .unwrap_or_default()
+    }
+
+    fn string(&self) -> String {
+        let values: Vec<String> = self.values.iter().map(|value| value.string()).collect();
+        format!("({})", values.join(", "))
+    }
+
+    fn node_type(&self) -> NodeType {
+        NodeType::ValueList
+    }
+}
+
+impl Expression for ValueList {
+    fn expression_node(&self) {}
+
+    // the kind of the values when they are all of one kind
+    fn value_kind(&self) -> Option<TokenKind> {
+        let kind = self.values.first()?.value_kind();
+        match self.values.iter().all(|value| value.value_kind() == kind) {
+            true => kind,
+            false => None,
+        }
+    }
+
+    fn graphql_value(&self) -> Result<serde_json::Value, String> {
+        self.values
+            .iter()
+            .map(|value| value.graphql_value())
+            .collect()
+    }
+}
+
+// The value of a contains()/startswith()/endswith() helper. The user's text is matched
+// literally, so LIKE wildcards in it are escaped before the helper's own are added.
+#[derive(Debug)]
+pub struct LikePattern {
+    pub token: Token,
+    pub value: String,
+}
+
+impl Node for LikePattern {
+    fn token_literal(&self) -> String {
+        self.value.clone()
+    }
+
+    fn string(&self) -> String {
+        let escaped = escape_like(&self.value);
+        match self.token.kind {
+            TokenKind::Startswith => format!("'{}%'", escaped),
+            TokenKind::Endswith => format!("'%{}'", escaped),
+            _ => format!("'%{}%'", escaped),
+        }
+    }
+
+    fn node_type(&self) -> NodeType {
+        NodeType::LikePattern
+    }
+}
+
+impl Expression for LikePattern {
+    fn expression_node(&self) {}
+
+    fn value_kind(&self) -> Option<TokenKind> {
+        Some(TokenKind::StringObject)
+    }
+
+    fn graphql_value(&self) -> Result<serde_json::Value, String> {
+        let escaped = self.value.replace('%', "\\%").replace('_', "\\_");
+        let pattern = match self.token.kind {
+            TokenKind::Startswith => format!("{}%", escaped),
+            TokenKind::Endswith => format!("%{}", escaped),
+            _ => format!("%{}%", escaped),
+        };
+        Ok(serde_json::Value::String(pattern))
+    }
+}
+
+fn escape_like(value: &str) -> String {
+    escape_string(value).replace('%', "\\%").replace('_', "\\_")
 }
 
 #[derive(Debug)]
@@ -441,6 +1008,16 @@ impl Node for PrefixExpression {
 
 impl Expression for PrefixExpression {
     fn expression_node(&self) {}
+
+    fn value_kind(&self) -> Option<TokenKind> {
+        self.right.value_kind()
+    }
+
+    // a signed number
+    fn graphql_value(&self) -> Result<serde_json::Value, String> {
+        let number = format!("{}{}", self.operator, self.right.string());
+        serde_json::from_str(&number).map_err(|_| format!("Invalid number: {}", number))
+    }
 }
 
 #[derive(Debug)]
@@ -473,6 +1050,22 @@ impl Node for InfixExpression {
 
 impl Expression for InfixExpression {
     fn expression_node(&self) {}
+
+    fn conditions(&self) -> Vec<&Condition> {
+        let mut conditions = self.left.conditions();
+        conditions.extend(self.right.conditions());
+        conditions
+    }
+
+    fn graphql_filter(&self) -> Result<serde_json::Value, String> {
+        let operator = match self.token.kind {
+            TokenKind::Or => "or",
+            _ => "and",
+        };
+        Ok(serde_json::json!({
+            operator: [self.left.graphql_filter()?, self.right.graphql_filter()?]
+        }))
+    }
 }
 
 #[derive(Debug)]
@@ -504,4 +1097,46 @@ impl Node for Condition {
 
 impl Expression for Condition {
     fn expression_node(&self) {}
+
+    fn conditions(&self) -> Vec<&Condition> {
+        vec![self]
+    }
+
+    // Owner.Name = 'a' is nested as { Owner: { Name: { eq: "a" } } }.
+    fn graphql_filter(&self) -> Result<serde_json::Value, String> {
+        let operator = match self.operator.token.kind {
+            TokenKind::Eq => "eq",
+            TokenKind::NotEq => "ne",
+            TokenKind::Greater => "gt",
+            TokenKind::GreaterEq => "gte",
+            TokenKind::Less => "lt",
+            TokenKind::LessEq => "lte",
+            TokenKind::Like => "like",
+            TokenKind::In => "in",
+            TokenKind::NotIn => "nin",
+            TokenKind::Includes => "includes",
+            _ => {
+                return Err(format!(
+                    "{} is not supported by the GraphQL backend",
+                    self.operator.string()
+                ))
+            }
+        };
+        if !self
+            .field
+            .name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '.')
+        {
+            return Err(format!(
+                "{} is not supported by the GraphQL backend",
+                self.field.name
+            ));
+        }
+        let mut filter = serde_json::json!({ operator: self.value.graphql_value()? });
+        for name in self.field.name.rsplit('.') {
+            filter = serde_json::json!({ name: filter });
+        }
+        Ok(filter)
+    }
 }
@@ -0,0 +1,100 @@
+use crate::engine::lexer::search_keywords;
+use crate::engine::token::{Token, TokenKind};
+use std::{
+    error::Error,
+    fmt::{self, Display},
+};
+
+// Method names of other query builders and the methods they stand for.
+const TYPOS: [(&str, &str); 4] = [
+    ("order_by", "orderby"),
+    ("group_by", "groupby"),
+    ("sort", "orderby"),
+    ("take", "limit"),
+];
+
+#[derive(Debug, PartialEq)]
+pub struct Typo {
+    pub method: String,
+    pub suggestion: &'static str,
+    pub offset: usize,
+}
+
+impl Display for Typo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Unknown method {}(), did you mean {}()?",
+            self.method, self.suggestion
+        )
+    }
+}
+
+impl Error for Typo {}
+
+// Finds methods called by a name from TYPOS, e.g. `.order_by(Name)`. They are replaced
+// by the canonical method when `apply` is set, and reported as a Typo otherwise.
+pub fn autocorrect(mut tokens: Vec<Token>, apply: bool) -> Result<Vec<Token>, Typo> {
+    for i in 1..tokens.len().saturating_sub(1) {
+        let is_call = tokens[i - 1].kind == TokenKind::Dot
+            && tokens[i].kind == TokenKind::Identifire
+            && tokens[i + 1].kind == TokenKind::Lparen;
+        if !is_call {
+            continue;
+        }
+
+        let method = tokens[i].literal();
+        let suggestion = match TYPOS
+            .iter()
+            .find(|(typo, _)| typo.eq_ignore_ascii_case(&method))
+        {
+            Some((_, suggestion)) => *suggestion,
+            None => continue,
+        };
+        if !apply {
+            return Err(Typo {
+                method,
+                suggestion,
+                offset: tokens[i].offset(),
+            });
+        }
+        tokens[i] = search_keywords(suggestion).at(tokens[i].offset());
+    }
+
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::lexer::tokenize;
+
+    #[test]
+    fn test_autocorrect() {
+        let tokens = tokenize("Account.where(sort = 1).sort(Name).take(5)").unwrap();
+        assert_eq!(
+            autocorrect(tokens.clone(), false),
+            Err(Typo {
+                method: String::from("sort"),
+                suggestion: "orderby",
+                offset: 24,
+            })
+        );
+
+        let kinds: Vec<TokenKind> = autocorrect(tokens, true)
+            .unwrap()
+            .into_iter()
+            .map(|t| t.kind)
+            .collect();
+        assert!(kinds.contains(&TokenKind::Orderby));
+        assert!(kinds.contains(&TokenKind::Limit));
+        // case: a field named sort is left alone
+        assert!(kinds.contains(&TokenKind::Identifire));
+
+        let tokens = tokenize("Account.Order_By(Name)").unwrap();
+        assert_eq!(
+            autocorrect(tokens, false).unwrap_err().to_string(),
+            "Unknown method Order_By(), did you mean orderby()?"
+        );
+    }
+}
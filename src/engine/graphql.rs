@@ -0,0 +1,333 @@
+use serde_json::{json, Map, Value};
+
+// Most records a page of the GraphQL API holds
+const PAGE_SIZE: usize = 200;
+
+// A query of the Salesforce GraphQL API, which `--backend graphql` runs in place of the
+// SOQL. The records come back a page at a time behind a cursor.
+#[derive(Debug, PartialEq)]
+pub struct GraphqlQuery {
+    object: String,
+    document: String,
+    // the where filter, the first and after of every page are added to it
+    variables: Map<String, Value>,
+    limit: Option<usize>,
+    count: bool,
+}
+
+// One page of the records of a GraphQL query, in the shape of the REST API's records.
+#[derive(Debug, PartialEq)]
+pub struct Page {
+    pub records: Vec<Value>,
+    // the number of records the query matches, at most its limit
+    pub total_size: u64,
+    // where the next page starts, None when this page is the last one
+    pub cursor: Option<String>,
+}
+
+// Builds the query of `object`, selecting `fields` or Id when there are none. A
+// field of a parent record is selected through the relationship, e.g.
+// Account.Name as Account { Name { value } }.
+pub fn build(
+    object: &str,
+    fields: &[String],
+    filter: Option<Value>,
+    orderby: Option<&str>,
+    limit: Option<usize>,
+    count: bool,
+) -> Result<GraphqlQuery, String> {
+    check_name(object)?;
+    let mut parameters = Vec::new();
+    let mut arguments = Vec::new();
+    let mut variables = Map::new();
+    if let Some(filter) = filter {
+        parameters.push(format!("$where: {}_Filter", object));
+        arguments.push(String::from("where: $where"));
+        variables.insert(String::from("where"), filter);
+    }
+    // the fields are ordered by in the order they are written, so the argument is
+    // written in the document rather than as a variable
+    if let Some(orderby) = orderby {
+        arguments.push(format!("orderBy: {}", order_by(orderby)?));
+    }
+
+    let selection = if count {
+        String::from("totalCount")
+    } else {
+        parameters.push(String::from("$first: Int"));
+        parameters.push(String::from("$after: String"));
+        arguments.push(String::from("first: $first"));
+        arguments.push(String::from("after: $after"));
+        let default_fields = [String::from("Id")];
+        let fields = if fields.is_empty() {
+            &default_fields[..]
+        } else {
+            fields
+        };
+        let mut paths = Vec::new();
+        for field in fields {
+            check_name(field)?;
+            paths.push(field.split('.').collect::<Vec<&str>>());
+        }
+        format!(
+            "edges {{ node {{ {} }} }} pageInfo {{ hasNextPage endCursor }} totalCount",
+            selection(&paths)
+        )
+    };
+
+    let parameters = match parameters.is_empty() {
+        true => String::new(),
+        false => format!("({})", parameters.join(", ")),
+    };
+    let arguments = match arguments.is_empty() {
+        true => String::new(),
+        false => format!("({})", arguments.join(", ")),
+    };
+    Ok(GraphqlQuery {
+        object: object.to_string(),
+        document: format!(
+            "query Records{} {{ uiapi {{ query {{ {}{} {{ {} }} }} }} }}",
+            parameters, object, arguments, selection
+        ),
+        variables,
+        limit,
+        count,
+    })
+}
+
+impl GraphqlQuery {
+    // The request body for the page after `cursor`, when `fetched` records have been
+    // fetched before it.
+    pub fn request(&self, fetched: usize, cursor: Option<&str>) -> Value {
+        let mut variables = self.variables.clone();
+        if !self.count {
+            let first = match self.limit {
+                Some(limit) => limit.saturating_sub(fetched).min(PAGE_SIZE),
+                None => PAGE_SIZE,
+            };
+            variables.insert(String::from("first"), json!(first));
+            variables.insert(String::from("after"), json!(cursor));
+        }
+        json!({ "query": self.document, "variables": variables })
+    }
+
+    // Reads a page out of a response, with every field as a plain value instead of a
+    // { value } object like the REST API returns it.
+    pub fn read_page(&self, response: &Value, fetched: usize) -> Result<Page, String> {
+        if let Some(errors) = response["errors"].as_array().filter(|e| !e.is_empty()) {
+            let messages: Vec<&str> = errors
+                .iter()
+                .map(|e| e["message"].as_str().unwrap_or("unknown error"))
+                .collect();
+            return Err(messages.join(", "));
+        }
+        let connection = &response["data"]["uiapi"]["query"][&self.object];
+        let mut total_size = connection["totalCount"]
+            .as_u64()
+            .ok_or_else(|| format!("Unexpected response of the GraphQL API: {}", response))?;
+        if let Some(limit) = self.limit {
+            total_size = total_size.min(limit as u64);
+        }
+
+        let records: Vec<Value> = connection["edges"]
+            .as_array()
+            .map_or(&[][..], |edges| edges)
+            .iter()
+            .map(|edge| {
+                let mut record = json!({ "attributes": { "type": self.object } });
+                if let (Some(record), Value::Object(fields)) =
+                    (record.as_object_mut(), plain_values(&edge["node"]))
+                {
+                    record.extend(fields);
+                }
+                record
+            })
+            .collect();
+        let cursor = match connection["pageInfo"]["endCursor"].as_str() {
+            _ if connection["pageInfo"]["hasNextPage"] != true => None,
+            _ if (fetched + records.len()) as u64 >= total_size => None,
+            cursor => cursor.map(String::from),
+        };
+        Ok(Page {
+            records,
+            total_size,
+            cursor,
+        })
+    }
+}
+
+// Object and field names are written into the document, so anything else, e.g. an
+// aggregate function, is refused.
+fn check_name(name: &str) -> Result<(), String> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '.')
+    {
+        return Err(format!("{} is not supported by the GraphQL backend", name));
+    }
+    Ok(())
+}
+
+// Id is a plain string, the other fields hold their value in { value }.
+fn selection(paths: &[Vec<&str>]) -> String {
+    let mut names: Vec<&str> = Vec::new();
+    for path in paths {
+        if !names.contains(&path[0]) {
+            names.push(path[0]);
+        }
+    }
+    names
+        .iter()
+        .map(|name| {
+            let children: Vec<Vec<&str>> = paths
+                .iter()
+                .filter(|path| path[0] == *name && path.len() > 1)
+                .map(|path| path[1..].to_vec())
+                .collect();
+            if !children.is_empty() {
+                format!("{} {{ {} }}", name, selection(&children))
+            } else if *name == "Id" {
+                name.to_string()
+            } else {
+                format!("{} {{ value }}", name)
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+// Name, CreatedDate DESC as { Name: { order: ASC }, CreatedDate: { order: DESC } }.
+fn order_by(orderby: &str) -> Result<String, String> {
+    let mut orders = Vec::new();
+    for option in orderby.split(", ") {
+        let (field, order) = match option.rsplit_once(' ') {
+            Some((field, order)) => (field, order.to_uppercase()),
+            None => (option, String::from("ASC")),
+        };
+        check_name(field)?;
+        let mut order = format!("{{ order: {} }}", order);
+        for name in field.rsplit('.') {
+            order = format!("{{ {}: {} }}", name, order);
+        }
+        // the braces of the outermost object are shared by every field
+        orders.push(order[2..order.len() - 2].to_string());
+    }
+    Ok(format!("{{ {} }}", orders.join(", ")))
+}
+
+fn plain_values(node: &Value) -> Value {
+    match node {
+        Value::Object(fields) => Value::Object(
+            fields
+                .iter()
+                .map(|(name, value)| {
+                    let value = match value.as_object() {
+                        Some(field) if field.len() == 1 && field.contains_key("value") => {
+                            field["value"].clone()
+                        }
+                        _ => plain_values(value),
+                    };
+                    (name.clone(), value)
+                })
+                .collect(),
+        ),
+        value => value.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build() {
+        let query = build(
+            "Opportunity",
+            &[
+                String::from("Id"),
+                String::from("Name"),
+                String::from("Account.Name"),
+                String::from("Account.Owner.Name"),
+            ],
+            Some(json!({ "StageName": { "eq": "Prospecting" } })),
+            Some("CloseDate DESC, Account.Name"),
+            Some(300),
+            false,
+        )
+        .unwrap();
+        assert_eq!(
+            query.request(200, Some("abc")),
+            json!({
+                "query": "query Records($where: Opportunity_Filter, $first: Int, $after: String) { uiapi { query { Opportunity(where: $where, orderBy: { CloseDate: { order: DESC }, Account: { Name: { order: ASC } } }, first: $first, after: $after) { edges { node { Id Name { value } Account { Name { value } Owner { Name { value } } } } } pageInfo { hasNextPage endCursor } totalCount } } } }",
+                "variables": {
+                    "where": { "StageName": { "eq": "Prospecting" } },
+                    "first": 100,
+                    "after": "abc",
+                },
+            })
+        );
+
+        let query = build("Account", &[], None, None, None, true).unwrap();
+        assert_eq!(
+            query.request(0, None),
+            json!({
+                "query": "query Records { uiapi { query { Account { totalCount } } } }",
+                "variables": {},
+            })
+        );
+
+        assert!(build(
+            "Account",
+            &[String::from("count(Id)")],
+            None,
+            None,
+            None,
+            false
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_read_page() {
+        let query = build(
+            "Account",
+            &[String::from("Name"), String::from("Owner.Name")],
+            None,
+            None,
+            Some(3),
+            false,
+        )
+        .unwrap();
+        let response = json!({
+            "data": { "uiapi": { "query": { "Account": {
+                "edges": [
+                    { "node": { "Name": { "value": "Acme" }, "Owner": { "Name": { "value": "Kumac" } } } },
+                    { "node": { "Name": { "value": null }, "Owner": null } },
+                ],
+                "pageInfo": { "hasNextPage": true, "endCursor": "abc" },
+                "totalCount": 10,
+            } } } },
+        });
+        assert_eq!(
+            query.read_page(&response, 0).unwrap(),
+            Page {
+                records: vec![
+                    json!({ "attributes": { "type": "Account" }, "Name": "Acme", "Owner": { "Name": "Kumac" } }),
+                    json!({ "attributes": { "type": "Account" }, "Name": null, "Owner": null }),
+                ],
+                total_size: 3,
+                cursor: Some(String::from("abc")),
+            }
+        );
+        // the limit is reached
+        assert_eq!(query.read_page(&response, 1).unwrap().cursor, None);
+
+        let response =
+            json!({ "errors": [{ "message": "Validation error: Field 'Nme' is undefined" }] });
+        assert_eq!(
+            query.read_page(&response, 0).unwrap_err(),
+            "Validation error: Field 'Nme' is undefined"
+        );
+    }
+}
@@ -0,0 +1,171 @@
+use crate::engine::lexer::{date_literals, KEYWORDS};
+use crate::engine::token::{Token, TokenKind};
+
+// The arguments a query method takes and what it does.
+fn method_usage(kind: &TokenKind) -> Option<(&'static str, &'static str)> {
+    let usage = match kind {
+        TokenKind::Select => (
+            "Field, ...",
+            "the fields to query, Id when it is not called",
+        ),
+        TokenKind::Where => ("condition", "filter the records"),
+        TokenKind::AndWhere => ("condition", "add a condition with AND"),
+        TokenKind::OrWhere => ("condition", "add a condition with OR"),
+        TokenKind::Orderby => ("Field [ASC|DESC], ...", "sort the records"),
+        TokenKind::Groupby => ("Field, ...", "group the records for aggregates"),
+        TokenKind::Limit => ("n", "fetch at most n records"),
+        TokenKind::Open => ("", "open the first record in the browser"),
+        TokenKind::OpenQuery => ("", "open the SOQL in Workbench instead of running it"),
+        TokenKind::Out => ("'<file>'", "write the records to a file"),
+        TokenKind::Distinct => ("", "keep one record of each combination of values"),
+        TokenKind::All => ("", "fetch every batch of records"),
+        TokenKind::Bulk => ("", "run as a Bulk API 2.0 query job"),
+        TokenKind::Explain => ("", "show the query plans instead of running it"),
+        TokenKind::Count => ("", "print the number of matching records"),
+        TokenKind::Insert => ("Field = value, ...", "create a record"),
+        TokenKind::UpdateAll => ("Field = value, ...", "update every matching record"),
+        TokenKind::Upsert => (
+            "ExternalId = value, Field = value, ...",
+            "update or create a record",
+        ),
+        TokenKind::Delete => ("", "delete every matching record"),
+        TokenKind::Contains => ("Field, 'text'", "Field LIKE '%text%'"),
+        TokenKind::Startswith => ("Field, 'text'", "Field LIKE 'text%'"),
+        TokenKind::Endswith => ("Field, 'text'", "Field LIKE '%text'"),
+        TokenKind::Withcount => ("Relationship, ...", "count the child records"),
+        TokenKind::Listview => ("'<list view>'", "run the query of a list view"),
+        TokenKind::Mine => ("", "records owned by you"),
+        TokenKind::Ownedby => ("'<alias>'", "records owned by the user"),
+        TokenKind::Createdby => ("'<alias>'", "records created by the user"),
+        TokenKind::On => ("Field, YYYY-MM-DD", "records of that day in your timezone"),
+        _ => return None,
+    };
+    Some(usage)
+}
+
+// Operators written after a field in a condition, and their arguments.
+fn operator_usage(kind: &TokenKind) -> Option<&'static str> {
+    let usage = match kind {
+        TokenKind::And | TokenKind::Or | TokenKind::Like => "",
+        TokenKind::Between => "(low, high)",
+        TokenKind::IsNull | TokenKind::IsNotNull => "()",
+        TokenKind::In | TokenKind::Includes => "(value, ...)",
+        TokenKind::Not => " in(value, ...)",
+        TokenKind::InFile => "('<file>')",
+        _ => return None,
+    };
+    Some(usage)
+}
+
+// The first spelling of every keyword read as a token that matches.
+fn keywords(matches: impl Fn(&TokenKind) -> bool) -> Vec<(&'static str, &'static TokenKind)> {
+    let mut keywords: Vec<(&str, &TokenKind)> = Vec::new();
+    for (keyword, kind) in KEYWORDS.iter() {
+        if matches(kind) && !keywords.iter().any(|(_, k)| *k == kind) {
+            keywords.push((keyword, kind));
+        }
+    }
+    keywords
+}
+
+// The grammar of method chains for `help`, listing the methods, operators and literals
+// the lexer knows.
+pub fn help() -> String {
+    let mut lines = vec![
+        String::from("Queries are method chains on an object:"),
+        String::from("  <Object>.<method>(<arguments>).<method>(<arguments>)..."),
+        String::from(
+            "  e.g. Opportunity.select(Name, Account.Name).where(StageName = 'Prospecting' and Amount > 1000).limit(5)",
+        ),
+        String::new(),
+        String::from("Methods:"),
+    ];
+    let methods = keywords(|kind| Token::new(kind.clone(), String::new()).is_query_method());
+    let usages: Vec<(String, &str)> = methods
+        .iter()
+        .filter_map(|(keyword, kind)| {
+            method_usage(kind).map(|(arguments, description)| {
+                (format!("{}({})", keyword, arguments), description)
+            })
+        })
+        .collect();
+    let width = usages
+        .iter()
+        .map(|(usage, _)| usage.len())
+        .max()
+        .unwrap_or(0);
+    for (usage, description) in usages {
+        lines.push(format!(
+            "  {:width$}  {}",
+            usage,
+            description,
+            width = width
+        ));
+    }
+
+    let comparisons = [
+        TokenKind::Eq,
+        TokenKind::NotEq,
+        TokenKind::Greater,
+        TokenKind::GreaterEq,
+        TokenKind::Less,
+        TokenKind::LessEq,
+    ];
+    let mut operators: Vec<String> = comparisons.iter().map(|kind| kind.to_string()).collect();
+    operators.extend(
+        keywords(|kind| operator_usage(kind).is_some())
+            .iter()
+            .map(|(keyword, kind)| {
+                format!("{}{}", keyword, operator_usage(kind).unwrap_or_default())
+            }),
+    );
+    lines.push(String::new());
+    lines.push(format!("Operators: {}", operators.join("  ")));
+    lines.push(String::from(
+        "  e.g. Account.where(Name like 'Acme%' or (AnnualRevenue between(1000, 5000) and ParentId isNull()))",
+    ));
+
+    let values: Vec<&str> =
+        keywords(|kind| matches!(kind, TokenKind::True | TokenKind::False | TokenKind::Null))
+            .iter()
+            .map(|(keyword, _)| *keyword)
+            .collect();
+    lines.push(String::new());
+    lines.push(format!(
+        "Values: 'text', 123, -1, 2024-01-31, 2024-01-31T09:00:00Z, {}, raw('<SOQL value>')",
+        values.join(", ")
+    ));
+    let date_literals: Vec<String> = date_literals()
+        .map(|keyword| match keyword.contains("_N_") {
+            true => format!("{}:n", keyword),
+            false => keyword.to_string(),
+        })
+        .collect();
+    lines.push(format!("Date literals: {}", date_literals.join(", ")));
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_keyword_is_described() {
+        for (keyword, kind) in KEYWORDS.iter() {
+            if Token::new(kind.clone(), String::new()).is_query_method() {
+                assert!(method_usage(kind).is_some(), "{} has no usage", keyword);
+            }
+        }
+        let help = help();
+        assert!(help.contains("  select(Field, ...)"));
+        assert!(help.contains("andWhere(condition)"));
+        assert!(!help.contains("andwhere("));
+        assert!(help.contains("between(low, high)"));
+        assert!(help.contains("  not in(value, ...)  includes(value, ...)"));
+        assert!(help.contains("TODAY, TOMORROW"));
+        assert!(help.contains("LAST_N_FISCAL_YEARS:n"));
+        assert!(help.contains(
+            "Values: 'text', 123, -1, 2024-01-31, 2024-01-31T09:00:00Z, true, false, null, raw("
+        ));
+    }
+}
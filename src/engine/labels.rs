@@ -0,0 +1,269 @@
+use crate::cache::{Field, SObject};
+use crate::engine::token::{Token, TokenKind};
+use std::collections::HashMap;
+
+// Replaces object and field labels written in place of API names, e.g.
+// `商談.select(フェーズ)` becomes `Opportunity.select(StageName)`. Names that are
+// already API names, and labels shared by several objects or fields, are kept as
+// written. Labels are in the language of the user the metadata was cached for.
+pub fn resolve_labels(
+    mut tokens: Vec<Token>,
+    objects: &[SObject],
+    object_fields: &HashMap<String, Vec<Field>>,
+) -> Vec<Token> {
+    let object = match tokens.first() {
+        Some(token) if token.kind == TokenKind::Identifire => {
+            let name = token.literal();
+            if objects.iter().any(|o| o.name.eq_ignore_ascii_case(&name)) {
+                name
+            } else {
+                match unique(objects.iter().filter(|o| o.label == name).map(|o| &o.name)) {
+                    Some(api_name) => {
+                        tokens[0] = Token::new(TokenKind::Identifire, api_name.clone())
+                            .at(tokens[0].offset());
+                        api_name.clone()
+                    }
+                    None => return tokens,
+                }
+            }
+        }
+        _ => return tokens,
+    };
+    let fields = match object_fields.get(&object) {
+        Some(fields) => fields,
+        None => return tokens,
+    };
+
+    let mut method = TokenKind::Eof;
+    for i in 1..tokens.len() {
+        if tokens[i].is_query_method() {
+            method = tokens[i].kind.clone();
+        }
+        // relationship paths and the relationships of withcount() are not fields
+        let in_path = tokens[i - 1].kind == TokenKind::Dot
+            || tokens.get(i + 1).is_some_and(|t| t.kind == TokenKind::Dot);
+        if tokens[i].kind != TokenKind::Identifire || in_path || method == TokenKind::Withcount {
+            continue;
+        }
+
+        let name = tokens[i].literal();
+        if fields.iter().any(|f| f.name.eq_ignore_ascii_case(&name)) {
+            continue;
+        }
+        if let Some(api_name) = unique(fields.iter().filter(|f| f.label == name).map(|f| &f.name)) {
+            tokens[i] = Token::new(TokenKind::Identifire, api_name.clone()).at(tokens[i].offset());
+        }
+    }
+
+    tokens
+}
+
+// Objects and fields whose label contains `text`, ignoring case, as (API name, label)
+// pairs. Fields are named Object.Field.
+pub fn find_labels(
+    text: &str,
+    objects: &[SObject],
+    object_fields: &HashMap<String, Vec<Field>>,
+) -> Vec<(String, String)> {
+    let text = text.to_lowercase();
+    let matches = |label: &str| !label.is_empty() && label.to_lowercase().contains(&text);
+
+    let mut found: Vec<(String, String)> = objects
+        .iter()
+        .filter(|object| matches(&object.label))
+        .map(|object| (object.name.clone(), object.label.clone()))
+        .collect();
+
+    let mut names: Vec<&String> = object_fields.keys().collect();
+    names.sort();
+    for name in names {
+        found.extend(
+            object_fields[name]
+                .iter()
+                .filter(|field| matches(&field.label))
+                .map(|field| (format!("{}.{}", name, field.name), field.label.clone())),
+        );
+    }
+
+    found
+}
+
+// Objects whose API name or label matches a glob pattern, ignoring case, e.g. opp* or
+// *__c. Objects that cannot be queried are left out unless `all` is set.
+pub fn find_objects<'a>(pattern: &str, objects: &'a [SObject], all: bool) -> Vec<&'a SObject> {
+    objects
+        .iter()
+        .filter(|object| object.queryable || all)
+        .filter(|object| glob_match(pattern, &object.name) || glob_match(pattern, &object.label))
+        .collect()
+}
+
+// Fields whose API name or label matches a glob pattern, ignoring case, e.g. *phone*.
+pub fn find_fields<'a>(pattern: &str, fields: &'a [Field]) -> Vec<&'a Field> {
+    fields
+        .iter()
+        .filter(|field| glob_match(pattern, &field.name) || glob_match(pattern, &field.label))
+        .collect()
+}
+
+// `*` matches any run of characters and `?` any one character.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    // the positions after the last `*` and where its match stopped, to retry from
+    let (mut p, mut t) = (0, 0);
+    let mut retry: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                p += 1;
+                retry = Some((p, t));
+            }
+            Some(c) if *c == '?' || *c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match retry {
+                Some((after_star, matched)) => {
+                    p = after_star;
+                    t = matched + 1;
+                    retry = Some((after_star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+fn unique<'a>(mut names: impl Iterator<Item = &'a String>) -> Option<&'a String> {
+    let first = names.next()?;
+    match names.next() {
+        Some(_) => None,
+        None => Some(first),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::lexer::tokenize;
+
+    fn objects() -> Vec<SObject> {
+        [
+            ("Opportunity", "商談", true),
+            ("Account", "取引先", true),
+            ("OpportunityFeed", "Opportunity Feed", false),
+        ]
+        .iter()
+        .map(|(name, label, queryable)| SObject {
+            name: name.to_string(),
+            label: label.to_string(),
+            queryable: *queryable,
+            ..Default::default()
+        })
+        .collect()
+    }
+
+    fn object_fields() -> HashMap<String, Vec<Field>> {
+        let fields = [
+            ("StageName", "フェーズ"),
+            ("Amount", "金額"),
+            ("Name", "商談名"),
+            ("Description", "説明"),
+            ("NextStep", "説明"),
+        ]
+        .iter()
+        .map(|(name, label)| Field {
+            name: name.to_string(),
+            label: label.to_string(),
+            ..Default::default()
+        })
+        .collect();
+        HashMap::from([(String::from("Opportunity"), fields)])
+    }
+
+    fn resolve(input: &str) -> Vec<String> {
+        resolve_labels(tokenize(input).unwrap(), &objects(), &object_fields())
+            .iter()
+            .filter(|t| t.kind == TokenKind::Identifire)
+            .map(|t| t.literal())
+            .collect()
+    }
+
+    #[test]
+    fn test_resolve_labels() {
+        assert_eq!(
+            resolve(
+                "商談.select(商談名, Account.Name).where(フェーズ = 'Prospecting').orderby(金額)"
+            ),
+            vec![
+                "Opportunity",
+                "Name",
+                "Account",
+                "Name",
+                "StageName",
+                "Amount"
+            ]
+        );
+        // case: a label shared by two fields stays as written
+        assert_eq!(
+            resolve("Opportunity.select(説明)"),
+            vec!["Opportunity", "説明"]
+        );
+        assert_eq!(resolve("Lead.select(Name)"), vec!["Lead", "Name"]);
+    }
+
+    #[test]
+    fn test_find_labels() {
+        assert_eq!(
+            find_labels("商談", &objects(), &object_fields()),
+            vec![
+                (String::from("Opportunity"), String::from("商談")),
+                (String::from("Opportunity.Name"), String::from("商談名")),
+            ]
+        );
+        assert!(find_labels("Stage", &objects(), &object_fields()).is_empty());
+    }
+
+    #[test]
+    fn test_find_objects() {
+        let objects = objects();
+        let names = |pattern: &str, all: bool| -> Vec<String> {
+            find_objects(pattern, &objects, all)
+                .iter()
+                .map(|o| o.name.clone())
+                .collect()
+        };
+        assert_eq!(names("opp*", false), vec!["Opportunity"]);
+        assert_eq!(names("opp*", true), vec!["Opportunity", "OpportunityFeed"]);
+        assert_eq!(names("取引*", false), vec!["Account"]);
+        assert!(names("opp", false).is_empty());
+    }
+
+    #[test]
+    fn test_find_fields() {
+        let object_fields = object_fields();
+        let names: Vec<&str> = find_fields("*name", &object_fields["Opportunity"])
+            .iter()
+            .map(|f| f.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["StageName", "Name"]);
+        let names: Vec<&str> = find_fields("説明", &object_fields["Opportunity"])
+            .iter()
+            .map(|f| f.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["Description", "NextStep"]);
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*phone*", "MobilePhone"));
+        assert!(glob_match("*__c", "Invoice__c"));
+        assert!(glob_match("acc?unt", "Account"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("a*b*c", "aXbYbZc"));
+        assert!(!glob_match("*__c", "Account"));
+        assert!(!glob_match("acc", "Account"));
+    }
+}
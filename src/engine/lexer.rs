@@ -1,103 +1,181 @@
 use crate::engine::token::{Token, TokenKind};
-use std::iter::Peekable;
-use std::str::Chars;
+use std::{
+    error::Error,
+    fmt::{self, Display},
+    str::Chars,
+};
 
-pub fn tokenize(input: &str) -> Vec<Token> {
+#[derive(Debug, PartialEq)]
+pub enum LexError {
+    // the query method and its offset
+    MissingDot(String, usize),
+}
+
+impl Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LexError::MissingDot(method, _) => {
+                write!(f, "Syntax error: {}() must be called with a dot", method)
+            }
+        }
+    }
+}
+
+impl Error for LexError {}
+
+impl LexError {
+    pub fn offset(&self) -> usize {
+        match self {
+            LexError::MissingDot(_, offset) => *offset,
+        }
+    }
+}
+
+// Peekable chars that also know the byte offset of the next char in the input.
+#[derive(Clone)]
+struct Cursor<'a> {
+    input_len: usize,
+    chars: Chars<'a>,
+    peeked: Option<Option<char>>,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(input: &'a str) -> Self {
+        Cursor {
+            input_len: input.len(),
+            chars: input.chars(),
+            peeked: None,
+        }
+    }
+
+    fn peek(&mut self) -> Option<&char> {
+        let chars = &mut self.chars;
+        self.peeked.get_or_insert_with(|| chars.next()).as_ref()
+    }
+
+    fn offset(&self) -> usize {
+        let peeked_len = match self.peeked {
+            Some(Some(c)) => c.len_utf8(),
+            _ => 0,
+        };
+        self.input_len - self.chars.as_str().len() - peeked_len
+    }
+}
+
+impl Iterator for Cursor<'_> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        match self.peeked.take() {
+            Some(c) => c,
+            None => self.chars.next(),
+        }
+    }
+}
+
+pub fn tokenize(input: &str) -> Result<Vec<Token>, LexError> {
     let mut tokens = Vec::new();
-    let mut input = input.chars().peekable();
+    let mut input = Cursor::new(input);
 
     while let Some(c) = input.next() {
+        let offset = input.offset() - c.len_utf8();
+
         if c.is_whitespace() {
             continue;
         }
 
         match c {
-            '=' => tokens.push(Token::new(TokenKind::Eq, String::from("="))),
+            '=' => tokens.push(Token::new(TokenKind::Eq, String::from("=")).at(offset)),
             // TODO: need to implement '+' and '-' for where condition
-            '+' => tokens.push(Token::new(TokenKind::Plus, String::from("+"))),
-            '-' => tokens.push(Token::new(TokenKind::Minus, String::from("-"))),
+            '+' => tokens.push(Token::new(TokenKind::Plus, String::from("+")).at(offset)),
+            '-' => tokens.push(Token::new(TokenKind::Minus, String::from("-")).at(offset)),
+            '*' => tokens.push(Token::new(TokenKind::Asterisk, String::from("*")).at(offset)),
             '>' => {
                 if let Some(c) = input.peek() {
                     if *c == '=' {
-                        tokens.push(Token::new(TokenKind::GreaterEq, String::from(">=")));
+                        tokens
+                            .push(Token::new(TokenKind::GreaterEq, String::from(">=")).at(offset));
                         input.next();
                     } else {
-                        tokens.push(Token::new(TokenKind::Greater, String::from(">")));
+                        tokens.push(Token::new(TokenKind::Greater, String::from(">")).at(offset));
                     }
                 } else {
-                    tokens.push(Token::new(TokenKind::Greater, String::from(">")));
+                    tokens.push(Token::new(TokenKind::Greater, String::from(">")).at(offset));
                 }
             }
             '<' => {
                 if let Some(c) = input.peek() {
                     if *c == '=' {
-                        tokens.push(Token::new(TokenKind::LessEq, String::from("<=")));
+                        tokens.push(Token::new(TokenKind::LessEq, String::from("<=")).at(offset));
+                        input.next();
+                    } else if *c == '>' {
+                        tokens.push(Token::new(TokenKind::NotEq, String::from("<>")).at(offset));
                         input.next();
                     } else {
-                        tokens.push(Token::new(TokenKind::Less, String::from("<")));
+                        tokens.push(Token::new(TokenKind::Less, String::from("<")).at(offset));
                     }
                 } else {
-                    tokens.push(Token::new(TokenKind::Less, String::from("<")));
+                    tokens.push(Token::new(TokenKind::Less, String::from("<")).at(offset));
                 }
             }
-            ',' => tokens.push(Token::new(TokenKind::Comma, String::from(","))),
-            '.' => tokens.push(Token::new(TokenKind::Dot, String::from("."))),
-            '(' => tokens.push(Token::new(TokenKind::Lparen, String::from("("))),
-            ')' => tokens.push(Token::new(TokenKind::Rparen, String::from(")"))),
+            ',' => tokens.push(Token::new(TokenKind::Comma, String::from(",")).at(offset)),
+            '.' => tokens.push(Token::new(TokenKind::Dot, String::from(".")).at(offset)),
+            '(' => tokens.push(Token::new(TokenKind::Lparen, String::from("(")).at(offset)),
+            ')' => tokens.push(Token::new(TokenKind::Rparen, String::from(")")).at(offset)),
             '!' => {
                 if let Some(c) = input.peek() {
                     if *c == '=' {
-                        tokens.push(Token::new(TokenKind::NotEq, String::from("!=")));
+                        tokens.push(Token::new(TokenKind::NotEq, String::from("!=")).at(offset));
                         input.next();
                     } else {
-                        tokens.push(Token::new(TokenKind::Illegal, String::from("!")));
+                        tokens.push(Token::new(TokenKind::Illegal, String::from("!")).at(offset));
                     }
                 } else {
-                    tokens.push(Token::new(TokenKind::Illegal, String::from("!")));
+                    tokens.push(Token::new(TokenKind::Illegal, String::from("!")).at(offset));
                 }
             }
             '\'' => {
-                let string_obj = consume_string_object(&mut input);
-                tokens.push(Token::new(TokenKind::StringObject, string_obj));
+                // raw('...') is SOQL already, its escapes are kept as written
+                let in_raw = tokens.len() >= 2
+                    && tokens[tokens.len() - 2].kind == TokenKind::Raw
+                    && tokens[tokens.len() - 1].kind == TokenKind::Lparen;
+                let string_obj = consume_string_object(&mut input, in_raw);
+                tokens.push(Token::new(TokenKind::StringObject, string_obj).at(offset));
             }
             _ => {
                 if c.is_ascii_digit() {
-                    tokens.push(Token::new(
-                        TokenKind::Integer,
-                        consume_integer(&mut input, c),
-                    ));
+                    let num = consume_integer(&mut input, c);
+                    match consume_date(&mut input, &num) {
+                        Some(date) => tokens.push(Token::new(TokenKind::Date, date).at(offset)),
+                        None => tokens.push(Token::new(TokenKind::Integer, num).at(offset)),
+                    }
                 } else if is_literal(c) {
                     let literal = consume_literal(&mut input, c);
-                    let token = search_keywords(&literal);
-                    if token.is_query_method() {
-                        match tokens.pop() {
-                            // the word before the query method must be a dot
-                            Some(token) => {
-                                if !token.is_dot() {
-                                    eprintln!("Syntax error: the word before the query method must be a dot");
-                                    std::process::exit(1);
-                                }
-                            }
-                            _ => {
-                                eprintln!(
-                                    "Syntax error: the word before the query method must be a dot"
-                                );
-                                std::process::exit(1);
-                            }
-                        }
+                    let mut token = search_keywords(&literal);
+                    if token.kind == TokenKind::DateLiteral && literal.contains("_N_") {
+                        token = consume_date_literal_parameter(&mut input, token);
                     }
-                    tokens.push(token);
+                    // count is also the SOQL aggregate, e.g. select(count(Id))
+                    if token.kind == TokenKind::Count && !tokens.last().is_some_and(|t| t.is_dot())
+                    {
+                        token = Token::new(TokenKind::Identifire, literal.clone());
+                    }
+                    // the word before the query method must be a dot
+                    if token.is_query_method() && !tokens.pop().is_some_and(|t| t.is_dot()) {
+                        return Err(LexError::MissingDot(literal, offset));
+                    }
+                    tokens.push(token.at(offset));
                 } else {
-                    tokens.push(Token::new(TokenKind::Illegal, String::from(c)));
+                    tokens.push(Token::new(TokenKind::Illegal, String::from(c)).at(offset));
                 }
             }
         }
     }
-    tokens.push(Token::new(TokenKind::Eof, String::from("")));
-    tokens
+    tokens.push(Token::new(TokenKind::Eof, String::from("")).at(input.offset()));
+    Ok(tokens)
 }
 
-fn consume_integer(input: &mut Peekable<Chars>, current_c: char) -> String {
+fn consume_integer(input: &mut Cursor, current_c: char) -> String {
     let mut num = String::from(current_c);
     while let Some(c) = input.peek() {
         if c.is_ascii_digit() {
@@ -110,7 +188,61 @@ fn consume_integer(input: &mut Peekable<Chars>, current_c: char) -> String {
     num
 }
 
-fn consume_literal(input: &mut Peekable<Chars>, current_c: char) -> String {
+// Reads the rest of an unquoted date (YYYY-MM-DD) or datetime (YYYY-MM-DDThh:mm:ssZ)
+// following `year`. The input is left untouched when it does not form a date.
+fn consume_date(input: &mut Cursor, year: &str) -> Option<String> {
+    if year.len() != 4 {
+        return None;
+    }
+
+    let mut lookahead = input.clone();
+    let mut date = String::from(year);
+    for _ in 0..2 {
+        if lookahead.next() != Some('-') {
+            return None;
+        }
+        date.push('-');
+        for _ in 0..2 {
+            match lookahead.next() {
+                Some(c) if c.is_ascii_digit() => date.push(c),
+                _ => return None,
+            }
+        }
+    }
+
+    if lookahead.peek() == Some(&'T') {
+        while let Some(c) = lookahead.peek() {
+            if c.is_ascii_alphanumeric() || matches!(c, ':' | '.' | '+' | '-') {
+                date.push(*c);
+                lookahead.next();
+            } else {
+                break;
+            }
+        }
+    }
+
+    *input = lookahead;
+    Some(date)
+}
+
+// Appends the `:n` of LAST_N_DAYS:n and the like. Without it the literal is
+// left for Salesforce to reject.
+fn consume_date_literal_parameter(input: &mut Cursor, token: Token) -> Token {
+    let mut lookahead = input.clone();
+    match (lookahead.next(), lookahead.next()) {
+        (Some(':'), Some(c)) if c.is_ascii_digit() => {
+            let num = consume_integer(&mut lookahead, c);
+            *input = lookahead;
+            Token::new(
+                TokenKind::DateLiteral,
+                format!("{}:{}", token.literal(), num),
+            )
+        }
+        _ => token,
+    }
+}
+
+fn consume_literal(input: &mut Cursor, current_c: char) -> String {
     let mut literal = String::from(current_c);
     while let Some(c) = input.peek() {
         if is_literal(*c) || c.is_ascii_digit() {
@@ -123,14 +255,24 @@ fn consume_literal(input: &mut Peekable<Chars>, current_c: char) -> String {
     literal
 }
 
-fn consume_string_object(input: &mut Peekable<Chars>) -> String {
+// A backslash keeps the next quote or backslash from being read as syntax, and is
+// dropped unless `keep_escapes`. Other escape sequences are left as written.
+fn consume_string_object(input: &mut Cursor, keep_escapes: bool) -> String {
     let mut string_obj = String::new();
-    for c in input.by_ref() {
-        if c == '\'' {
-            break;
+    while let Some(c) = input.next() {
+        match c {
+            '\'' => break,
+            '\\' => match input.peek() {
+                Some('\'') | Some('\\') => {
+                    if keep_escapes {
+                        string_obj.push(c);
+                    }
+                    string_obj.push(input.next().unwrap());
+                }
+                _ => string_obj.push(c),
+            },
+            _ => string_obj.push(c),
         }
-        string_obj.push(c);
-        continue;
     }
     string_obj
 }
@@ -139,24 +281,124 @@ fn is_literal(c: char) -> bool {
     c.is_alphabetic() || c == '_'
 }
 
-fn search_keywords(literal: &str) -> Token {
-    match literal {
-        "select" => Token::new(TokenKind::Select, String::from(literal)),
-        "where" => Token::new(TokenKind::Where, String::from(literal)),
-        "orderby" => Token::new(TokenKind::Orderby, String::from(literal)),
-        "groupby" => Token::new(TokenKind::Groupby, String::from(literal)),
-        "limit" => Token::new(TokenKind::Limit, String::from(literal)),
-        "open" => Token::new(TokenKind::Open, String::from(literal)),
-        "and" | "AND" => Token::new(TokenKind::And, String::from(literal)),
-        "or" | "OR" => Token::new(TokenKind::Or, String::from(literal)),
-        "like" | "LIKE" => Token::new(TokenKind::Like, String::from(literal)),
-        "asc" | "ASC" => Token::new(TokenKind::Asc, String::from(literal)),
-        "desc" | "DESC" => Token::new(TokenKind::Desc, String::from(literal)),
-        "true" | "TRUE" => Token::new(TokenKind::True, String::from(literal)),
-        "false" | "FALSE" => Token::new(TokenKind::False, String::from(literal)),
-        "null" | "NULL" => Token::new(TokenKind::Null, String::from(literal)),
-        _ => Token::new(TokenKind::Identifire, String::from(literal)),
-    }
+// Every keyword with the token it is read as. The first spelling of a token is the one
+// help shows.
+pub const KEYWORDS: [(&str, TokenKind); 99] = [
+    ("select", TokenKind::Select),
+    ("where", TokenKind::Where),
+    ("andWhere", TokenKind::AndWhere),
+    ("andwhere", TokenKind::AndWhere),
+    ("orWhere", TokenKind::OrWhere),
+    ("orwhere", TokenKind::OrWhere),
+    ("orderby", TokenKind::Orderby),
+    ("groupby", TokenKind::Groupby),
+    ("limit", TokenKind::Limit),
+    ("open", TokenKind::Open),
+    ("openQuery", TokenKind::OpenQuery),
+    ("openquery", TokenKind::OpenQuery),
+    ("out", TokenKind::Out),
+    ("distinct", TokenKind::Distinct),
+    ("all", TokenKind::All),
+    ("bulk", TokenKind::Bulk),
+    ("explain", TokenKind::Explain),
+    ("count", TokenKind::Count),
+    ("insert", TokenKind::Insert),
+    ("updateAll", TokenKind::UpdateAll),
+    ("updateall", TokenKind::UpdateAll),
+    ("upsert", TokenKind::Upsert),
+    ("delete", TokenKind::Delete),
+    ("contains", TokenKind::Contains),
+    ("startswith", TokenKind::Startswith),
+    ("endswith", TokenKind::Endswith),
+    ("withcount", TokenKind::Withcount),
+    ("listview", TokenKind::Listview),
+    ("mine", TokenKind::Mine),
+    ("ownedby", TokenKind::Ownedby),
+    ("createdby", TokenKind::Createdby),
+    ("on", TokenKind::On),
+    ("and", TokenKind::And),
+    ("AND", TokenKind::And),
+    ("or", TokenKind::Or),
+    ("OR", TokenKind::Or),
+    ("like", TokenKind::Like),
+    ("LIKE", TokenKind::Like),
+    ("raw", TokenKind::Raw),
+    ("between", TokenKind::Between),
+    ("BETWEEN", TokenKind::Between),
+    ("isNull", TokenKind::IsNull),
+    ("isnull", TokenKind::IsNull),
+    ("isNotNull", TokenKind::IsNotNull),
+    ("isnotnull", TokenKind::IsNotNull),
+    ("in", TokenKind::In),
+    ("IN", TokenKind::In),
+    ("not", TokenKind::Not),
+    ("NOT", TokenKind::Not),
+    ("includes", TokenKind::Includes),
+    ("INCLUDES", TokenKind::Includes),
+    ("in_file", TokenKind::InFile),
+    ("YESTERDAY", TokenKind::DateLiteral),
+    ("TODAY", TokenKind::DateLiteral),
+    ("TOMORROW", TokenKind::DateLiteral),
+    ("LAST_WEEK", TokenKind::DateLiteral),
+    ("THIS_WEEK", TokenKind::DateLiteral),
+    ("NEXT_WEEK", TokenKind::DateLiteral),
+    ("LAST_MONTH", TokenKind::DateLiteral),
+    ("THIS_MONTH", TokenKind::DateLiteral),
+    ("NEXT_MONTH", TokenKind::DateLiteral),
+    ("LAST_90_DAYS", TokenKind::DateLiteral),
+    ("NEXT_90_DAYS", TokenKind::DateLiteral),
+    ("LAST_N_DAYS", TokenKind::DateLiteral),
+    ("NEXT_N_DAYS", TokenKind::DateLiteral),
+    ("LAST_N_WEEKS", TokenKind::DateLiteral),
+    ("NEXT_N_WEEKS", TokenKind::DateLiteral),
+    ("LAST_N_MONTHS", TokenKind::DateLiteral),
+    ("NEXT_N_MONTHS", TokenKind::DateLiteral),
+    ("THIS_QUARTER", TokenKind::DateLiteral),
+    ("LAST_QUARTER", TokenKind::DateLiteral),
+    ("NEXT_QUARTER", TokenKind::DateLiteral),
+    ("LAST_N_QUARTERS", TokenKind::DateLiteral),
+    ("NEXT_N_QUARTERS", TokenKind::DateLiteral),
+    ("THIS_YEAR", TokenKind::DateLiteral),
+    ("LAST_YEAR", TokenKind::DateLiteral),
+    ("NEXT_YEAR", TokenKind::DateLiteral),
+    ("LAST_N_YEARS", TokenKind::DateLiteral),
+    ("NEXT_N_YEARS", TokenKind::DateLiteral),
+    ("THIS_FISCAL_QUARTER", TokenKind::DateLiteral),
+    ("LAST_FISCAL_QUARTER", TokenKind::DateLiteral),
+    ("NEXT_FISCAL_QUARTER", TokenKind::DateLiteral),
+    ("THIS_FISCAL_YEAR", TokenKind::DateLiteral),
+    ("LAST_FISCAL_YEAR", TokenKind::DateLiteral),
+    ("NEXT_FISCAL_YEAR", TokenKind::DateLiteral),
+    ("LAST_N_FISCAL_QUARTERS", TokenKind::DateLiteral),
+    ("NEXT_N_FISCAL_QUARTERS", TokenKind::DateLiteral),
+    ("LAST_N_FISCAL_YEARS", TokenKind::DateLiteral),
+    ("NEXT_N_FISCAL_YEARS", TokenKind::DateLiteral),
+    ("asc", TokenKind::Asc),
+    ("ASC", TokenKind::Asc),
+    ("desc", TokenKind::Desc),
+    ("DESC", TokenKind::Desc),
+    ("true", TokenKind::True),
+    ("TRUE", TokenKind::True),
+    ("false", TokenKind::False),
+    ("FALSE", TokenKind::False),
+    ("null", TokenKind::Null),
+    ("NULL", TokenKind::Null),
+];
+
+// The date literals, of which those with `_N_` take a number after a colon.
+pub fn date_literals() -> impl Iterator<Item = &'static str> {
+    KEYWORDS
+        .iter()
+        .filter(|(_, kind)| *kind == TokenKind::DateLiteral)
+        .map(|(keyword, _)| *keyword)
+}
+
+pub fn search_keywords(literal: &str) -> Token {
+    let kind = KEYWORDS
+        .iter()
+        .find(|(keyword, _)| *keyword == literal)
+        .map_or(TokenKind::Identifire, |(_, kind)| kind.clone());
+    Token::new(kind, String::from(literal))
 }
 
 #[cfg(test)]
@@ -167,7 +409,7 @@ mod tests {
     fn test_tokenize_only_table_name() {
         let input = "Account";
 
-        let tokens = tokenize(input);
+        let tokens = tokenize(input).unwrap();
         assert_eq!(
             tokens[0],
             Token::new(TokenKind::Identifire, String::from("Account"))
@@ -239,27 +481,160 @@ mod tests {
             Token::new(TokenKind::Eof, String::from("")),
         ];
 
-        let tokens = tokenize(input);
+        let tokens = tokenize(input).unwrap();
         assert_eq!(tokens, expected);
     }
 
+    #[test]
+    fn test_tokenize_missing_dot() {
+        assert_eq!(
+            tokenize("Account where(Name = 'a')"),
+            Err(LexError::MissingDot(String::from("where"), 8))
+        );
+        assert_eq!(
+            tokenize("select(Id)"),
+            Err(LexError::MissingDot(String::from("select"), 0))
+        );
+    }
+
+    #[test]
+    fn test_tokenize_count() {
+        let kinds: Vec<TokenKind> = tokenize("Lead.select(count(Id)).count()")
+            .unwrap()
+            .into_iter()
+            .map(|t| t.kind)
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                TokenKind::Identifire,
+                TokenKind::Select,
+                TokenKind::Lparen,
+                TokenKind::Identifire,
+                TokenKind::Lparen,
+                TokenKind::Identifire,
+                TokenKind::Rparen,
+                TokenKind::Rparen,
+                TokenKind::Count,
+                TokenKind::Lparen,
+                TokenKind::Rparen,
+                TokenKind::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_offsets() {
+        let tokens = tokenize("Account.where(Name = 'Ä' AND Id >= 1)").unwrap();
+        let offsets: Vec<usize> = tokens.iter().map(|t| t.offset()).collect();
+        // the query method token takes the place of its dot
+        assert_eq!(offsets, vec![0, 8, 13, 14, 19, 21, 26, 30, 33, 36, 37, 38]);
+    }
+
+    #[test]
+    fn test_tokenize_not_eq() {
+        let tokens = tokenize("Name <> 'a' AND Id != 1 AND Amount <= 2").unwrap();
+        let operators: Vec<TokenKind> = tokens
+            .into_iter()
+            .filter(|t| t.is_operator() && !matches!(t.kind, TokenKind::And))
+            .map(|t| t.kind)
+            .collect();
+        assert_eq!(
+            operators,
+            vec![TokenKind::NotEq, TokenKind::NotEq, TokenKind::LessEq]
+        );
+    }
+
     #[test]
     fn test_consume_ineger() {
-        let mut input = "1234567890".chars().peekable();
+        let mut input = Cursor::new("1234567890");
         input.next();
         let num = consume_integer(&mut input, '1');
         assert_eq!(num, "1234567890");
     }
 
+    #[test]
+    fn test_consume_date() {
+        let mut input = Cursor::new("-05-01)");
+        let date = consume_date(&mut input, "2024");
+        assert_eq!(date.unwrap(), "2024-05-01");
+        assert_eq!(input.next(), Some(')'));
+
+        let mut input = Cursor::new("-05-01T10:00:00Z");
+        let date = consume_date(&mut input, "2024");
+        assert_eq!(date.unwrap(), "2024-05-01T10:00:00Z");
+
+        // case: not a date, the input must be left as it was
+        let mut input = Cursor::new("-1000");
+        let date = consume_date(&mut input, "2024");
+        assert_eq!(date, None);
+        assert_eq!(input.next(), Some('-'));
+    }
+
+    #[test]
+    fn test_fiscal_date_literals() {
+        let tokens =
+            tokenize("THIS_FISCAL_QUARTER LAST_N_FISCAL_YEARS:3 NEXT_N_FISCAL_QUARTERS").unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::new(TokenKind::DateLiteral, String::from("THIS_FISCAL_QUARTER")),
+                Token::new(
+                    TokenKind::DateLiteral,
+                    String::from("LAST_N_FISCAL_YEARS:3")
+                ),
+                Token::new(
+                    TokenKind::DateLiteral,
+                    String::from("NEXT_N_FISCAL_QUARTERS")
+                ),
+                Token::new(TokenKind::Eof, String::from("")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_relative_date_literals() {
+        let tokens = tokenize("TODAY LAST_90_DAYS LAST_N_DAYS:30").unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::new(TokenKind::DateLiteral, String::from("TODAY")),
+                Token::new(TokenKind::DateLiteral, String::from("LAST_90_DAYS")),
+                Token::new(TokenKind::DateLiteral, String::from("LAST_N_DAYS:30")),
+                Token::new(TokenKind::Eof, String::from("")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_list_operators() {
+        let kinds: Vec<TokenKind> =
+            tokenize("Id in ('a') AND Id NOT IN ('b') AND Tags includes ('c')")
+                .unwrap()
+                .into_iter()
+                .map(|t| t.kind)
+                .filter(|kind| matches!(kind, TokenKind::In | TokenKind::Not | TokenKind::Includes))
+                .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                TokenKind::In,
+                TokenKind::Not,
+                TokenKind::In,
+                TokenKind::Includes
+            ]
+        );
+    }
+
     #[test]
     fn test_consume_literal() {
-        let mut input = "Account".chars().peekable();
+        let mut input = Cursor::new("Account");
         input.next();
         let literal = consume_literal(&mut input, 'A');
         assert_eq!(literal, "Account");
 
         // case: literal with underscore and integer in the middle
-        let mut input = "Product2__c".chars().peekable();
+        let mut input = Cursor::new("Product2__c");
         input.next();
         let literal = consume_literal(&mut input, 'P');
         assert_eq!(literal, "Product2__c");
@@ -267,9 +642,21 @@ mod tests {
 
     #[test]
     fn test_consume_string_object() {
-        let mut input = "'%Test'".chars().peekable();
+        let mut input = Cursor::new("'%Test'");
         input.next();
-        let string_obj = consume_string_object(&mut input);
+        let string_obj = consume_string_object(&mut input, false);
         assert_eq!(string_obj, "%Test");
+
+        // case: escaped quote and backslash
+        let mut input = Cursor::new(r"'O\'Brien \\ 50\%' rest");
+        input.next();
+        let string_obj = consume_string_object(&mut input, false);
+        assert_eq!(string_obj, r"O'Brien \ 50\%");
+
+        // case: the escapes of raw() are kept
+        let mut input = Cursor::new(r"'O\'Brien \\ 50\%' rest");
+        input.next();
+        let string_obj = consume_string_object(&mut input, true);
+        assert_eq!(string_obj, r"O\'Brien \\ 50\%");
     }
 }
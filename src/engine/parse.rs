@@ -1,6 +1,9 @@
 use crate::engine::ast::*;
 use crate::engine::token::{Token, TokenKind};
+use chrono::{NaiveDate, TimeZone, Utc};
+use chrono_tz::Tz;
 use std::{
+    collections::{HashMap, HashSet},
     error::Error,
     fmt::{self, Display},
     iter::Peekable,
@@ -9,22 +12,27 @@ use std::{
 
 #[derive(Debug)]
 pub enum ParseError {
-    UnexpectedToken(String, String),
-    InvalidMethod(String),
+    UnexpectedToken(String, Token),
+    InvalidMethod(Token),
+    UnknownUser(String),
 }
 
 impl Display for ParseError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            ParseError::UnexpectedToken(message, token_literal) => {
+            ParseError::UnexpectedToken(message, token) => {
                 write!(
                     f,
                     "Unexpected token: expected {}. got \'{}\'",
-                    message, token_literal
+                    message,
+                    token.literal()
                 )
             }
-            ParseError::InvalidMethod(method) => {
-                write!(f, "Invalid method: {}", method)
+            ParseError::InvalidMethod(token) => {
+                write!(f, "Invalid method: {}", token.literal())
+            }
+            ParseError::UnknownUser(alias) => {
+                write!(f, "Unknown user: {}", alias)
             }
         }
     }
@@ -32,10 +40,87 @@ impl Display for ParseError {
 
 impl Error for ParseError {}
 
+impl ParseError {
+    // Offset of the offending token in the input, if the error points at one.
+    pub fn offset(&self) -> Option<usize> {
+        match self {
+            ParseError::UnexpectedToken(_, token) | ParseError::InvalidMethod(token) => {
+                Some(token.offset())
+            }
+            _ => None,
+        }
+    }
+}
+
+// An error shown together with the input line and a caret under the offending token.
+#[derive(Debug)]
+pub struct SourceError {
+    message: String,
+    line: String,
+    column: usize,
+}
+
+impl SourceError {
+    pub fn new(error: &dyn Error, input: &str, offset: usize) -> Self {
+        let offset = offset.min(input.len());
+        let line_start = input[..offset].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = input[offset..]
+            .find('\n')
+            .map_or(input.len(), |i| offset + i);
+
+        SourceError {
+            message: error.to_string(),
+            line: input[line_start..line_end].to_string(),
+            column: input[line_start..offset].chars().count(),
+        }
+    }
+}
+
+impl Display for SourceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}\n  {}\n  {}^",
+            self.message,
+            self.line,
+            " ".repeat(self.column)
+        )
+    }
+}
+
+impl Error for SourceError {}
+
+// User Ids that mine(), ownedby() and createdby() are resolved to.
+#[derive(Debug, Default, Clone)]
+pub struct UserIds {
+    pub current: Option<String>,
+    pub aliases: HashMap<String, String>,
+}
+
+// The part of a line that is still being typed, as found by Parser::parse_partial.
+#[derive(Debug, PartialEq)]
+pub enum Incomplete {
+    Object,
+    Method,
+    // the arguments of the query method with this name
+    Arguments(String),
+}
+
+#[derive(Debug)]
+pub struct PartialProgram {
+    pub object: Option<String>,
+    pub program: Program,
+    pub incomplete: Option<Incomplete>,
+}
+
 #[derive(Debug)]
 pub struct Parser {
     pub tokens: Peekable<IntoIter<Token>>,
     pub current_token: Token,
+    pub user_ids: UserIds,
+    pub timezone: Tz,
+    // the fields of on() that are dates, which are compared with the day itself
+    pub date_fields: HashSet<String>,
 }
 
 impl Parser {
@@ -44,11 +129,19 @@ impl Parser {
         Parser {
             tokens: iter,
             current_token: Token::new(TokenKind::Illegal, String::from("")),
+            user_ids: UserIds::default(),
+            timezone: Tz::UTC,
+            date_fields: HashSet::new(),
         }
     }
 
+    // The Eof token is never consumed, so peeking at the end of the input keeps returning it.
     pub fn next_token(&mut self) -> Option<Token> {
-        self.current_token = self.tokens.next()?;
+        self.current_token = if self.peek_token_is(TokenKind::Eof) {
+            self.peek_token().cloned()?
+        } else {
+            self.tokens.next()?
+        };
         Some(self.current_token.clone())
     }
 
@@ -59,24 +152,119 @@ impl Parser {
     // <program> := <table> <statement>*
     pub fn parse(&mut self) -> Result<Program, ParseError> {
         let mut statements = Vec::new();
+        let mut where_statement: Option<(usize, WhereStatement)> = None;
 
         statements.push(self.parse_table()?);
 
         while let Some(token) = self.peek_token() {
             match token.kind {
                 TokenKind::Eof => break,
+                _ if token.is_where_method() => {
+                    let statement = self.parse_where_method()?;
+                    where_statement = match where_statement {
+                        Some((index, current)) => {
+                            Some((index, merge_where_statements(current, statement)))
+                        }
+                        None if matches!(
+                            statement.token.kind,
+                            TokenKind::AndWhere | TokenKind::OrWhere
+                        ) =>
+                        {
+                            return Err(ParseError::UnexpectedToken(
+                                format!("where() before {}()", statement.token.literal()),
+                                statement.token.clone(),
+                            ))
+                        }
+                        None => Some((statements.len(), statement)),
+                    };
+                }
                 _ if token.is_query_method() => statements.push(self.parse_statement()?),
                 _ => {
                     return Err(ParseError::InvalidMethod(
-                        self.peek_token().unwrap().literal(),
+                        self.peek_token().unwrap().clone(),
                     ))
                 }
             }
         }
 
+        if let Some((index, statement)) = where_statement {
+            statements.insert(index, Box::new(statement));
+        }
+
         Ok(Program { statements })
     }
 
+    // Parses a line that may still be being typed, e.g. `Account.select(Id, Na`.
+    // Parsing stops at the first error in the word being typed, which is reported as
+    // incomplete along with the statements parsed before it. Other errors are returned.
+    pub fn parse_partial(&mut self) -> Result<PartialProgram, ParseError> {
+        let tokens: Vec<Token> = self
+            .tokens
+            .clone()
+            .filter(|t| t.kind != TokenKind::Eof)
+            .collect();
+        // a method name being typed is lexed as a dot followed by an identifier
+        let typing_from = match tokens.as_slice() {
+            [.., dot, last] if dot.is_dot() && last.kind == TokenKind::Identifire => dot.offset(),
+            [.., last] => last.offset(),
+            [] => 0,
+        };
+        let at_end = |e: &ParseError| e.offset().is_some_and(|offset| offset >= typing_from);
+
+        let mut partial = PartialProgram {
+            object: None,
+            program: Program {
+                statements: Vec::new(),
+            },
+            incomplete: None,
+        };
+
+        match self.parse_table() {
+            Ok(table) => {
+                partial.object = Some(table.string());
+                partial.program.statements.push(table);
+            }
+            Err(e) if at_end(&e) => {
+                partial.incomplete = if self.peek_token_is(TokenKind::Dot) {
+                    partial.object = Some(self.current_token.literal());
+                    Some(Incomplete::Method)
+                } else {
+                    Some(Incomplete::Object)
+                };
+                return Ok(partial);
+            }
+            Err(e) => return Err(e),
+        }
+
+        while let Some(token) = self.peek_token() {
+            if token.kind == TokenKind::Eof {
+                break;
+            }
+
+            let method = token.clone();
+            let statement = if method.is_query_method() {
+                self.parse_statement()
+            } else {
+                Err(ParseError::InvalidMethod(method.clone()))
+            };
+
+            match statement {
+                Ok(statement) => partial.program.statements.push(statement),
+                Err(e) if at_end(&e) => {
+                    partial.incomplete = Some(if method.is_query_method() {
+                        Incomplete::Arguments(method.literal())
+                    } else {
+                        Incomplete::Method
+                    });
+                    break;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(partial)
+    }
+
     // <table> := <identifier>
     fn parse_table(&mut self) -> Result<Box<dyn Statement>, ParseError> {
         self.next_token();
@@ -85,7 +273,7 @@ impl Parser {
         if !self.current_token_is(TokenKind::Identifire) {
             return Err(ParseError::UnexpectedToken(
                 String::from("SObject Name"),
-                self.current_token.literal(),
+                self.current_token.clone(),
             ));
         }
 
@@ -95,7 +283,7 @@ impl Parser {
         if !self.peek_token_is_query() {
             return Err(ParseError::UnexpectedToken(
                 String::from("query method after SObject Name"),
-                self.peek_token().unwrap().literal(),
+                self.peek_token().unwrap().clone(),
             ));
         }
         Ok(Box::new(Table { token, table_name }))
@@ -106,25 +294,42 @@ impl Parser {
         match self.peek_token() {
             Some(token) => match token.kind {
                 TokenKind::Select | TokenKind::Groupby => self.parse_select_groupby_statement(),
-                TokenKind::Where => self.parse_where_statement(),
+                _ if token.is_where_method() => Ok(Box::new(self.parse_where_method()?)),
                 TokenKind::Orderby => self.parse_orderby_statement(),
                 TokenKind::Limit => self.parse_limit_statement(),
                 TokenKind::Open => self.parse_open_statement(),
+                TokenKind::OpenQuery => self.parse_open_query_statement(),
+                TokenKind::Out => self.parse_out_statement(),
+                TokenKind::Distinct => self.parse_distinct_statement(),
+                TokenKind::All => self.parse_all_statement(),
+                TokenKind::Bulk => self.parse_bulk_statement(),
+                TokenKind::Explain => self.parse_explain_statement(),
+                TokenKind::Count => self.parse_count_statement(),
+                TokenKind::Insert
+                | TokenKind::UpdateAll
+                | TokenKind::Upsert
+                | TokenKind::Delete => self.parse_dml_statement(),
+                TokenKind::Withcount => self.parse_withcount_statement(),
+                TokenKind::Listview => self.parse_list_view_statement(),
                 _ => Err(ParseError::InvalidMethod(
-                    self.peek_token().unwrap().literal(),
+                    self.peek_token().unwrap().clone(),
                 )),
             },
             None => unreachable!(),
         }
     }
 
-    // <select_statement> := 'select' '(' <field> (',' <field>)* ')'
-    // <groupby_statement> := 'groupby' '(' <field> (',' <field>)* ')'
+    // <select_statement> := 'select' '(' <field_or_function> (',' <field_or_function>)* ')'
+    // <groupby_statement> := 'groupby' '(' <field_or_function> (',' <field_or_function>)* ')'
     fn parse_select_groupby_statement(&mut self) -> Result<Box<dyn Statement>, ParseError> {
         let token = self.next_token().unwrap();
 
         self.expect_peek(TokenKind::Lparen)?;
 
+        if token.kind == TokenKind::Select && self.peek_token_is(TokenKind::Asterisk) {
+            return self.parse_select_all_statement(token);
+        }
+
         let fields = self.parse_fields()?;
 
         self.expect_peek(TokenKind::Rparen)?;
@@ -138,7 +343,48 @@ impl Parser {
         Ok(statement)
     }
 
-    fn parse_where_statement(&mut self) -> Result<Box<dyn Statement>, ParseError> {
+    // <select_all_statement> := 'select' '(' '*' (',' ('createable' | 'updateable'))? ')'
+    fn parse_select_all_statement(
+        &mut self,
+        token: Token,
+    ) -> Result<Box<dyn Statement>, ParseError> {
+        self.expect_peek(TokenKind::Asterisk)?;
+
+        let mut filter = None;
+        if self.peek_token_is(TokenKind::Comma) {
+            self.next_token();
+            self.expect_peek(TokenKind::Identifire)?;
+
+            let name = self.current_token.literal();
+            if name != "createable" && name != "updateable" {
+                return Err(ParseError::UnexpectedToken(
+                    String::from("createable or updateable"),
+                    self.current_token.clone(),
+                ));
+            }
+            filter = Some(name);
+        }
+
+        self.expect_peek(TokenKind::Rparen)?;
+
+        Ok(Box::new(SelectAllStatement { token, filter }))
+    }
+
+    fn parse_where_method(&mut self) -> Result<WhereStatement, ParseError> {
+        match self.peek_token().map(|token| token.kind.clone()) {
+            Some(TokenKind::Contains) | Some(TokenKind::Startswith) | Some(TokenKind::Endswith) => {
+                self.parse_like_helper_statement()
+            }
+            Some(TokenKind::Mine) | Some(TokenKind::Ownedby) | Some(TokenKind::Createdby) => {
+                self.parse_user_filter_statement()
+            }
+            Some(TokenKind::On) => self.parse_on_statement(),
+            _ => self.parse_where_statement(),
+        }
+    }
+
+    // <where_statement> := ('where' | 'andWhere' | 'orWhere') '(' <where_expression> ')'
+    fn parse_where_statement(&mut self) -> Result<WhereStatement, ParseError> {
         let token = self.next_token().unwrap();
 
         self.expect_peek(TokenKind::Lparen)?;
@@ -147,7 +393,159 @@ impl Parser {
 
         self.expect_peek(TokenKind::Rparen)?;
 
-        Ok(Box::new(WhereStatement { token, expression }))
+        Ok(WhereStatement { token, expression })
+    }
+
+    // <like_helper_statement> := ('contains' | 'startswith' | 'endswith') '(' <field> ',' <string> ')'
+    fn parse_like_helper_statement(&mut self) -> Result<WhereStatement, ParseError> {
+        let token = self.next_token().unwrap();
+
+        self.expect_peek(TokenKind::Lparen)?;
+        self.expect_peek(TokenKind::Identifire)?;
+
+        let field = self.parse_field()?;
+
+        self.expect_peek(TokenKind::Comma)?;
+        self.expect_peek(TokenKind::StringObject)?;
+
+        let value = LikePattern {
+            token: token.clone(),
+            value: self.current_token.literal(),
+        };
+
+        self.expect_peek(TokenKind::Rparen)?;
+
+        let operator_token = Token::new(TokenKind::Like, TokenKind::Like.to_string());
+        let expression = Condition {
+            token: field.token.clone(),
+            field,
+            operator: OperatorLiteral {
+                value: operator_token.literal(),
+                token: operator_token,
+            },
+            value: Box::new(value),
+        };
+
+        Ok(WhereStatement {
+            token,
+            expression: Box::new(expression),
+        })
+    }
+
+    // <on_statement> := 'on' '(' <field> ',' <date> ')'
+    // A date field is compared with the day. Otherwise the day is taken in the session
+    // timezone and compared as a UTC datetime range.
+    fn parse_on_statement(&mut self) -> Result<WhereStatement, ParseError> {
+        let token = self.next_token().unwrap();
+
+        self.expect_peek(TokenKind::Lparen)?;
+        self.expect_peek(TokenKind::Identifire)?;
+
+        let field = self.parse_field()?;
+
+        self.expect_peek(TokenKind::Comma)?;
+        self.expect_peek(TokenKind::Date)?;
+
+        let day =
+            NaiveDate::parse_from_str(&self.current_token.literal(), "%Y-%m-%d").map_err(|_| {
+                ParseError::UnexpectedToken(String::from("YYYY-MM-DD"), self.current_token.clone())
+            })?;
+
+        self.expect_peek(TokenKind::Rparen)?;
+
+        if self.date_fields.contains(&field.name) {
+            let operator_token = Token::new(TokenKind::Eq, TokenKind::Eq.to_string());
+            let value = day.format("%Y-%m-%d").to_string();
+            return Ok(WhereStatement {
+                token,
+                expression: Box::new(Condition {
+                    token: field.token.clone(),
+                    field,
+                    operator: OperatorLiteral {
+                        value: operator_token.literal(),
+                        token: operator_token,
+                    },
+                    value: Box::new(Value {
+                        token: Token::new(TokenKind::Date, value.clone()),
+                        value,
+                    }),
+                }),
+            });
+        }
+
+        let datetime = |day: NaiveDate| {
+            let value = start_of_day(&self.timezone, day);
+            Box::new(Value {
+                token: Token::new(TokenKind::Date, value.clone()),
+                value,
+            }) as Box<dyn Expression>
+        };
+        let start = datetime(day);
+        let end = datetime(day.succ_opt().unwrap());
+
+        let expression = range_expression(
+            field.token.clone(),
+            Token::new(TokenKind::And, TokenKind::And.to_string()),
+            field,
+            (TokenKind::GreaterEq, start),
+            (TokenKind::Less, end),
+        );
+
+        Ok(WhereStatement {
+            token,
+            expression: Box::new(expression),
+        })
+    }
+
+    // <user_filter_statement> := 'mine' '(' ')' | ('ownedby' | 'createdby') '(' <string> ')'
+    fn parse_user_filter_statement(&mut self) -> Result<WhereStatement, ParseError> {
+        let token = self.next_token().unwrap();
+
+        self.expect_peek(TokenKind::Lparen)?;
+
+        let user_id = if token.kind == TokenKind::Mine {
+            self.user_ids
+                .current
+                .clone()
+                .ok_or_else(|| ParseError::UnknownUser(String::from("current user")))?
+        } else {
+            self.expect_peek(TokenKind::StringObject)?;
+            let alias = self.current_token.literal();
+            self.user_ids
+                .aliases
+                .get(&alias)
+                .cloned()
+                .ok_or(ParseError::UnknownUser(alias))?
+        };
+
+        self.expect_peek(TokenKind::Rparen)?;
+
+        let field_name = match token.kind {
+            TokenKind::Createdby => "CreatedById",
+            _ => "OwnerId",
+        };
+        let field = FieldLiteral {
+            token: Token::new(TokenKind::Identifire, String::from(field_name)),
+            name: String::from(field_name),
+        };
+        let operator_token = Token::new(TokenKind::Eq, TokenKind::Eq.to_string());
+        let expression = Condition {
+            token: field.token.clone(),
+            field,
+            operator: OperatorLiteral {
+                value: operator_token.literal(),
+                token: operator_token,
+            },
+            value: Box::new(Value {
+                token: Token::new(TokenKind::StringObject, user_id.clone()),
+                value: user_id,
+            }),
+        };
+
+        Ok(WhereStatement {
+            token,
+            expression: Box::new(expression),
+        })
     }
 
     // <orderby_statement> := 'orderby' '(' <orderby_option> (',' <orderby_option>)* ')'
@@ -186,13 +584,172 @@ impl Parser {
         Ok(Box::new(OpenStatement { token }))
     }
 
+    // <out_statement> := 'out' '(' <string> ')'
+    fn parse_out_statement(&mut self) -> Result<Box<dyn Statement>, ParseError> {
+        let token = self.next_token().unwrap();
+
+        self.expect_peek(TokenKind::Lparen)?;
+        self.expect_peek(TokenKind::StringObject)?;
+        let path = self.current_token.literal();
+        self.expect_peek(TokenKind::Rparen)?;
+
+        Ok(Box::new(OutStatement { token, path }))
+    }
+
+    // <list_view_statement> := 'listview' '(' <string> ')'
+    fn parse_list_view_statement(&mut self) -> Result<Box<dyn Statement>, ParseError> {
+        let token = self.next_token().unwrap();
+
+        self.expect_peek(TokenKind::Lparen)?;
+        self.expect_peek(TokenKind::StringObject)?;
+        let name = self.current_token.literal();
+        self.expect_peek(TokenKind::Rparen)?;
+
+        Ok(Box::new(ListViewStatement { token, name }))
+    }
+
+    // <distinct_statement> := 'distinct' '(' ')'
+    fn parse_distinct_statement(&mut self) -> Result<Box<dyn Statement>, ParseError> {
+        let token = self.next_token().unwrap();
+
+        self.expect_peek(TokenKind::Lparen)?;
+        self.expect_peek(TokenKind::Rparen)?;
+
+        Ok(Box::new(DistinctStatement { token }))
+    }
+
+    // <all_statement> := 'all' '(' ')'
+    fn parse_all_statement(&mut self) -> Result<Box<dyn Statement>, ParseError> {
+        let token = self.next_token().unwrap();
+
+        self.expect_peek(TokenKind::Lparen)?;
+        self.expect_peek(TokenKind::Rparen)?;
+
+        Ok(Box::new(AllStatement { token }))
+    }
+
+    // <bulk_statement> := 'bulk' '(' ')'
+    fn parse_bulk_statement(&mut self) -> Result<Box<dyn Statement>, ParseError> {
+        let token = self.next_token().unwrap();
+
+        self.expect_peek(TokenKind::Lparen)?;
+        self.expect_peek(TokenKind::Rparen)?;
+
+        Ok(Box::new(BulkStatement { token }))
+    }
+
+    // <open_query_statement> := 'openQuery' '(' ')'
+    fn parse_open_query_statement(&mut self) -> Result<Box<dyn Statement>, ParseError> {
+        let token = self.next_token().unwrap();
+
+        self.expect_peek(TokenKind::Lparen)?;
+        self.expect_peek(TokenKind::Rparen)?;
+
+        Ok(Box::new(OpenQueryStatement { token }))
+    }
+
+    // <explain_statement> := 'explain' '(' ')'
+    fn parse_explain_statement(&mut self) -> Result<Box<dyn Statement>, ParseError> {
+        let token = self.next_token().unwrap();
+
+        self.expect_peek(TokenKind::Lparen)?;
+        self.expect_peek(TokenKind::Rparen)?;
+
+        Ok(Box::new(ExplainStatement { token }))
+    }
+
+    // <count_statement> := 'count' '(' ')'
+    fn parse_count_statement(&mut self) -> Result<Box<dyn Statement>, ParseError> {
+        let token = self.next_token().unwrap();
+
+        self.expect_peek(TokenKind::Lparen)?;
+        self.expect_peek(TokenKind::Rparen)?;
+
+        Ok(Box::new(CountStatement { token }))
+    }
+
+    // <dml_statement> := ('insert' | 'updateAll' | 'upsert') '(' <assignment> (',' <assignment>)* ')'
+    //                  | 'delete' '(' ')'
+    // <assignment> := <field> '=' <literal>
+    fn parse_dml_statement(&mut self) -> Result<Box<dyn Statement>, ParseError> {
+        let token = self.next_token().unwrap();
+        let mut assignments = Vec::new();
+
+        self.expect_peek(TokenKind::Lparen)?;
+        if token.kind != TokenKind::Delete {
+            loop {
+                self.expect_peek(TokenKind::Identifire)?;
+                let field = self.parse_field()?;
+                self.expect_peek(TokenKind::Eq)?;
+                assignments.push((field.name, self.parse_literal()?));
+
+                if !self.peek_token_is(TokenKind::Comma) {
+                    break;
+                }
+                self.next_token();
+            }
+        }
+        self.expect_peek(TokenKind::Rparen)?;
+
+        Ok(Box::new(DmlStatement { token, assignments }))
+    }
+
+    // <literal> := <string> | <date> | ['-'] <integer> | 'true' | 'false' | 'null'
+    fn parse_literal(&mut self) -> Result<serde_json::Value, ParseError> {
+        let negative = self.peek_token_is(TokenKind::Minus);
+        if negative {
+            self.next_token();
+        }
+        let token = self.next_token().unwrap();
+
+        match token.kind {
+            TokenKind::Integer => {
+                let value = token.literal().parse::<i64>().map_err(|_| {
+                    ParseError::UnexpectedToken(String::from("integer"), token.clone())
+                })?;
+                Ok(serde_json::Value::from(if negative {
+                    -value
+                } else {
+                    value
+                }))
+            }
+            _ if negative => Err(ParseError::UnexpectedToken(String::from("integer"), token)),
+            TokenKind::StringObject | TokenKind::Date => {
+                Ok(serde_json::Value::String(token.literal()))
+            }
+            TokenKind::True => Ok(serde_json::Value::Bool(true)),
+            TokenKind::False => Ok(serde_json::Value::Bool(false)),
+            TokenKind::Null => Ok(serde_json::Value::Null),
+            _ => Err(ParseError::UnexpectedToken(
+                String::from("string, number, date, true, false or null"),
+                token,
+            )),
+        }
+    }
+
+    // <withcount_statement> := 'withcount' '(' <field> (',' <field>)* ')'
+    fn parse_withcount_statement(&mut self) -> Result<Box<dyn Statement>, ParseError> {
+        let token = self.next_token().unwrap();
+
+        self.expect_peek(TokenKind::Lparen)?;
+
+        let relationships = self.parse_fields()?;
+
+        self.expect_peek(TokenKind::Rparen)?;
+
+        Ok(Box::new(WithCountStatement {
+            token,
+            relationships,
+        }))
+    }
+
     fn parse_fields(&mut self) -> Result<Vec<FieldLiteral>, ParseError> {
         let mut fields = Vec::new();
 
         self.next_token();
 
         while !self.current_token_is(TokenKind::Rparen) {
-            let field = self.parse_field()?;
+            let field = self.parse_field_or_function()?;
 
             if self.peek_token_is(TokenKind::Rparen) {
                 fields.push(field);
@@ -225,6 +782,35 @@ impl Parser {
         Ok(FieldLiteral { token, name })
     }
 
+    // <function_call> := <identifier> <lparen> <rparen>
+    //                   | <identifier> <lparen> <field_or_function> <rparen>
+    // e.g. CALENDAR_YEAR(CloseDate), SUM(Amount), COUNT()
+    fn parse_field_or_function(&mut self) -> Result<FieldLiteral, ParseError> {
+        if !(self.current_token_is(TokenKind::Identifire) && self.peek_token_is(TokenKind::Lparen))
+        {
+            return self.parse_field();
+        }
+
+        let token = self.current_token.clone();
+        let function = self.current_token.literal();
+
+        self.next_token();
+
+        let argument = if self.peek_token_is(TokenKind::Rparen) {
+            String::new()
+        } else {
+            self.next_token();
+            self.parse_field_or_function()?.name
+        };
+
+        self.expect_peek(TokenKind::Rparen)?;
+
+        Ok(FieldLiteral {
+            token,
+            name: format!("{}({})", function, argument),
+        })
+    }
+
     // <orderby_option> := <field> | <field> <asc_or_desc>
     fn parse_orderby_options(&mut self) -> Result<Vec<OrderByOptionLiteral>, ParseError> {
         let mut options = Vec::new();
@@ -232,7 +818,7 @@ impl Parser {
         self.next_token();
 
         while !self.peek_token_is(TokenKind::Rparen) {
-            let mut field = self.parse_field()?;
+            let mut field = self.parse_field_or_function()?;
 
             if self.peek_token_is(TokenKind::Asc) {
                 self.next_token();
@@ -269,14 +855,14 @@ impl Parser {
                 _ => {
                     return Err(ParseError::UnexpectedToken(
                         String::from("where clause"),
-                        self.current_token.literal(),
+                        token.clone(),
                     ))
                 }
             },
             None => {
                 return Err(ParseError::UnexpectedToken(
                     String::from("where clause"),
-                    self.current_token.literal(),
+                    self.current_token.clone(),
                 ))
             }
         };
@@ -292,7 +878,7 @@ impl Parser {
                 _ => {
                     return Err(ParseError::UnexpectedToken(
                         String::from("where clause"),
-                        self.current_token.literal(),
+                        token.clone(),
                     ))
                 }
             }
@@ -317,10 +903,29 @@ impl Parser {
         }))
     }
 
-    // <condition> := <field> <operator> <value>
+    // <condition> := <field_or_function> <operator> <value>
+    //              | <between_condition> | <null_condition> | <in_file_condition>
+    //              | <list_condition>
     fn parse_condition(&mut self) -> Result<Box<dyn Expression>, ParseError> {
         let token = self.next_token().unwrap();
-        let field = self.parse_field()?;
+        let field = self.parse_field_or_function()?;
+
+        if self.peek_token_is(TokenKind::Between) {
+            return self.parse_between_condition(token, field);
+        }
+        if self.peek_token_is(TokenKind::IsNull) || self.peek_token_is(TokenKind::IsNotNull) {
+            return self.parse_null_condition(token, field);
+        }
+        if self.peek_token_is(TokenKind::InFile) {
+            return self.parse_in_file_condition(token, field);
+        }
+        if self.peek_token_is(TokenKind::In)
+            || self.peek_token_is(TokenKind::Not)
+            || self.peek_token_is(TokenKind::Includes)
+        {
+            return self.parse_list_condition(token, field);
+        }
+
         let operator = self.parse_operator_literal()?;
         let value = self.parse_value()?;
 
@@ -332,21 +937,137 @@ impl Parser {
         }))
     }
 
-    // <grouped_condition> := '(' <where_expression>')'
-    fn parse_grouped_condition(&mut self) -> Result<Box<dyn Expression>, ParseError> {
-        self.next_token();
-
-        let exp = self.parse_where_expressions()?;
+    // <between_condition> := <field> 'between' '(' <value> ',' <value> ')'
+    fn parse_between_condition(
+        &mut self,
+        token: Token,
+        field: FieldLiteral,
+    ) -> Result<Box<dyn Expression>, ParseError> {
+        let between_token = self.next_token().unwrap();
 
+        self.expect_peek(TokenKind::Lparen)?;
+        let low = self.parse_value()?;
+        self.expect_peek(TokenKind::Comma)?;
+        let high = self.parse_value()?;
         self.expect_peek(TokenKind::Rparen)?;
 
-        Ok(exp)
+        Ok(Box::new(range_expression(
+            token,
+            Token::new(TokenKind::And, between_token.literal()),
+            field,
+            (TokenKind::GreaterEq, low),
+            (TokenKind::LessEq, high),
+        )))
     }
 
-    fn parse_integer_literal(&mut self) -> Result<IntegerLiteral, ParseError> {
-        let token = self.next_token().unwrap();
-        let value = token.literal().parse::<i64>().unwrap();
-        Ok(IntegerLiteral { token, value })
+    // <null_condition> := <field> ('isNull' | 'isNotNull') '(' ')'
+    fn parse_null_condition(
+        &mut self,
+        token: Token,
+        field: FieldLiteral,
+    ) -> Result<Box<dyn Expression>, ParseError> {
+        let null_token = self.next_token().unwrap();
+
+        self.expect_peek(TokenKind::Lparen)?;
+        self.expect_peek(TokenKind::Rparen)?;
+
+        let operator_token = match null_token.kind {
+            TokenKind::IsNotNull => Token::new(TokenKind::NotEq, TokenKind::NotEq.to_string()),
+            _ => Token::new(TokenKind::Eq, TokenKind::Eq.to_string()),
+        };
+
+        Ok(Box::new(Condition {
+            token,
+            field,
+            operator: OperatorLiteral {
+                value: operator_token.literal(),
+                token: operator_token,
+            },
+            value: Box::new(Value {
+                token: Token::new(TokenKind::Null, String::from("null")),
+                value: String::from("null"),
+            }),
+        }))
+    }
+
+    // <in_file_condition> := <field> 'in_file' '(' <string> ')'
+    // The file is read when the query is run, see Query::read_in_file.
+    fn parse_in_file_condition(
+        &mut self,
+        token: Token,
+        field: FieldLiteral,
+    ) -> Result<Box<dyn Expression>, ParseError> {
+        let in_file_token = self.next_token().unwrap();
+
+        self.expect_peek(TokenKind::Lparen)?;
+        self.expect_peek(TokenKind::StringObject)?;
+        let path = self.current_token.literal();
+        self.expect_peek(TokenKind::Rparen)?;
+
+        let operator_token = Token::new(TokenKind::In, TokenKind::In.to_string());
+        Ok(Box::new(Condition {
+            token,
+            field,
+            operator: OperatorLiteral {
+                value: operator_token.literal(),
+                token: operator_token,
+            },
+            value: Box::new(InList {
+                token: Token::new(in_file_token.kind, path),
+            }),
+        }))
+    }
+
+    // <list_condition> := <field> ('in' | 'not' 'in' | 'includes') '(' <value> {',' <value>} ')'
+    fn parse_list_condition(
+        &mut self,
+        token: Token,
+        field: FieldLiteral,
+    ) -> Result<Box<dyn Expression>, ParseError> {
+        let mut kind = self.next_token().unwrap().kind;
+        if kind == TokenKind::Not {
+            self.expect_peek(TokenKind::In)?;
+            kind = TokenKind::NotIn;
+        }
+        let operator_token = Token::new(kind.clone(), kind.to_string());
+
+        self.expect_peek(TokenKind::Lparen)?;
+        let mut values = vec![self.parse_value()?];
+        while self.peek_token_is(TokenKind::Comma) {
+            self.next_token();
+            values.push(self.parse_value()?);
+        }
+        self.expect_peek(TokenKind::Rparen)?;
+
+        Ok(Box::new(Condition {
+            token,
+            field,
+            operator: OperatorLiteral {
+                value: operator_token.literal(),
+                token: operator_token,
+            },
+            value: Box::new(ValueList { values }),
+        }))
+    }
+
+    // <grouped_condition> := '(' <where_expression>')'
+    fn parse_grouped_condition(&mut self) -> Result<Box<dyn Expression>, ParseError> {
+        self.next_token();
+
+        let exp = self.parse_where_expressions()?;
+
+        self.expect_peek(TokenKind::Rparen)?;
+
+        Ok(exp)
+    }
+
+    fn parse_integer_literal(&mut self) -> Result<IntegerLiteral, ParseError> {
+        let token = self.next_token().unwrap();
+        let value = token
+            .literal()
+            .parse::<i64>()
+            .map_err(|_| ParseError::UnexpectedToken(String::from("integer"), token.clone()))?;
+        Ok(IntegerLiteral { token, value })
     }
 
     fn parse_operator_literal(&mut self) -> Result<OperatorLiteral, ParseError> {
@@ -359,16 +1080,16 @@ impl Parser {
                 };
                 Ok(operator)
             } else {
-                return Err(ParseError::UnexpectedToken(
-                    String::from("Operator(AND, OR, =, >, >=, <, <=, LIKE)"),
-                    self.peek_token().unwrap().literal(),
-                ));
+                Err(ParseError::UnexpectedToken(
+                    String::from("Operator(AND, OR, =, >, >=, <, <=, LIKE, IN, NOT IN, INCLUDES)"),
+                    self.peek_token().unwrap().clone(),
+                ))
             }
         } else {
-            return Err(ParseError::UnexpectedToken(
-                String::from("Operator(AND, OR, =, >, >=, <, <=, LIKE)"),
-                self.peek_token().unwrap().literal(),
-            ));
+            Err(ParseError::UnexpectedToken(
+                String::from("Operator(AND, OR, =, >, >=, <, <=, LIKE, IN, NOT IN, INCLUDES)"),
+                self.peek_token().unwrap().clone(),
+            ))
         }
     }
 
@@ -376,28 +1097,47 @@ impl Parser {
         match self.peek_token() {
             Some(token) => match token.kind {
                 TokenKind::Plus | TokenKind::Minus => self.parse_prefix_expression(),
-                TokenKind::StringObject | TokenKind::Integer | TokenKind::Null => {
-                    Ok(Box::new(Value {
-                        token: self.next_token().unwrap(),
-                        value: self.current_token.literal(),
-                    }))
-                }
-                _ => {
-                    return Err(ParseError::UnexpectedToken(
-                        String::from(""),
-                        self.peek_token().unwrap().literal(),
-                    ))
+                TokenKind::StringObject
+                | TokenKind::Integer
+                | TokenKind::Date
+                | TokenKind::DateLiteral
+                | TokenKind::Null => Ok(Box::new(Value {
+                    token: self.next_token().unwrap(),
+                    value: self.current_token.literal(),
+                })),
+                TokenKind::Raw => self.parse_raw_value(),
+                TokenKind::True | TokenKind::False => {
+                    let token = self.next_token().unwrap();
+                    let value = token.kind == TokenKind::True;
+                    Ok(Box::new(BooleanLiteral { token, value }))
                 }
-            },
-            None => {
-                return Err(ParseError::UnexpectedToken(
+                _ => Err(ParseError::UnexpectedToken(
                     String::from(""),
-                    self.peek_token().unwrap().literal(),
-                ))
-            }
+                    self.peek_token().unwrap().clone(),
+                )),
+            },
+            None => Err(ParseError::UnexpectedToken(
+                String::from(""),
+                self.peek_token().unwrap().clone(),
+            )),
         }
     }
 
+    // <raw_value> := 'raw' '(' <string> ')'
+    fn parse_raw_value(&mut self) -> Result<Box<dyn Expression>, ParseError> {
+        self.next_token();
+
+        self.expect_peek(TokenKind::Lparen)?;
+        self.expect_peek(TokenKind::StringObject)?;
+
+        let value = self.current_token.literal();
+        let token = Token::new(TokenKind::Raw, value.clone());
+
+        self.expect_peek(TokenKind::Rparen)?;
+
+        Ok(Box::new(Value { token, value }))
+    }
+
     fn parse_prefix_expression(&mut self) -> Result<Box<dyn Expression>, ParseError> {
         let token = self.next_token().unwrap();
         let operator = token.literal();
@@ -415,12 +1155,12 @@ impl Parser {
     }
 
     fn peek_token_is(&mut self, kind: TokenKind) -> bool {
-        self.peek_token().map_or(false, |token| token.kind == kind)
+        self.peek_token().is_some_and(|token| token.kind == kind)
     }
 
     fn peek_token_is_query(&mut self) -> bool {
         self.peek_token()
-            .map_or(false, |token| token.is_query_method())
+            .is_some_and(|token| token.is_query_method())
     }
 
     fn expect_peek(&mut self, kind: TokenKind) -> Result<(), ParseError> {
@@ -430,12 +1170,74 @@ impl Parser {
         } else {
             Err(ParseError::UnexpectedToken(
                 kind.to_string(),
-                self.peek_token().unwrap().literal(),
+                self.peek_token().unwrap().clone(),
             ))
         }
     }
 }
 
+// Chains an andWhere()/orWhere() onto the preceding where() as a single expression.
+fn merge_where_statements(current: WhereStatement, next: WhereStatement) -> WhereStatement {
+    let kind = match next.token.kind {
+        TokenKind::OrWhere => TokenKind::Or,
+        _ => TokenKind::And,
+    };
+    let operator = kind.to_string();
+
+    WhereStatement {
+        token: current.token,
+        expression: Box::new(InfixExpression {
+            token: Token::new(kind, operator.clone()),
+            left: current.expression,
+            operator,
+            right: next.expression,
+        }),
+    }
+}
+
+// <field> <low_operator> <low> AND <field> <high_operator> <high>
+fn range_expression(
+    token: Token,
+    and_token: Token,
+    field: FieldLiteral,
+    low: (TokenKind, Box<dyn Expression>),
+    high: (TokenKind, Box<dyn Expression>),
+) -> InfixExpression {
+    let condition = |(kind, value): (TokenKind, Box<dyn Expression>), field: FieldLiteral| {
+        let operator_token = Token::new(kind.clone(), kind.to_string());
+        Box::new(Condition {
+            token: token.clone(),
+            field,
+            operator: OperatorLiteral {
+                value: operator_token.literal(),
+                token: operator_token,
+            },
+            value,
+        })
+    };
+
+    InfixExpression {
+        left: condition(low, field.clone()),
+        operator: TokenKind::And.to_string(),
+        right: condition(high, field),
+        token: and_token,
+    }
+}
+
+// Midnight may not exist on DST switch days, the day then starts at the first valid hour.
+fn start_of_day(timezone: &Tz, day: NaiveDate) -> String {
+    (0..24)
+        .find_map(|hour| {
+            timezone
+                .from_local_datetime(&day.and_hms_opt(hour, 0, 0)?)
+                .earliest()
+        })
+        .unwrap()
+        .with_timezone(&Utc)
+        .format("%Y-%m-%dT%H:%M:%SZ")
+        .to_string()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -444,7 +1246,7 @@ mod tests {
     #[test]
     fn test_parse_talbe() {
         let input = "Produc2__c";
-        let tokens = tokenize(input);
+        let tokens = tokenize(input).unwrap();
         let mut parser = Parser::new(tokens);
         assert!(parser.parse().is_err());
     }
@@ -452,7 +1254,7 @@ mod tests {
     #[test]
     fn test_parse_select() {
         let input = "Opportunity.select(Id, Name, Account.Name, Contract.LastName)";
-        let tokens = tokenize(input);
+        let tokens = tokenize(input).unwrap();
         let mut parser = Parser::new(tokens);
         let program = parser.parse().unwrap();
 
@@ -468,7 +1270,7 @@ mod tests {
     fn test_parse_where() {
         let input =
             "Opportunity.where(Id = 123 AND (Name = 'test' OR Account.Name LIKE '%test%' OR Name != NULL) AND Status = 'Closed')";
-        let tokens = tokenize(input);
+        let tokens = tokenize(input).unwrap();
         let mut parser = Parser::new(tokens);
         let program = parser.parse().unwrap();
 
@@ -485,7 +1287,7 @@ mod tests {
     #[test]
     fn test_parse_groupby() {
         let input = "Opportunity.groupby(Id, Name, Account.Name)";
-        let tokens = tokenize(input);
+        let tokens = tokenize(input).unwrap();
         let mut parser = Parser::new(tokens);
         let program = parser.parse().unwrap();
 
@@ -497,10 +1299,190 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_functions() {
+        let input = "Opportunity.select(CALENDAR_YEAR(CloseDate), SUM(Amount), COUNT()).groupby(CALENDAR_YEAR(CloseDate)).orderby(CALENDAR_YEAR(convertTimezone(CloseDate)) DESC)";
+        let tokens = tokenize(input).unwrap();
+        let mut parser = Parser::new(tokens);
+        let program = parser.parse().unwrap();
+
+        assert_eq!(program.statements.len(), 4);
+        assert_eq!(
+            program.statements[1].string(),
+            "CALENDAR_YEAR(CloseDate), SUM(Amount), COUNT()".to_string()
+        );
+        assert_eq!(
+            program.statements[2].string(),
+            "CALENDAR_YEAR(CloseDate)".to_string()
+        );
+        assert_eq!(
+            program.statements[3].string(),
+            "CALENDAR_YEAR(convertTimezone(CloseDate)) DESC".to_string()
+        );
+
+        let tokens = tokenize("Opportunity.select(SUM(Amount)").unwrap();
+        assert!(Parser::new(tokens).parse().is_err());
+    }
+
+    #[test]
+    fn test_parse_where_functions() {
+        let input = "Opportunity.where(CALENDAR_YEAR(CreatedDate) = 2024 AND (HOUR_IN_DAY(convertTimezone(CreatedDate)) > 8 OR Name = 'test'))";
+        let tokens = tokenize(input).unwrap();
+        let mut parser = Parser::new(tokens);
+        let program = parser.parse().unwrap();
+
+        assert_eq!(
+            program.statements[1].string(),
+            "(CALENDAR_YEAR(CreatedDate) = 2024 AND (HOUR_IN_DAY(convertTimezone(CreatedDate)) > 8 OR Name = 'test'))"
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn test_parse_on() {
+        let tokens = tokenize("Account.on(CreatedDate, 2024-05-01)").unwrap();
+        let mut parser = Parser::new(tokens);
+        parser.timezone = "Asia/Tokyo".parse().unwrap();
+        let program = parser.parse().unwrap();
+
+        assert_eq!(
+            program.statements[1].string(),
+            "(CreatedDate >= 2024-04-30T15:00:00Z AND CreatedDate < 2024-05-01T15:00:00Z)"
+                .to_string()
+        );
+
+        // case: the day after a DST switch is 23 hours long
+        let tokens = tokenize("Account.where(Name = 'test').on(CreatedDate, 2024-03-10)").unwrap();
+        let mut parser = Parser::new(tokens);
+        parser.timezone = "America/New_York".parse().unwrap();
+        let program = parser.parse().unwrap();
+
+        assert_eq!(
+            program.statements[1].string(),
+            "(Name = 'test' AND (CreatedDate >= 2024-03-10T05:00:00Z AND CreatedDate < 2024-03-11T04:00:00Z))"
+                .to_string()
+        );
+
+        let tokens = tokenize("Account.on(CreatedDate, 2024-05-01T00:00:00Z)").unwrap();
+        assert!(Parser::new(tokens).parse().is_err());
+
+        // case: a date field has no time to shift
+        let tokens = tokenize("Opportunity.on(CloseDate, 2024-05-01)").unwrap();
+        let mut parser = Parser::new(tokens);
+        parser.timezone = "Asia/Tokyo".parse().unwrap();
+        parser.date_fields = HashSet::from([String::from("CloseDate")]);
+        let program = parser.parse().unwrap();
+
+        assert_eq!(
+            program.statements[1].string(),
+            "CloseDate = 2024-05-01".to_string()
+        );
+    }
+
+    #[test]
+    fn test_parse_error_position() {
+        let input = "Account.select(Id Name)";
+        let err = Parser::new(tokenize(input).unwrap()).parse().unwrap_err();
+        assert_eq!(err.offset(), Some(18));
+        assert_eq!(
+            SourceError::new(&err, input, err.offset().unwrap()).to_string(),
+            "Unexpected token: expected ,. got 'Name'\n  Account.select(Id Name)\n                    ^"
+        );
+
+        // case: the caret is placed on the line of the token
+        let input = "Account\n  .where(Name = 'a' Id = 1)";
+        let err = Parser::new(tokenize(input).unwrap()).parse().unwrap_err();
+        assert_eq!(
+            SourceError::new(&err, input, err.offset().unwrap()).to_string(),
+            "Unexpected token: expected where clause. got 'Id'\n    .where(Name = 'a' Id = 1)\n                      ^"
+        );
+    }
+
+    #[test]
+    fn test_parse_partial() {
+        let parse_partial = |input: &str| {
+            let partial = Parser::new(tokenize(input).unwrap())
+                .parse_partial()
+                .unwrap();
+            (
+                partial.object,
+                partial.program.statements.len(),
+                partial.incomplete,
+            )
+        };
+        let object = Some(String::from("Account"));
+
+        assert_eq!(parse_partial("Acc"), (None, 0, Some(Incomplete::Object)));
+        assert_eq!(
+            parse_partial("Account."),
+            (object.clone(), 0, Some(Incomplete::Method))
+        );
+        assert_eq!(
+            parse_partial("Account.select(Id).sel"),
+            (object.clone(), 2, Some(Incomplete::Method))
+        );
+        assert_eq!(
+            parse_partial("Account.select(Id, Na"),
+            (
+                object.clone(),
+                1,
+                Some(Incomplete::Arguments(String::from("select")))
+            )
+        );
+        assert_eq!(
+            parse_partial("Account.select(Id, "),
+            (
+                object.clone(),
+                1,
+                Some(Incomplete::Arguments(String::from("select")))
+            )
+        );
+        assert_eq!(
+            parse_partial("Account.select(Id).where(Name = 'a' AN"),
+            (
+                object.clone(),
+                2,
+                Some(Incomplete::Arguments(String::from("where")))
+            )
+        );
+        assert_eq!(
+            parse_partial("Account.select(Owner.Ali"),
+            (
+                object.clone(),
+                1,
+                Some(Incomplete::Arguments(String::from("select")))
+            )
+        );
+        assert_eq!(
+            parse_partial("Account.where(Parent.Owner."),
+            (
+                object.clone(),
+                1,
+                Some(Incomplete::Arguments(String::from("where")))
+            )
+        );
+        assert_eq!(
+            parse_partial("Account.limit("),
+            (
+                object.clone(),
+                1,
+                Some(Incomplete::Arguments(String::from("limit")))
+            )
+        );
+        assert_eq!(
+            parse_partial("Account.select(Id).limit(1)"),
+            (object, 3, None)
+        );
+
+        // case: errors before the word being typed are not recovered from
+        let tokens = tokenize("Account.select(Id Name).where(Na").unwrap();
+        assert!(Parser::new(tokens).parse_partial().is_err());
+    }
+
     #[test]
     fn test_parse_orderby() {
         let input = "Opportunity.orderby(Id, Name ASC, Account.Name DESC)";
-        let tokens = tokenize(input);
+        let tokens = tokenize(input).unwrap();
         let mut parser = Parser::new(tokens);
         let program = parser.parse().unwrap();
 
@@ -515,7 +1497,7 @@ mod tests {
     #[test]
     fn test_parse_limit() {
         let input = "Account.limit(10)";
-        let tokens = tokenize(input);
+        let tokens = tokenize(input).unwrap();
         let mut parser = Parser::new(tokens);
         let program = parser.parse().unwrap();
 
@@ -527,7 +1509,7 @@ mod tests {
     #[test]
     fn test_parse_open() {
         let input = "Account.open()";
-        let tokens = tokenize(input);
+        let tokens = tokenize(input).unwrap();
         let mut parser = Parser::new(tokens);
         let program = parser.parse().unwrap();
 
@@ -535,4 +1517,199 @@ mod tests {
         assert_eq!(program.statements[1].token_literal(), "open".to_string());
         assert_eq!(program.string(), "Account.open".to_string());
     }
+
+    #[test]
+    fn test_parse_chained_where() {
+        let input = "Account.where(Industry = 'Tech').andWhere(AnnualRevenue > 1000000).orWhere(Rating = 'Hot').limit(10)";
+        let tokens = tokenize(input).unwrap();
+        let mut parser = Parser::new(tokens);
+        let program = parser.parse().unwrap();
+
+        assert_eq!(program.statements.len(), 3);
+        assert_eq!(program.statements[1].token_literal(), "where".to_string());
+        assert_eq!(
+            program.statements[1].string(),
+            "((Industry = 'Tech' AND AnnualRevenue > 1000000) OR Rating = 'Hot')".to_string()
+        );
+        assert_eq!(program.statements[2].token_literal(), "limit".to_string());
+    }
+
+    #[test]
+    fn test_parse_chained_where_without_where() {
+        let input = "Account.andWhere(Rating = 'Hot')";
+        let tokens = tokenize(input).unwrap();
+        let mut parser = Parser::new(tokens);
+        assert!(parser.parse().is_err());
+    }
+
+    #[test]
+    fn test_parse_distinct() {
+        let input = "Account.select(Industry).distinct()";
+        let tokens = tokenize(input).unwrap();
+        let mut parser = Parser::new(tokens);
+        let program = parser.parse().unwrap();
+
+        assert_eq!(program.statements.len(), 3);
+        assert_eq!(
+            program.statements[2].token_literal(),
+            "distinct".to_string()
+        );
+        assert_eq!(program.string(), "Account.Industry.distinct".to_string());
+    }
+
+    #[test]
+    fn test_parse_select_all() {
+        let input = "Account.select(*, createable)";
+        let tokens = tokenize(input).unwrap();
+        let mut parser = Parser::new(tokens);
+        let program = parser.parse().unwrap();
+
+        assert_eq!(program.statements.len(), 2);
+        assert_eq!(program.statements[1].token_literal(), "select".to_string());
+        assert_eq!(program.statements[1].string(), "createable".to_string());
+
+        let input = "Account.select(*, Name)";
+        let tokens = tokenize(input).unwrap();
+        let mut parser = Parser::new(tokens);
+        assert!(parser.parse().is_err());
+    }
+
+    #[test]
+    fn test_parse_like_helpers() {
+        let input = "Account.where(Industry = 'Tech').contains(Name, '50%_off').startswith(Site, 'Tokyo').endswith(Owner.Email, '@example.com')";
+        let tokens = tokenize(input).unwrap();
+        let mut parser = Parser::new(tokens);
+        let program = parser.parse().unwrap();
+
+        assert_eq!(program.statements.len(), 2);
+        assert_eq!(
+            program.statements[1].string(),
+            "(((Industry = 'Tech' AND Name LIKE '%50\\%\\_off%') AND Site LIKE 'Tokyo%') AND Owner.Email LIKE '%@example.com')"
+                .to_string()
+        );
+
+        // case: a helper on its own becomes the where clause
+        let input = "Account.contains(Name, 'test')";
+        let tokens = tokenize(input).unwrap();
+        let mut parser = Parser::new(tokens);
+        let program = parser.parse().unwrap();
+
+        assert_eq!(program.statements.len(), 2);
+        assert_eq!(
+            program.statements[1].string(),
+            "Name LIKE '%test%'".to_string()
+        );
+    }
+
+    #[test]
+    fn test_parse_between() {
+        let input = "Opportunity.where(Amount between(1000, 5000) AND CloseDate between(2024-01-01, 2024-03-31))";
+        let tokens = tokenize(input).unwrap();
+        let mut parser = Parser::new(tokens);
+        let program = parser.parse().unwrap();
+
+        assert_eq!(
+            program.statements[1].string(),
+            "((Amount >= 1000 AND Amount <= 5000) AND (CloseDate >= 2024-01-01 AND CloseDate <= 2024-03-31))"
+                .to_string()
+        );
+        assert_eq!(program.statements[1].conditions().len(), 4);
+    }
+
+    #[test]
+    fn test_parse_escaped_values() {
+        let input = r"Account.where(Name = 'O\'Brien' OR Name LIKE raw('50\%%') OR Description = 'C:\\temp')";
+        let tokens = tokenize(input).unwrap();
+        let mut parser = Parser::new(tokens);
+        let program = parser.parse().unwrap();
+
+        assert_eq!(
+            program.statements[1].string(),
+            r"(Name = 'O\'Brien' OR (Name LIKE '50\%%' OR Description = 'C:\\temp'))".to_string()
+        );
+
+        // case: raw() is pre-escaped SOQL and goes out as written
+        let input = r"Account.where(Name = raw('O\'Brien') OR Name LIKE raw('C:\\%'))";
+        let program = Parser::new(tokenize(input).unwrap()).parse().unwrap();
+
+        assert_eq!(
+            program.statements[1].string(),
+            r"(Name = 'O\'Brien' OR Name LIKE 'C:\\%')".to_string()
+        );
+    }
+
+    #[test]
+    fn test_parse_null_helpers() {
+        let input = "Account.where(OwnerId isNull() OR Parent.Name isNotNull())";
+        let tokens = tokenize(input).unwrap();
+        let mut parser = Parser::new(tokens);
+        let program = parser.parse().unwrap();
+
+        assert_eq!(
+            program.statements[1].string(),
+            "(OwnerId = null OR Parent.Name != null)".to_string()
+        );
+    }
+
+    #[test]
+    fn test_parse_list_conditions() {
+        let input = "Account.where(Rating in('Hot', 'Warm') AND NumberOfEmployees NOT IN (1, 2) AND Regions__c includes('APAC'))";
+        let tokens = tokenize(input).unwrap();
+        let mut parser = Parser::new(tokens);
+        let program = parser.parse().unwrap();
+
+        assert_eq!(
+            program.statements[1].string(),
+            "(Rating IN ('Hot', 'Warm') AND (NumberOfEmployees NOT IN (1, 2) AND Regions__c INCLUDES ('APAC')))"
+                .to_string()
+        );
+        assert_eq!(program.statements[1].conditions().len(), 3);
+
+        for input in [
+            "Account.where(Rating in())",
+            "Account.where(Rating not ('Hot'))",
+        ] {
+            let mut parser = Parser::new(tokenize(input).unwrap());
+            assert!(parser.parse().is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_parse_in_file() {
+        // the file is only read when the query is run
+        let tokens = tokenize("Account.where(Id in_file('does/not/exist.csv'))").unwrap();
+        let mut parser = Parser::new(tokens);
+        let program = parser.parse().unwrap();
+
+        assert_eq!(
+            program.statements[1].string(),
+            "Id IN in_file('does/not/exist.csv')".to_string()
+        );
+        assert_eq!(
+            program.statements[1].conditions()[0].value.in_file(),
+            Some(String::from("does/not/exist.csv"))
+        );
+    }
+
+    #[test]
+    fn test_parse_user_filters() {
+        let input = "Opportunity.mine().createdby('jdoe').where(IsClosed = false)";
+        let tokens = tokenize(input).unwrap();
+        let mut parser = Parser::new(tokens);
+        parser.user_ids = UserIds {
+            current: Some(String::from("005000000000001AAA")),
+            aliases: HashMap::from([(String::from("jdoe"), String::from("005000000000002AAA"))]),
+        };
+        let program = parser.parse().unwrap();
+
+        assert_eq!(
+            program.statements[1].string(),
+            "((OwnerId = '005000000000001AAA' AND CreatedById = '005000000000002AAA') AND IsClosed = false)"
+                .to_string()
+        );
+
+        let tokens = tokenize("Opportunity.ownedby('unknown')").unwrap();
+        let mut parser = Parser::new(tokens);
+        assert!(parser.parse().is_err());
+    }
 }
@@ -1,35 +1,204 @@
+use crate::cache::Field;
 use crate::engine::ast::*;
+use crate::engine::graphql::{self, GraphqlQuery};
 use crate::helper::DynError;
+use std::collections::HashMap;
+use std::fs;
 
+// A query built from the method chain, with its clauses kept apart from how it is to be
+// run, so that callers can decide how to run it or which records to change.
 #[derive(Default, Debug)]
 pub struct Query {
-    pub select: Option<String>,
-    pub from: String,
-    pub where_clause: Option<String>,
-    pub orderby: Option<String>,
-    pub groupby: Option<String>,
-    pub limit: Option<String>,
-    pub open_browser: bool,
+    select: Option<String>,
+    fields: Vec<String>,
+    from: String,
+    where_clause: Option<String>,
+    // the where() condition as a filter of the GraphQL API, or why it can not be one
+    where_filter: Option<Result<serde_json::Value, String>>,
+    orderby: Option<String>,
+    groupby: Option<String>,
+    limit: Option<String>,
+    open_browser: bool,
+    open_query: bool,
+    output_path: Option<String>,
+    fetch_all: bool,
+    bulk: bool,
+    graphql: bool,
+    explain: bool,
+    count: bool,
+    distinct: bool,
+    count_relationships: Vec<String>,
+    // the path of the in_file() list and the field it is compared with
+    in_file: Option<(String, String)>,
+    in_values: Option<Vec<String>>,
+    dml: Option<Dml>,
+    list_view: Option<String>,
+    // the query of the list view, run in place of the generated one
+    list_view_query: Option<String>,
 }
 
+// How the query was assembled from the method chain, so that callers can explain the
+// generated SOQL.
+#[derive(Default, Debug, PartialEq)]
+pub struct Evaluation {
+    // SELECT, WHERE, ... in the order the methods setting them were called
+    pub clauses: Vec<String>,
+    // clauses that were added without a method asking for them
+    pub defaults: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+// Maximum length of a SOQL statement
+const MAX_QUERY_LENGTH: usize = 100_000;
+
+const MAX_IN_FILE_VALUES: usize = 50_000;
+
 impl Query {
+    pub fn object(&self) -> &str {
+        &self.from
+    }
+
+    // The selected fields in the order of select(), which is also the order of the
+    // result columns. Empty when select() is not called.
+    pub fn fields(&self) -> &[String] {
+        &self.fields
+    }
+
+    // Whether the first record of the result should be opened in the browser, i.e.
+    // open() was called. The query itself runs as written.
+    pub fn opens_browser(&self) -> bool {
+        self.open_browser
+    }
+
+    // Whether the query should be opened in a query tool in the browser instead of
+    // running it, i.e. openQuery() was called.
+    pub fn opens_query(&self) -> bool {
+        self.open_query
+    }
+
+    // The file out() writes the result to instead of the terminal.
+    pub fn output_path(&self) -> Option<&str> {
+        self.output_path.as_deref()
+    }
+
+    // Whether every batch of the result should be fetched at once, i.e. all() was called.
+    pub fn fetches_all(&self) -> bool {
+        self.fetch_all
+    }
+
+    // Whether the query should run as a Bulk API 2.0 job, i.e. bulk() was called or
+    // set_bulk() used for --bulk.
+    pub fn uses_bulk(&self) -> bool {
+        self.bulk
+    }
+
+    pub fn set_bulk(&mut self) {
+        self.bulk = true;
+    }
+
+    // Whether the query should run on the GraphQL API, i.e. set_graphql() was used for
+    // --backend graphql.
+    pub fn uses_graphql(&self) -> bool {
+        self.graphql
+    }
+
+    pub fn set_graphql(&mut self) {
+        self.graphql = true;
+    }
+
+    // The query for the GraphQL API. The methods that SOQL alone has are refused.
+    pub fn to_graphql(&self) -> Result<GraphqlQuery, DynError> {
+        let methods = [
+            ("groupby()", self.groupby.is_some()),
+            ("distinct()", self.distinct),
+            ("withcount()", !self.count_relationships.is_empty()),
+            ("listview()", self.list_view.is_some()),
+        ];
+        if let Some((method, _)) = methods.iter().find(|(_, called)| *called) {
+            return Err(format!("{} is not supported by the GraphQL backend", method).into());
+        }
+        let filter = self.where_filter.clone().transpose()?;
+        let limit = self.limit.as_deref().map(str::parse).transpose()?;
+        Ok(graphql::build(
+            &self.from,
+            &self.fields,
+            filter,
+            self.orderby.as_deref(),
+            limit,
+            self.count,
+        )?)
+    }
+
+    // Whether the query plans of the query should be shown instead of running it, i.e.
+    // explain() was called.
+    pub fn explains(&self) -> bool {
+        self.explain
+    }
+
+    // Whether only the number of records is asked for, i.e. count() was called or
+    // set_count() used for --count. The query selects COUNT() and its totalSize is
+    // the answer.
+    pub fn counts(&self) -> bool {
+        self.count
+    }
+
+    pub fn set_count(&mut self) {
+        self.count = true;
+    }
+
+    // The change of records asked for instead of a query. updateAll() and delete() change
+    // the records the query matches, which selects only their Ids.
+    pub fn dml(&self) -> Option<&Dml> {
+        self.dml.as_ref()
+    }
+
+    // The list view whose query listview() runs. It has to be looked up and passed to
+    // set_list_view_query before the query is generated.
+    pub fn list_view(&self) -> Option<&str> {
+        self.list_view.as_deref()
+    }
+
+    pub fn set_list_view_query(&mut self, soql: String) {
+        self.list_view_query = Some(soql);
+    }
+
+    // Relationships whose child records are counted by withcount().
+    pub fn count_relationships(&self) -> &[String] {
+        &self.count_relationships
+    }
+
     pub fn generate(&self) -> String {
-        let mut query = format!(
-            "SELECT {} FROM {}",
-            self.select.clone().unwrap_or_else(|| String::from("Id")),
-            self.from
-        );
+        if let Some(soql) = &self.list_view_query {
+            return soql.clone();
+        }
+        if self.count {
+            return self.generate_count();
+        }
+        let mut select = match self.dml {
+            Some(_) => String::from("Id"),
+            None => self.select.clone().unwrap_or_else(|| String::from("Id")),
+        };
+        // SOQL has no aggregates in child subqueries, the records are counted from
+        // the subquery's totalSize after the query ran
+        for relationship in &self.count_relationships {
+            select = format!("{}, (SELECT Id FROM {})", select, relationship);
+        }
+
+        let mut query = format!("SELECT {} FROM {}", select, self.from);
 
         if let Some(where_clause) = &self.where_clause {
             query = format!("{} WHERE {}", query, where_clause);
         }
 
-        if self.open_browser {
-            query = format!("{} LIMIT 1", query);
-            return query;
-        }
-
-        if let Some(groupby) = &self.groupby {
+        // SOQL has no DISTINCT, grouping by every selected field gives the same result
+        let groupby = match &self.groupby {
+            Some(groupby) => Some(groupby.clone()),
+            None if self.distinct => {
+                Some(self.select.clone().unwrap_or_else(|| String::from("Id")))
+            }
+            None => None,
+        };
+        if let Some(groupby) = groupby {
             query = format!("{} GROUP BY {}", query, groupby);
         }
         if let Some(orderby) = &self.orderby {
@@ -41,26 +210,252 @@ impl Query {
         query
     }
 
-    pub fn evaluate(&mut self, prgram: Program) -> Result<(), DynError> {
-        for node in prgram.statements {
-            self.evalute_statement(node)?;
+    // COUNT() can not be combined with other fields, grouping or ordering.
+    fn generate_count(&self) -> String {
+        let mut query = format!("SELECT COUNT() FROM {}", self.from);
+        if let Some(where_clause) = &self.where_clause {
+            query = format!("{} WHERE {}", query, where_clause);
         }
+        if let Some(limit) = &self.limit {
+            query = format!("{} LIMIT {}", query, limit);
+        }
+        query
+    }
+
+    // Reads the values of the in_file() list into the query, which is left to the run of the
+    // query so that parsing a line never touches the filesystem.
+    pub fn read_in_file(&mut self) -> Result<(), DynError> {
+        let (path, field_name) = match self.in_file.take() {
+            Some(in_file) => in_file,
+            None => return Ok(()),
+        };
+        let values = read_in_file_values(&path, &field_name)?;
+
+        let placeholder = InList::placeholder(&path);
+        let list = InList::render(&values);
+        if let Some(where_clause) = &mut self.where_clause {
+            *where_clause = where_clause.replacen(&placeholder, &list, 1);
+        }
+        if let Some(Ok(filter)) = &mut self.where_filter {
+            replace_placeholder(filter, &placeholder, &values);
+        }
+        self.in_values = Some(values);
         Ok(())
     }
 
-    fn evalute_statement(&mut self, node: Box<dyn Statement>) -> Result<(), DynError> {
+    // Splits a query whose in_file() list makes it longer than SOQL allows into
+    // several queries over consecutive chunks of the list.
+    pub fn generate_chunks(&self) -> Vec<String> {
+        let query = self.generate();
+        let values = match &self.in_values {
+            // the GraphQL API takes the list in the body of the request
+            _ if self.graphql => return vec![query],
+            Some(values) if query.len() > MAX_QUERY_LENGTH => values,
+            _ => return vec![query],
+        };
+
+        let list = InList::render(values);
+        let room = MAX_QUERY_LENGTH.saturating_sub(query.len() - list.len());
+
+        let mut chunks: Vec<Vec<String>> = vec![Vec::new()];
+        let mut chunk_length = 2;
+        for value in values {
+            let value_length = value.len() + 4;
+            let chunk = chunks.last_mut().unwrap();
+            if !chunk.is_empty() && chunk_length + value_length > room {
+                chunks.push(Vec::new());
+                chunk_length = 2;
+            }
+            chunks.last_mut().unwrap().push(value.clone());
+            chunk_length += value_length;
+        }
+
+        chunks
+            .iter()
+            .map(|chunk| query.replacen(&list, &InList::render(chunk), 1))
+            .collect()
+    }
+
+    pub fn evaluate(
+        &mut self,
+        prgram: Program,
+        object_fields: &HashMap<String, Vec<Field>>,
+    ) -> Result<Evaluation, DynError> {
+        let mut evaluation = Evaluation::default();
+        for node in prgram.statements {
+            self.evalute_statement(node, object_fields, &mut evaluation)?;
+        }
+
+        if let Some(dml) = &self.dml {
+            self.check_dml(dml)?;
+            return Ok(evaluation);
+        }
+        if self.list_view.is_some() {
+            self.check_list_view()?;
+            return Ok(evaluation);
+        }
+        if self.count {
+            let ignored = [
+                ("select()", self.select.is_some()),
+                ("groupby()", self.groupby.is_some()),
+                ("orderby()", self.orderby.is_some()),
+                ("distinct()", self.distinct),
+                ("withcount()", !self.count_relationships.is_empty()),
+            ];
+            for (method, called) in ignored {
+                if called {
+                    evaluation
+                        .warnings
+                        .push(format!("{} is ignored because count() is called", method));
+                }
+            }
+            return Ok(evaluation);
+        }
+        if self.select.is_none() {
+            evaluation
+                .defaults
+                .push(String::from("SELECT Id, since select() is not called"));
+        }
+        for relationship in &self.count_relationships {
+            evaluation
+                .defaults
+                .push(format!("(SELECT Id FROM {}) by withcount()", relationship));
+        }
+        if self.distinct {
+            match &self.groupby {
+                Some(_) => evaluation.warnings.push(String::from(
+                    "distinct() is ignored because groupby() is called",
+                )),
+                None => evaluation.defaults.push(format!(
+                    "GROUP BY {} by distinct()",
+                    self.select.as_deref().unwrap_or("Id")
+                )),
+            }
+        }
+
+        Ok(evaluation)
+    }
+
+    // insert() and upsert() stand alone, updateAll() and delete() only take the methods
+    // that choose records.
+    fn check_dml(&self, dml: &Dml) -> Result<(), DynError> {
+        let creates = matches!(dml, Dml::Insert(_) | Dml::Upsert(_, _, _));
+        let methods = [
+            ("select()", self.select.is_some()),
+            ("where()", creates && self.where_clause.is_some()),
+            ("orderby()", creates && self.orderby.is_some()),
+            ("limit()", creates && self.limit.is_some()),
+            ("groupby()", self.groupby.is_some()),
+            ("distinct()", self.distinct),
+            ("open()", self.open_browser),
+            ("openQuery()", self.open_query),
+            ("listview()", self.list_view.is_some()),
+            ("out()", self.output_path.is_some()),
+            ("all()", self.fetch_all),
+            ("bulk()", self.bulk),
+            ("explain()", self.explain),
+            ("count()", self.count),
+            ("withcount()", !self.count_relationships.is_empty()),
+        ];
+        match methods.iter().find(|(_, called)| *called) {
+            Some((method, _)) => {
+                Err(format!("{} can not be combined with {}", dml.method(), method).into())
+            }
+            None => Ok(()),
+        }
+    }
+
+    // The query of a list view is run as it is, so only the methods deciding how to run
+    // it can be added.
+    fn check_list_view(&self) -> Result<(), DynError> {
+        let methods = [
+            ("select()", self.select.is_some()),
+            ("where()", self.where_clause.is_some()),
+            ("orderby()", self.orderby.is_some()),
+            ("groupby()", self.groupby.is_some()),
+            ("limit()", self.limit.is_some()),
+            ("distinct()", self.distinct),
+            ("count()", self.count),
+            ("withcount()", !self.count_relationships.is_empty()),
+        ];
+        match methods.iter().find(|(_, called)| *called) {
+            Some((method, _)) => Err(format!(
+                "listview() runs the query of the list view as it is and can not be combined with {}",
+                method
+            )
+            .into()),
+            None => Ok(()),
+        }
+    }
+
+    fn evalute_statement(
+        &mut self,
+        node: Box<dyn Statement>,
+        object_fields: &HashMap<String, Vec<Field>>,
+        evaluation: &mut Evaluation,
+    ) -> Result<(), DynError> {
+        if self.output_path.is_some() {
+            return Err("out() has to be the last method".into());
+        }
+        let clause = match node.node_type() {
+            NodeType::SelectStatement | NodeType::SelectAllStatement => {
+                Some(("SELECT", "select()"))
+            }
+            NodeType::WhereStatement => Some(("WHERE", "where()")),
+            NodeType::GroupByStatement => Some(("GROUP BY", "groupby()")),
+            NodeType::OrderByStatement => Some(("ORDER BY", "orderby()")),
+            NodeType::LimitStatement => Some(("LIMIT", "limit()")),
+            _ => None,
+        };
+        if let Some((clause, method)) = clause {
+            if evaluation.clauses.iter().any(|c| c == clause) {
+                evaluation.warnings.push(format!(
+                    "{} is called more than once, only the last one is used",
+                    method
+                ));
+            } else {
+                evaluation.clauses.push(clause.to_string());
+            }
+        }
+
         match node.node_type() {
             NodeType::Table => {
                 self.from = node.string();
             }
             NodeType::SelectStatement => {
                 self.select = Some(node.string());
+                self.fields = node.fields();
+            }
+            NodeType::SelectAllStatement => {
+                let fields = object_fields
+                    .get(&self.from)
+                    .ok_or_else(|| format!("select(*) needs the cached fields of {}", self.from))?;
+                let names: Vec<String> = fields
+                    .iter()
+                    .filter(|f| match node.string().as_str() {
+                        "createable" => f.createable,
+                        "updateable" => f.updateable,
+                        _ => true,
+                    })
+                    .map(|f| f.name.clone())
+                    .collect();
+                self.select = Some(names.join(", "));
+                self.fields = names;
             }
             NodeType::GroupByStatement => {
                 self.groupby = Some(node.string());
             }
             NodeType::WhereStatement => {
+                for condition in node.conditions() {
+                    if let Some(path) = condition.value.in_file() {
+                        if self.in_file.is_some() {
+                            return Err("only one in_file() is allowed per query".into());
+                        }
+                        self.in_file = Some((path, condition.field.name.clone()));
+                    }
+                }
                 self.where_clause = Some(node.string());
+                self.where_filter = Some(node.graphql_filter());
             }
             NodeType::OrderByStatement => {
                 self.orderby = Some(node.string());
@@ -71,6 +466,37 @@ impl Query {
             NodeType::OpenStatement => {
                 self.open_browser = true;
             }
+            NodeType::OutStatement => {
+                self.output_path = Some(node.string());
+            }
+            NodeType::DistinctStatement => {
+                self.distinct = true;
+            }
+            NodeType::AllStatement => {
+                self.fetch_all = true;
+            }
+            NodeType::BulkStatement => {
+                self.bulk = true;
+            }
+            NodeType::ExplainStatement => {
+                self.explain = true;
+            }
+            NodeType::OpenQueryStatement => {
+                self.open_query = true;
+            }
+            NodeType::ListViewStatement => {
+                self.list_view = Some(node.string());
+            }
+            NodeType::DmlStatement => {
+                self.dml = node.dml();
+            }
+            NodeType::CountStatement => {
+                self.count = true;
+            }
+            NodeType::WithCountStatement => {
+                self.count_relationships
+                    .extend(node.string().split(", ").map(String::from));
+            }
             _ => {
                 return Err("invalid node type".into());
             }
@@ -80,6 +506,59 @@ impl Query {
     }
 }
 
+// Reads the first column of a CSV or newline separated file. A header row named
+// after the field is skipped.
+fn read_in_file_values(path: &str, field_name: &str) -> Result<Vec<String>, DynError> {
+    let content = fs::read_to_string(path).map_err(|e| format!("Invalid file: {}: {}", path, e))?;
+
+    let mut values: Vec<String> = content
+        .lines()
+        .filter_map(|line| line.split(',').next())
+        .map(|value| value.trim().trim_matches('"').to_string())
+        .filter(|value| !value.is_empty())
+        .collect();
+
+    if values
+        .first()
+        .is_some_and(|v| v.eq_ignore_ascii_case(field_name))
+    {
+        values.remove(0);
+    }
+
+    if values.is_empty() {
+        return Err(format!("Invalid file: {}: no values found", path).into());
+    }
+    if values.len() > MAX_IN_FILE_VALUES {
+        return Err(format!(
+            "Invalid file: {}: more than {} values",
+            path, MAX_IN_FILE_VALUES
+        )
+        .into());
+    }
+
+    Ok(values)
+}
+
+// The GraphQL filter holds the placeholder as the value of the in_file() condition.
+fn replace_placeholder(filter: &mut serde_json::Value, placeholder: &str, values: &[String]) {
+    match filter {
+        serde_json::Value::String(value) if value == placeholder => {
+            *filter = serde_json::json!(values);
+        }
+        serde_json::Value::Array(filters) => {
+            for filter in filters {
+                replace_placeholder(filter, placeholder, values);
+            }
+        }
+        serde_json::Value::Object(filters) => {
+            for filter in filters.values_mut() {
+                replace_placeholder(filter, placeholder, values);
+            }
+        }
+        _ => {}
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -89,12 +568,12 @@ mod tests {
     #[test]
     fn test_generate_query() {
         let input = "Opportunity.select(Id, Account.Name).where(Account.Name like '%test%' or (Id = 1 and Status = 'completed')).orderby(Id, Account.Name DESC).groupby(Id, Account.Name).limit(10)";
-        let tokens = tokenize(input);
+        let tokens = tokenize(input).unwrap();
         let mut parser = Parser::new(tokens);
         let program = parser.parse().unwrap();
 
         let mut query = Query::default();
-        query.evaluate(program).unwrap();
+        query.evaluate(program, &HashMap::new()).unwrap();
         let soql = query.generate();
 
         assert_eq!("SELECT Id, Account.Name FROM Opportunity WHERE (Account.Name like '%test%' or (Id = 1 and Status = 'completed')) GROUP BY Id, Account.Name ORDER BY Id, Account.Name DESC LIMIT 10", soql);
@@ -103,28 +582,32 @@ mod tests {
     #[test]
     fn test_evaluate_select() {
         let input = "Opportunity.select(Id, Name, Account.Name, Contract.LastName)";
-        let tokens = tokenize(input);
+        let tokens = tokenize(input).unwrap();
         let mut parser = Parser::new(tokens);
         let program = parser.parse().unwrap();
 
         let mut query = Query::default();
-        query.evaluate(program).unwrap();
+        query.evaluate(program, &HashMap::new()).unwrap();
 
         assert_eq!(
             query.select.unwrap(),
             "Id, Name, Account.Name, Contract.LastName".to_string()
         );
+        assert_eq!(
+            query.fields,
+            vec!["Id", "Name", "Account.Name", "Contract.LastName"]
+        );
     }
 
     #[test]
     fn test_evaluate_groupby() {
         let input = "Opportunity.groupby(Id, Name, Account.Name, Contract.LastName)";
-        let tokens = tokenize(input);
+        let tokens = tokenize(input).unwrap();
         let mut parser = Parser::new(tokens);
         let program = parser.parse().unwrap();
 
         let mut query = Query::default();
-        query.evaluate(program).unwrap();
+        query.evaluate(program, &HashMap::new()).unwrap();
 
         assert_eq!(
             query.groupby.unwrap(),
@@ -135,12 +618,12 @@ mod tests {
     #[test]
     fn test_evaluate_where() {
         let input = "Opportunity.where(Id = 123 AND (Name = 'test' OR Account.Name LIKE '%test%') AND Status = 'Closed')";
-        let tokens = tokenize(input);
+        let tokens = tokenize(input).unwrap();
         let mut parser = Parser::new(tokens);
         let program = parser.parse().unwrap();
 
         let mut query = Query::default();
-        query.evaluate(program).unwrap();
+        query.evaluate(program, &HashMap::new()).unwrap();
 
         assert_eq!(
             query.where_clause.unwrap(),
@@ -152,12 +635,12 @@ mod tests {
     #[test]
     fn test_evaluate_orderby() {
         let input = "Account.orderby(Id, Name ASC, Account.Name DESC)";
-        let tokens = tokenize(input);
+        let tokens = tokenize(input).unwrap();
         let mut parser = Parser::new(tokens);
         let program = parser.parse().unwrap();
 
         let mut query = Query::default();
-        query.evaluate(program).unwrap();
+        query.evaluate(program, &HashMap::new()).unwrap();
 
         assert_eq!(
             query.orderby.unwrap(),
@@ -168,27 +651,481 @@ mod tests {
     #[test]
     fn test_evaluate_limit() {
         let input = "Account.limit(10)";
-        let tokens = tokenize(input);
+        let tokens = tokenize(input).unwrap();
         let mut parser = Parser::new(tokens);
         let program = parser.parse().unwrap();
 
         let mut query = Query::default();
-        query.evaluate(program).unwrap();
+        query.evaluate(program, &HashMap::new()).unwrap();
 
         assert_eq!(query.limit.unwrap(), "10");
     }
 
     #[test]
     fn test_evaluate_open() {
-        let input = "Account.open()";
-        let tokens = tokenize(input);
+        let input = "Account.orderby(CreatedDate DESC).limit(5).open()";
+        let tokens = tokenize(input).unwrap();
         let mut parser = Parser::new(tokens);
         let program = parser.parse().unwrap();
 
         let mut query = Query::default();
-        query.evaluate(program).unwrap();
+        query.evaluate(program, &HashMap::new()).unwrap();
 
         assert_eq!(query.from, "Account");
-        assert_eq!(query.open_browser, true);
+        assert!(query.open_browser);
+        assert_eq!(
+            query.generate(),
+            "SELECT Id FROM Account ORDER BY CreatedDate DESC LIMIT 5"
+        );
+    }
+
+    #[test]
+    fn test_evaluate_out() {
+        let input = "Account.select(Id, Name).out('accounts.csv')";
+        let tokens = tokenize(input).unwrap();
+        let mut parser = Parser::new(tokens);
+        let program = parser.parse().unwrap();
+
+        let mut query = Query::default();
+        query.evaluate(program, &HashMap::new()).unwrap();
+
+        assert_eq!(query.output_path(), Some("accounts.csv"));
+        assert_eq!(query.generate(), "SELECT Id, Name FROM Account");
+
+        let tokens = tokenize("Account.out('accounts.csv').limit(5)").unwrap();
+        let program = Parser::new(tokens).parse().unwrap();
+        let err = Query::default()
+            .evaluate(program, &HashMap::new())
+            .unwrap_err();
+        assert_eq!(err.to_string(), "out() has to be the last method");
+    }
+
+    #[test]
+    fn test_evaluate_all() {
+        let tokens = tokenize("Lead.select(Id, Email).all()").unwrap();
+        let program = Parser::new(tokens).parse().unwrap();
+
+        let mut query = Query::default();
+        query.evaluate(program, &HashMap::new()).unwrap();
+
+        assert!(query.fetches_all());
+        assert_eq!(query.generate(), "SELECT Id, Email FROM Lead");
+    }
+
+    #[test]
+    fn test_evaluate_bulk() {
+        let tokens = tokenize("Lead.select(Id, Email).bulk().out('leads.csv')").unwrap();
+        let program = Parser::new(tokens).parse().unwrap();
+
+        let mut query = Query::default();
+        query.evaluate(program, &HashMap::new()).unwrap();
+
+        assert!(query.uses_bulk());
+        assert_eq!(query.output_path(), Some("leads.csv"));
+        assert_eq!(query.generate(), "SELECT Id, Email FROM Lead");
+    }
+
+    #[test]
+    fn test_to_graphql() {
+        let input = "Opportunity.select(Name, Account.Name).where(Amount >= -100 and (Account.Name = 'Acme' or CloseDate = LAST_N_FISCAL_YEARS:2)).startswith(Name, '50%').limit(10)";
+        let program = Parser::new(tokenize(input).unwrap()).parse().unwrap();
+        let mut query = Query::default();
+        query.evaluate(program, &HashMap::new()).unwrap();
+        query.set_graphql();
+
+        assert_eq!(
+            query.to_graphql().unwrap(),
+            graphql::build(
+                "Opportunity",
+                &[String::from("Name"), String::from("Account.Name")],
+                Some(serde_json::json!({
+                    "and": [
+                        { "and": [
+                            { "Amount": { "gte": -100 } },
+                            { "or": [
+                                { "Account": { "Name": { "eq": "Acme" } } },
+                                { "CloseDate": { "eq": { "range": { "last_n_fiscal_years": 2 } } } },
+                            ] },
+                        ] },
+                        { "Name": { "like": "50\\%%" } },
+                    ]
+                })),
+                None,
+                Some(10),
+                false,
+            )
+            .unwrap()
+        );
+
+        let program =
+            Parser::new(tokenize("Opportunity.where(CloseDate = THIS_FISCAL_YEAR)").unwrap())
+                .parse()
+                .unwrap();
+        let mut query = Query::default();
+        query.evaluate(program, &HashMap::new()).unwrap();
+        assert_eq!(
+            query.to_graphql().unwrap(),
+            graphql::build(
+                "Opportunity",
+                &[],
+                Some(
+                    serde_json::json!({ "CloseDate": { "eq": { "literal": "THIS_FISCAL_YEAR" } } })
+                ),
+                None,
+                None,
+                false,
+            )
+            .unwrap()
+        );
+
+        let input = "Opportunity.where(StageName in('Won', 'Lost') and Type not in('New') and CloseDate = LAST_N_DAYS:30)";
+        let program = Parser::new(tokenize(input).unwrap()).parse().unwrap();
+        let mut query = Query::default();
+        query.evaluate(program, &HashMap::new()).unwrap();
+        assert_eq!(
+            query.to_graphql().unwrap(),
+            graphql::build(
+                "Opportunity",
+                &[],
+                Some(serde_json::json!({
+                    "and": [
+                        { "StageName": { "in": ["Won", "Lost"] } },
+                        { "and": [
+                            { "Type": { "nin": ["New"] } },
+                            { "CloseDate": { "eq": { "range": { "last_n_days": 30 } } } },
+                        ] },
+                    ]
+                })),
+                None,
+                None,
+                false,
+            )
+            .unwrap()
+        );
+
+        let program = Parser::new(tokenize("Opportunity.select(StageName).distinct()").unwrap())
+            .parse()
+            .unwrap();
+        let mut query = Query::default();
+        query.evaluate(program, &HashMap::new()).unwrap();
+        assert!(query.to_graphql().is_err());
+    }
+
+    #[test]
+    fn test_evaluate_explain() {
+        let tokens = tokenize("Lead.where(Email = 'a@example.com').explain()").unwrap();
+        let program = Parser::new(tokens).parse().unwrap();
+
+        let mut query = Query::default();
+        query.evaluate(program, &HashMap::new()).unwrap();
+
+        assert!(query.explains());
+        assert_eq!(
+            query.generate(),
+            "SELECT Id FROM Lead WHERE Email = 'a@example.com'"
+        );
+    }
+
+    #[test]
+    fn test_evaluate_open_query() {
+        let tokens = tokenize("Case.where(Status = 'New').openQuery()").unwrap();
+        let program = Parser::new(tokens).parse().unwrap();
+
+        let mut query = Query::default();
+        query.evaluate(program, &HashMap::new()).unwrap();
+
+        assert!(query.opens_query());
+        assert!(!query.opens_browser());
+        assert_eq!(query.generate(), "SELECT Id FROM Case WHERE Status = 'New'");
+    }
+
+    #[test]
+    fn test_evaluate_list_view() {
+        let evaluate = |input: &str| {
+            let program = Parser::new(tokenize(input).unwrap()).parse().unwrap();
+            let mut query = Query::default();
+            query.evaluate(program, &HashMap::new()).map(|_| query)
+        };
+
+        let mut query = evaluate("Account.listview('Recently Viewed').all()").unwrap();
+        assert_eq!(query.list_view(), Some("Recently Viewed"));
+        assert!(query.fetches_all());
+        query.set_list_view_query(String::from(
+            "SELECT Name FROM Account ORDER BY LastViewedDate DESC",
+        ));
+        assert_eq!(
+            query.generate(),
+            "SELECT Name FROM Account ORDER BY LastViewedDate DESC"
+        );
+
+        assert!(evaluate("Account.listview('All Accounts').where(Name = 'Acme')").is_err());
+    }
+
+    #[test]
+    fn test_evaluate_dml() {
+        let evaluate = |input: &str| {
+            let program = Parser::new(tokenize(input).unwrap()).parse().unwrap();
+            let mut query = Query::default();
+            query.evaluate(program, &HashMap::new()).map(|_| query)
+        };
+
+        let query =
+            evaluate("Account.insert(Name = 'Acme', NumberOfEmployees = -1, IsActive__c = true)")
+                .unwrap();
+        assert_eq!(
+            query.dml(),
+            Some(&Dml::Insert(
+                serde_json::json!({ "Name": "Acme", "NumberOfEmployees": -1, "IsActive__c": true })
+                    .as_object()
+                    .unwrap()
+                    .clone()
+            ))
+        );
+
+        let query = evaluate(
+            "Account.where(Rating = NULL).limit(10).updateAll(Rating = 'Hot', Site = null)",
+        )
+        .unwrap();
+        assert_eq!(
+            query.dml(),
+            Some(&Dml::Update(
+                serde_json::json!({ "Rating": "Hot", "Site": null })
+                    .as_object()
+                    .unwrap()
+                    .clone()
+            ))
+        );
+        assert_eq!(
+            query.generate(),
+            "SELECT Id FROM Account WHERE Rating = NULL LIMIT 10"
+        );
+
+        let query = evaluate("Account.upsert(External_Id__c = 'A-1', Name = 'Acme')").unwrap();
+        assert_eq!(
+            query.dml(),
+            Some(&Dml::Upsert(
+                String::from("External_Id__c"),
+                String::from("A-1"),
+                serde_json::json!({ "Name": "Acme" })
+                    .as_object()
+                    .unwrap()
+                    .clone()
+            ))
+        );
+
+        let query = evaluate("Lead.where(Email = NULL).delete()").unwrap();
+        assert_eq!(query.dml(), Some(&Dml::Delete));
+
+        assert_eq!(
+            evaluate("Account.where(Name = 'Acme').insert(Name = 'Acme')")
+                .unwrap_err()
+                .to_string(),
+            "insert() can not be combined with where()"
+        );
+        assert_eq!(
+            evaluate("Account.select(Name).delete()")
+                .unwrap_err()
+                .to_string(),
+            "delete() can not be combined with select()"
+        );
+    }
+
+    #[test]
+    fn test_generate_count() {
+        let tokens = tokenize(
+            "Lead.select(Id, Email).where(Status = 'Open').orderby(CreatedDate).limit(100).count()",
+        )
+        .unwrap();
+        let program = Parser::new(tokens).parse().unwrap();
+
+        let mut query = Query::default();
+        let evaluation = query.evaluate(program, &HashMap::new()).unwrap();
+
+        assert!(query.counts());
+        assert_eq!(
+            query.generate(),
+            "SELECT COUNT() FROM Lead WHERE Status = 'Open' LIMIT 100"
+        );
+        assert!(evaluation.defaults.is_empty());
+        assert_eq!(
+            evaluation.warnings,
+            vec![
+                "select() is ignored because count() is called",
+                "orderby() is ignored because count() is called",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_generate_distinct() {
+        let input = "Account.select(Industry, Rating).where(Rating != NULL).distinct().limit(5)";
+        let tokens = tokenize(input).unwrap();
+        let mut parser = Parser::new(tokens);
+        let program = parser.parse().unwrap();
+
+        let mut query = Query::default();
+        query.evaluate(program, &HashMap::new()).unwrap();
+
+        assert!(query.distinct);
+        assert_eq!(
+            query.generate(),
+            "SELECT Industry, Rating FROM Account WHERE Rating != NULL GROUP BY Industry, Rating LIMIT 5"
+        );
+    }
+
+    #[test]
+    fn test_evaluate_select_all() {
+        let fields = [
+            ("Id", false, false),
+            ("Name", true, true),
+            ("OwnerId", true, false),
+        ]
+        .iter()
+        .map(|(name, createable, updateable)| Field {
+            name: name.to_string(),
+            createable: *createable,
+            updateable: *updateable,
+            ..Default::default()
+        })
+        .collect();
+        let object_fields = HashMap::from([(String::from("Account"), fields)]);
+
+        for (input, expected) in [
+            ("Account.select(*)", "Id, Name, OwnerId"),
+            ("Account.select(*, createable)", "Name, OwnerId"),
+            ("Account.select(*, updateable)", "Name"),
+        ] {
+            let tokens = tokenize(input).unwrap();
+            let mut parser = Parser::new(tokens);
+            let program = parser.parse().unwrap();
+
+            let mut query = Query::default();
+            query.evaluate(program, &object_fields).unwrap();
+            assert_eq!(query.select.unwrap(), expected);
+        }
+
+        let tokens = tokenize("Contact.select(*)").unwrap();
+        let mut parser = Parser::new(tokens);
+        let program = parser.parse().unwrap();
+        let mut query = Query::default();
+        assert!(query.evaluate(program, &object_fields).is_err());
+    }
+
+    #[test]
+    fn test_generate_withcount() {
+        let input = "Account.select(Id, Name).withcount(Contacts, Opportunities).limit(10)";
+        let tokens = tokenize(input).unwrap();
+        let mut parser = Parser::new(tokens);
+        let program = parser.parse().unwrap();
+
+        let mut query = Query::default();
+        query.evaluate(program, &HashMap::new()).unwrap();
+
+        assert_eq!(query.count_relationships, vec!["Contacts", "Opportunities"]);
+        assert_eq!(
+            query.generate(),
+            "SELECT Id, Name, (SELECT Id FROM Contacts), (SELECT Id FROM Opportunities) FROM Account LIMIT 10"
+        );
+    }
+
+    #[test]
+    fn test_evaluation() {
+        let evaluate = |input: &str| {
+            let tokens = tokenize(input).unwrap();
+            let program = Parser::new(tokens).parse().unwrap();
+            Query::default().evaluate(program, &HashMap::new()).unwrap()
+        };
+
+        let evaluation = evaluate("Account.where(Name = 'Acme').limit(5).limit(10)");
+        assert_eq!(evaluation.clauses, vec!["WHERE", "LIMIT"]);
+        assert_eq!(
+            evaluation.defaults,
+            vec!["SELECT Id, since select() is not called"]
+        );
+        assert_eq!(
+            evaluation.warnings,
+            vec!["limit() is called more than once, only the last one is used"]
+        );
+
+        let evaluation =
+            evaluate("Account.select(Name).withcount(Contacts).groupby(Name).distinct()");
+        assert_eq!(
+            evaluation.defaults,
+            vec!["(SELECT Id FROM Contacts) by withcount()"]
+        );
+        assert_eq!(
+            evaluation.warnings,
+            vec!["distinct() is ignored because groupby() is called"]
+        );
+
+        let evaluation = evaluate("Account.select(Industry).distinct()");
+        assert_eq!(evaluation.defaults, vec!["GROUP BY Industry by distinct()"]);
+        assert!(evaluation.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_read_in_file() {
+        let path = std::env::temp_dir().join("soql_generator_test_read_in_file.csv");
+        fs::write(
+            &path,
+            "Id,Name\n001000000000001,\"Acme\"\n\n001000000000002,Foo\n",
+        )
+        .unwrap();
+
+        let input = format!("Account.where(Id in_file('{}'))", path.display());
+        let program = Parser::new(tokenize(&input).unwrap()).parse().unwrap();
+        let mut query = Query::default();
+        query.evaluate(program, &HashMap::new()).unwrap();
+        assert_eq!(
+            query.generate(),
+            format!(
+                "SELECT Id FROM Account WHERE Id IN in_file('{}')",
+                path.display()
+            )
+        );
+
+        query.read_in_file().unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(
+            query.generate(),
+            "SELECT Id FROM Account WHERE Id IN ('001000000000001', '001000000000002')"
+        );
+        assert_eq!(
+            query.where_filter.unwrap().unwrap(),
+            serde_json::json!({ "Id": { "in": ["001000000000001", "001000000000002"] } })
+        );
+
+        let program =
+            Parser::new(tokenize("Account.where(Id in_file('does/not/exist.csv'))").unwrap())
+                .parse()
+                .unwrap();
+        let mut query = Query::default();
+        query.evaluate(program, &HashMap::new()).unwrap();
+        assert!(query.read_in_file().is_err());
+    }
+
+    #[test]
+    fn test_generate_chunks() {
+        let values: Vec<String> = (0..10_000).map(|i| format!("{:0>18}", i)).collect();
+        let query = Query {
+            from: String::from("Account"),
+            where_clause: Some(format!("Id IN {}", InList::render(&values))),
+            in_values: Some(values.clone()),
+            ..Default::default()
+        };
+
+        let chunks = query.generate_chunks();
+        assert_eq!(chunks.len(), 3);
+        assert!(chunks.iter().all(|c| c.len() <= MAX_QUERY_LENGTH));
+        assert!(chunks[0].starts_with("SELECT Id FROM Account WHERE Id IN ('000000000000000000', "));
+        assert!(chunks[2].ends_with("'000000000000009999')"));
+
+        let query = Query {
+            from: String::from("Account"),
+            where_clause: Some(format!("Id IN {}", InList::render(&values[..10]))),
+            in_values: Some(values[..10].to_vec()),
+            ..Default::default()
+        };
+        assert_eq!(query.generate_chunks(), vec![query.generate()]);
     }
 }
@@ -0,0 +1,317 @@
+use std::{
+    error::Error,
+    fmt::{self, Display},
+};
+
+#[derive(Debug)]
+pub enum ReverseError {
+    MissingClause(String),
+    UnsupportedClause(String),
+    Subquery,
+}
+
+impl Display for ReverseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReverseError::MissingClause(clause) => {
+                write!(f, "Invalid SOQL: missing {} clause", clause)
+            }
+            ReverseError::UnsupportedClause(clause) => {
+                write!(f, "Unsupported SOQL: {} has no DSL method", clause)
+            }
+            ReverseError::Subquery => {
+                write!(f, "Unsupported SOQL: subqueries have no DSL method")
+            }
+        }
+    }
+}
+
+impl Error for ReverseError {}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Clause {
+    Select,
+    From,
+    Where,
+    GroupBy,
+    OrderBy,
+    Limit,
+    Scope,
+    Unsupported(&'static str),
+}
+
+const KEYWORDS: [(&str, Clause); 12] = [
+    ("SELECT", Clause::Select),
+    ("FROM", Clause::From),
+    ("WHERE", Clause::Where),
+    ("GROUP BY", Clause::GroupBy),
+    ("ORDER BY", Clause::OrderBy),
+    ("LIMIT", Clause::Limit),
+    ("OFFSET", Clause::Unsupported("OFFSET")),
+    ("HAVING", Clause::Unsupported("HAVING")),
+    ("WITH", Clause::Unsupported("WITH")),
+    ("USING", Clause::Scope),
+    ("FOR", Clause::Unsupported("FOR")),
+    ("TYPEOF", Clause::Unsupported("TYPEOF")),
+];
+
+// Converts a SOQL statement into the equivalent method chain.
+// Conditions are carried over as written since the where() grammar follows SOQL.
+pub fn to_dsl(soql: &str) -> Result<String, ReverseError> {
+    let clauses = split_clauses(soql.trim().trim_end_matches(';'));
+
+    let clause = |kind: Clause| {
+        clauses
+            .iter()
+            .find(|(k, _)| *k == kind)
+            .map(|(_, body)| body.as_str())
+    };
+
+    if let Some((Clause::Unsupported(name), _)) = clauses
+        .iter()
+        .find(|(k, _)| matches!(k, Clause::Unsupported(_)))
+    {
+        return Err(ReverseError::UnsupportedClause(name.to_string()));
+    }
+
+    let select = clause(Clause::Select)
+        .ok_or_else(|| ReverseError::MissingClause(String::from("SELECT")))?;
+    let object =
+        clause(Clause::From).ok_or_else(|| ReverseError::MissingClause(String::from("FROM")))?;
+
+    if select.contains('(') && select.to_ascii_uppercase().contains("SELECT") {
+        return Err(ReverseError::Subquery);
+    }
+
+    let mut dsl = object.to_string();
+    dsl.push_str(&format!(".select({})", split_list(select).join(", ")));
+    // USING SCOPE mine is what list views of "My ..." records use
+    if let Some(scope) = clause(Clause::Scope) {
+        let mut words = scope.split_whitespace();
+        match (words.next(), words.next(), words.next()) {
+            (Some(keyword), Some(scope), None)
+                if keyword.eq_ignore_ascii_case("SCOPE") && scope.eq_ignore_ascii_case("mine") =>
+            {
+                dsl.push_str(".mine()")
+            }
+            _ => return Err(ReverseError::UnsupportedClause(String::from("USING SCOPE"))),
+        }
+    }
+    if let Some(condition) = clause(Clause::Where) {
+        dsl.push_str(&format!(".where({})", condition));
+    }
+    if let Some(fields) = clause(Clause::GroupBy) {
+        dsl.push_str(&format!(".groupby({})", split_list(fields).join(", ")));
+    }
+    if let Some(options) = clause(Clause::OrderBy) {
+        let options = split_list(options)
+            .iter()
+            .map(|option| without_default_nulls(option))
+            .collect::<Result<Vec<_>, _>>()?;
+        dsl.push_str(&format!(".orderby({})", options.join(", ")));
+    }
+    if let Some(limit) = clause(Clause::Limit) {
+        dsl.push_str(&format!(".limit({})", limit));
+    }
+
+    Ok(dsl)
+}
+
+// Splits the statement at the clause keywords outside of string literals and parentheses.
+fn split_clauses(soql: &str) -> Vec<(Clause, String)> {
+    let mut clauses: Vec<(Clause, String)> = Vec::new();
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut chars = soql.char_indices().peekable();
+    let mut previous = ' ';
+
+    while let Some((i, c)) = chars.next() {
+        if in_string {
+            match c {
+                '\\' => {
+                    if let Some((_, escaped)) = chars.next() {
+                        push_char(&mut clauses, c);
+                        push_char(&mut clauses, escaped);
+                    }
+                    continue;
+                }
+                '\'' => in_string = false,
+                _ => {}
+            }
+            push_char(&mut clauses, c);
+            previous = c;
+            continue;
+        }
+
+        match c {
+            '\'' => in_string = true,
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            _ => {}
+        }
+
+        if depth == 0 && previous.is_whitespace() {
+            if let Some((kind, len)) = match_keyword(&soql[i..]) {
+                clauses.push((kind, String::new()));
+                while chars.peek().is_some_and(|(j, _)| *j < i + len) {
+                    chars.next();
+                }
+                previous = ' ';
+                continue;
+            }
+        }
+
+        push_char(&mut clauses, c);
+        previous = c;
+    }
+
+    clauses
+        .into_iter()
+        .map(|(kind, body)| (kind, body.trim().to_string()))
+        .collect()
+}
+
+fn push_char(clauses: &mut [(Clause, String)], c: char) {
+    if let Some((_, body)) = clauses.last_mut() {
+        body.push(c);
+    }
+}
+
+// Returns the clause and the byte length of its keyword when `rest` starts with one.
+// The words of GROUP BY / ORDER BY may be separated by any whitespace.
+fn match_keyword(rest: &str) -> Option<(Clause, usize)> {
+    KEYWORDS.iter().find_map(|(keyword, kind)| {
+        let mut len = 0;
+        for (n, word) in keyword.split(' ').enumerate() {
+            if n > 0 {
+                let spaces = rest[len..].len() - rest[len..].trim_start().len();
+                if spaces == 0 {
+                    return None;
+                }
+                len += spaces;
+            }
+            if !rest
+                .get(len..len + word.len())
+                .is_some_and(|w| w.eq_ignore_ascii_case(word))
+            {
+                return None;
+            }
+            len += word.len();
+        }
+
+        let boundary = rest[len..].chars().next();
+        if boundary.is_none_or(|c| c.is_whitespace()) {
+            Some((*kind, len))
+        } else {
+            None
+        }
+    })
+}
+
+// Drops NULLS FIRST from ascending and NULLS LAST from descending sort options, which
+// is how SOQL sorts nulls anyway. List view queries spell them out on every option.
+fn without_default_nulls(option: &str) -> Result<String, ReverseError> {
+    let upper = option.to_ascii_uppercase();
+    let descending = upper.contains(" DESC ");
+    for (suffix, default) in [(" NULLS FIRST", !descending), (" NULLS LAST", descending)] {
+        if upper.ends_with(suffix) {
+            if !default {
+                return Err(ReverseError::UnsupportedClause(suffix.trim().to_string()));
+            }
+            return Ok(option[..option.len() - suffix.len()].trim_end().to_string());
+        }
+    }
+    Ok(option.to_string())
+}
+
+// Splits a comma separated list at the top level, leaving function arguments intact.
+fn split_list(list: &str) -> Vec<String> {
+    let mut items = Vec::new();
+    let mut item = String::new();
+    let mut depth = 0;
+
+    for c in list.chars() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                items.push(item.trim().to_string());
+                item.clear();
+                continue;
+            }
+            _ => {}
+        }
+        item.push(c);
+    }
+    items.push(item.trim().to_string());
+
+    items
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_dsl() {
+        let soql = "SELECT Id, Name, Account.Name FROM Opportunity WHERE (Name LIKE '%from%' OR Amount > 100) AND StageName != 'Closed Won' ORDER BY Name DESC, Id LIMIT 10";
+        assert_eq!(
+            to_dsl(soql).unwrap(),
+            "Opportunity.select(Id, Name, Account.Name).where((Name LIKE '%from%' OR Amount > 100) AND StageName != 'Closed Won').orderby(Name DESC, Id).limit(10)"
+        );
+
+        let soql = "select calendar_year(CloseDate), sum(Amount) from Opportunity group   by calendar_year(CloseDate);";
+        assert_eq!(
+            to_dsl(soql).unwrap(),
+            "Opportunity.select(calendar_year(CloseDate), sum(Amount)).groupby(calendar_year(CloseDate))"
+        );
+    }
+
+    #[test]
+    fn test_to_dsl_list_view() {
+        let soql = "SELECT Name, toLabel(Type), Owner.Alias, Id FROM Account USING SCOPE mine WHERE Type = 'Customer' ORDER BY Name ASC NULLS FIRST, CreatedDate DESC NULLS LAST, Id ASC NULLS FIRST";
+        assert_eq!(
+            to_dsl(soql).unwrap(),
+            "Account.select(Name, toLabel(Type), Owner.Alias, Id).mine().where(Type = 'Customer').orderby(Name ASC, CreatedDate DESC, Id ASC)"
+        );
+    }
+
+    #[test]
+    fn test_to_dsl_string_literals() {
+        let soql = "SELECT Id FROM Account WHERE Name = 'it\\'s WHERE x LIMIT 1'";
+        assert_eq!(
+            to_dsl(soql).unwrap(),
+            "Account.select(Id).where(Name = 'it\\'s WHERE x LIMIT 1')"
+        );
+    }
+
+    #[test]
+    fn test_to_dsl_unsupported() {
+        let err = to_dsl("SELECT Id FROM Account LIMIT 10 OFFSET 5").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Unsupported SOQL: OFFSET has no DSL method"
+        );
+
+        let err = to_dsl("SELECT Id, (SELECT Id FROM Contacts) FROM Account").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Unsupported SOQL: subqueries have no DSL method"
+        );
+
+        let err = to_dsl("SELECT Id FROM Account ORDER BY Name ASC NULLS LAST").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Unsupported SOQL: NULLS LAST has no DSL method"
+        );
+
+        let err = to_dsl("SELECT Id FROM Account USING SCOPE team").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Unsupported SOQL: USING SCOPE has no DSL method"
+        );
+
+        let err = to_dsl("SELECT Id").unwrap_err();
+        assert_eq!(err.to_string(), "Invalid SOQL: missing FROM clause");
+    }
+}
@@ -11,19 +11,52 @@ pub enum TokenKind {
     Integer,
     Identifire,
     StringObject,
+    Date,
+    DateLiteral,
     Plus,
     Minus,
+    Asterisk,
     // Methods
     Select,
     Where,
+    AndWhere,
+    OrWhere,
     Orderby,
     Groupby,
     Limit,
     Open,
+    OpenQuery,
+    Out,
+    Distinct,
+    All,
+    Bulk,
+    Explain,
+    Count,
+    Insert,
+    UpdateAll,
+    Upsert,
+    Delete,
+    Contains,
+    Startswith,
+    Endswith,
+    Withcount,
+    Listview,
+    Mine,
+    Ownedby,
+    Createdby,
+    On,
     // Method Operators
     And,
     Or,
     Like,
+    Between,
+    IsNull,
+    IsNotNull,
+    In,
+    Not,
+    NotIn,
+    Includes,
+    InFile,
     Eq,
     NotEq,
     Greater,
@@ -33,6 +66,7 @@ pub enum TokenKind {
     True,
     False,
     Null,
+    Raw,
     // Orderby Option
     Asc,
     Desc,
@@ -51,17 +85,50 @@ impl fmt::Display for TokenKind {
             TokenKind::Integer => write!(f, "INTEGER"),
             TokenKind::Identifire => write!(f, "IDENTIFIRE"),
             TokenKind::StringObject => write!(f, "STRING"),
+            TokenKind::Date => write!(f, "DATE"),
+            TokenKind::DateLiteral => write!(f, "DATE_LITERAL"),
             TokenKind::Plus => write!(f, "+"),
             TokenKind::Minus => write!(f, "-"),
+            TokenKind::Asterisk => write!(f, "*"),
             TokenKind::Select => write!(f, "SELECT"),
             TokenKind::Where => write!(f, "WHERE"),
+            TokenKind::AndWhere => write!(f, "ANDWHERE"),
+            TokenKind::OrWhere => write!(f, "ORWHERE"),
             TokenKind::Orderby => write!(f, "ORDERBY"),
             TokenKind::Groupby => write!(f, "GROUPBY"),
             TokenKind::Limit => write!(f, "LIMIT"),
             TokenKind::Open => write!(f, "OPEN"),
+            TokenKind::OpenQuery => write!(f, "OPENQUERY"),
+            TokenKind::Out => write!(f, "OUT"),
+            TokenKind::Distinct => write!(f, "DISTINCT"),
+            TokenKind::All => write!(f, "ALL"),
+            TokenKind::Bulk => write!(f, "BULK"),
+            TokenKind::Explain => write!(f, "EXPLAIN"),
+            TokenKind::Count => write!(f, "COUNT"),
+            TokenKind::Insert => write!(f, "INSERT"),
+            TokenKind::UpdateAll => write!(f, "UPDATEALL"),
+            TokenKind::Upsert => write!(f, "UPSERT"),
+            TokenKind::Delete => write!(f, "DELETE"),
+            TokenKind::Contains => write!(f, "CONTAINS"),
+            TokenKind::Startswith => write!(f, "STARTSWITH"),
+            TokenKind::Endswith => write!(f, "ENDSWITH"),
+            TokenKind::Withcount => write!(f, "WITHCOUNT"),
+            TokenKind::Listview => write!(f, "LISTVIEW"),
+            TokenKind::Mine => write!(f, "MINE"),
+            TokenKind::Ownedby => write!(f, "OWNEDBY"),
+            TokenKind::Createdby => write!(f, "CREATEDBY"),
+            TokenKind::On => write!(f, "ON"),
             TokenKind::And => write!(f, "AND"),
             TokenKind::Or => write!(f, "OR"),
             TokenKind::Like => write!(f, "LIKE"),
+            TokenKind::Between => write!(f, "BETWEEN"),
+            TokenKind::IsNull => write!(f, "ISNULL"),
+            TokenKind::IsNotNull => write!(f, "ISNOTNULL"),
+            TokenKind::In => write!(f, "IN"),
+            TokenKind::Not => write!(f, "NOT"),
+            TokenKind::NotIn => write!(f, "NOT IN"),
+            TokenKind::Includes => write!(f, "INCLUDES"),
+            TokenKind::InFile => write!(f, "INFILE"),
             TokenKind::Eq => write!(f, "="),
             TokenKind::NotEq => write!(f, "!="),
             TokenKind::Greater => write!(f, ">"),
@@ -71,36 +138,97 @@ impl fmt::Display for TokenKind {
             TokenKind::True => write!(f, "TRUE"),
             TokenKind::False => write!(f, "FALSE"),
             TokenKind::Null => write!(f, "NULL"),
+            TokenKind::Raw => write!(f, "RAW"),
             TokenKind::Asc => write!(f, "ASC"),
             TokenKind::Desc => write!(f, "DESC"),
         }
     }
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, Clone)]
 pub struct Token {
     pub kind: TokenKind,
     literal: String,
+    // byte offset in the input, 0 for tokens made up by the parser
+    offset: usize,
+}
+
+// Tokens are compared by what they are, not where they are.
+impl PartialEq for Token {
+    fn eq(&self, other: &Self) -> bool {
+        self.kind == other.kind && self.literal == other.literal
+    }
 }
 
 impl Token {
     pub fn new(kind: TokenKind, literal: String) -> Self {
-        Self { kind, literal }
+        Self {
+            kind,
+            literal,
+            offset: 0,
+        }
+    }
+
+    pub fn at(mut self, offset: usize) -> Self {
+        self.offset = offset;
+        self
     }
 
     pub fn literal(&self) -> String {
         self.literal.clone()
     }
 
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
     pub fn is_query_method(&self) -> bool {
         matches!(
             self.kind,
             TokenKind::Select
                 | TokenKind::Where
+                | TokenKind::AndWhere
+                | TokenKind::OrWhere
                 | TokenKind::Orderby
                 | TokenKind::Groupby
                 | TokenKind::Limit
                 | TokenKind::Open
+                | TokenKind::OpenQuery
+                | TokenKind::Out
+                | TokenKind::Distinct
+                | TokenKind::All
+                | TokenKind::Bulk
+                | TokenKind::Explain
+                | TokenKind::Count
+                | TokenKind::Insert
+                | TokenKind::UpdateAll
+                | TokenKind::Upsert
+                | TokenKind::Delete
+                | TokenKind::Contains
+                | TokenKind::Startswith
+                | TokenKind::Endswith
+                | TokenKind::Withcount
+                | TokenKind::Listview
+                | TokenKind::Mine
+                | TokenKind::Ownedby
+                | TokenKind::Createdby
+                | TokenKind::On
+        )
+    }
+
+    pub fn is_where_method(&self) -> bool {
+        matches!(
+            self.kind,
+            TokenKind::Where
+                | TokenKind::AndWhere
+                | TokenKind::OrWhere
+                | TokenKind::Contains
+                | TokenKind::Startswith
+                | TokenKind::Endswith
+                | TokenKind::Mine
+                | TokenKind::Ownedby
+                | TokenKind::Createdby
+                | TokenKind::On
         )
     }
 
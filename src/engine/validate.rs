@@ -0,0 +1,437 @@
+use crate::cache::{parent_object, Field, SObject};
+use crate::engine::ast::*;
+use crate::engine::token::TokenKind;
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt::{self, Display},
+};
+
+#[derive(Debug)]
+pub enum ValidationError {
+    TypeMismatch(String, String, String),
+    IdPrefixMismatch(String, String, String),
+}
+
+impl Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationError::TypeMismatch(field, field_type, value) => {
+                write!(
+                    f,
+                    "Type mismatch: {} is a {} field. got {}",
+                    field, field_type, value
+                )
+            }
+            ValidationError::IdPrefixMismatch(field, expected, value) => {
+                write!(
+                    f,
+                    "Id prefix mismatch: {} expects an Id of {}. got {}",
+                    field, expected, value
+                )
+            }
+        }
+    }
+}
+
+impl Error for ValidationError {}
+
+// Checks the values in where() against the cached field metadata of the queried object and
+// returns warnings for filters that are valid but most likely match nothing.
+// Fields of parent records, e.g. Owner.IsActive, are checked against the parent object.
+// Objects and fields that are not in the cache are left to Salesforce to reject.
+pub fn validate(
+    program: &Program,
+    objects: &[SObject],
+    object_fields: &HashMap<String, Vec<Field>>,
+) -> Result<Vec<String>, ValidationError> {
+    let mut warnings = Vec::new();
+
+    let object_name = match program
+        .statements
+        .iter()
+        .find(|s| matches!(s.node_type(), NodeType::Table))
+    {
+        Some(table) => table.string(),
+        None => return Ok(warnings),
+    };
+    if !object_fields.contains_key(&object_name) {
+        return Ok(warnings);
+    }
+
+    for statement in &program.statements {
+        for condition in statement.conditions() {
+            validate_condition(
+                condition,
+                &object_name,
+                object_fields,
+                objects,
+                &mut warnings,
+            )?;
+        }
+    }
+
+    Ok(warnings)
+}
+
+// The field at the end of a path such as Owner.IsActive, with the object it belongs to.
+// None when an object or field on the way is not cached.
+pub fn find_field<'a>(
+    object_fields: &'a HashMap<String, Vec<Field>>,
+    object_name: &'a str,
+    path: &str,
+) -> Option<(&'a str, &'a Field)> {
+    let mut object_name = object_name;
+    let mut path: Vec<&str> = path.split('.').collect();
+    let field_name = path.pop().unwrap_or_default();
+    for relationship in path {
+        object_name = parent_object(object_fields, object_name, relationship)?;
+    }
+    let field = object_fields
+        .get(object_name)?
+        .iter()
+        .find(|f| f.name.eq_ignore_ascii_case(field_name))?;
+    Some((object_name, field))
+}
+
+fn validate_condition(
+    condition: &Condition,
+    object_name: &str,
+    object_fields: &HashMap<String, Vec<Field>>,
+    objects: &[SObject],
+    warnings: &mut Vec<String>,
+) -> Result<(), ValidationError> {
+    let (object_name, field) = match find_field(object_fields, object_name, &condition.field.name) {
+        Some(found) => found,
+        None => return Ok(()),
+    };
+
+    let kind = match condition.value.value_kind() {
+        Some(kind) => kind,
+        None => return Ok(()),
+    };
+
+    if accepts(&field.field_type, &kind) == Some(false) {
+        return Err(ValidationError::TypeMismatch(
+            field.name.clone(),
+            field.field_type.clone(),
+            condition.value.string(),
+        ));
+    }
+
+    if kind == TokenKind::StringObject {
+        let referenced = match field.field_type.as_str() {
+            "id" => vec![object_name.to_string()],
+            "reference" => field.reference_to.clone(),
+            _ => Vec::new(),
+        };
+        check_id_prefix(
+            field,
+            &referenced,
+            &condition.value.token_literal(),
+            objects,
+        )?;
+    }
+
+    if condition.operator.token.kind == TokenKind::Eq {
+        let value = condition.value.token_literal();
+        // a multi-select picklist is compared with its values joined by semicolons
+        let values: Vec<&str> = match field.field_type.as_str() {
+            "picklist" => vec![&value],
+            "multipicklist" => value.split(';').map(str::trim).collect(),
+            _ => Vec::new(),
+        };
+        for value in values {
+            if let Some(warning) = check_picklist_value(field, value) {
+                warnings.push(warning);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Compares the 3-character key prefix of a 15 or 18 character Id literal with the
+// prefixes of the objects the field can point to.
+fn check_id_prefix(
+    field: &Field,
+    referenced: &[String],
+    value: &str,
+    objects: &[SObject],
+) -> Result<(), ValidationError> {
+    if value.len() != 15 && value.len() != 18 {
+        return Ok(());
+    }
+
+    let prefix_of = |name: &String| {
+        objects
+            .iter()
+            .find(|o| o.name == *name)
+            .and_then(|o| o.key_prefix.clone())
+    };
+    let expected: Vec<(&String, String)> = referenced
+        .iter()
+        .filter_map(|name| prefix_of(name).map(|prefix| (name, prefix)))
+        .collect();
+
+    if expected.is_empty() || expected.iter().any(|(_, prefix)| value.starts_with(prefix)) {
+        return Ok(());
+    }
+
+    let expected = expected
+        .iter()
+        .map(|(name, prefix)| format!("{} ({})", name, prefix))
+        .collect::<Vec<String>>()
+        .join(" or ");
+    let actual = match objects
+        .iter()
+        .find(|o| o.key_prefix.as_deref() == value.get(..3))
+    {
+        Some(object) => format!("'{}' which is a {} Id", value, object.name),
+        None => format!("'{}'", value),
+    };
+
+    Err(ValidationError::IdPrefixMismatch(
+        field.name.clone(),
+        expected,
+        actual,
+    ))
+}
+
+fn check_picklist_value(field: &Field, value: &str) -> Option<String> {
+    if field.picklist_values.is_empty() {
+        return None;
+    }
+
+    let active = field.picklist_values.iter().filter(|p| p.active);
+    if active.clone().any(|p| p.value == value) {
+        return None;
+    }
+
+    if let Some(p) = active.clone().find(|p| p.value.eq_ignore_ascii_case(value)) {
+        return Some(format!(
+            "'{}' differs in case from the picklist value '{}' of {}",
+            value, p.value, field.name
+        ));
+    }
+
+    if field.picklist_values.iter().any(|p| p.value == value) {
+        return Some(format!(
+            "'{}' is an inactive picklist value of {}",
+            value, field.name
+        ));
+    }
+
+    let values: Vec<&str> = active.map(|p| p.value.as_str()).collect();
+    Some(format!(
+        "'{}' is not a picklist value of {} (expected one of: {})",
+        value,
+        field.name,
+        values.join(", ")
+    ))
+}
+
+// Returns None for field types this check does not know about (address, location, ...).
+fn accepts(field_type: &str, kind: &TokenKind) -> Option<bool> {
+    if *kind == TokenKind::Null {
+        return Some(true);
+    }
+
+    let accepted = match field_type {
+        "id" | "reference" | "string" | "picklist" | "multipicklist" | "combobox" | "textarea"
+        | "phone" | "email" | "url" | "encryptedstring" => *kind == TokenKind::StringObject,
+        "int" | "long" | "double" | "currency" | "percent" => *kind == TokenKind::Integer,
+        "boolean" => matches!(kind, TokenKind::True | TokenKind::False),
+        "date" | "datetime" => matches!(kind, TokenKind::Date | TokenKind::DateLiteral),
+        _ => return None,
+    };
+
+    Some(accepted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::PicklistValue;
+    use crate::engine::lexer::tokenize;
+    use crate::engine::parse::Parser;
+
+    fn objects() -> Vec<SObject> {
+        [("Account", "001"), ("Contact", "003"), ("User", "005")]
+            .iter()
+            .map(|(name, prefix)| SObject {
+                name: name.to_string(),
+                key_prefix: Some(prefix.to_string()),
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    fn object_fields() -> HashMap<String, Vec<Field>> {
+        let mut fields: Vec<Field> = [
+            ("Name", "string"),
+            ("AnnualRevenue", "currency"),
+            ("IsDeleted", "boolean"),
+            ("CreatedDate", "datetime"),
+            ("Rating", "picklist"),
+            ("Id", "id"),
+            ("OwnerId", "reference"),
+            ("Regions__c", "multipicklist"),
+        ]
+        .iter()
+        .map(|(name, field_type)| Field {
+            name: name.to_string(),
+            field_type: field_type.to_string(),
+            ..Default::default()
+        })
+        .collect();
+
+        fields[4].picklist_values = [("Hot", true), ("Warm", true), ("Cold", false)]
+            .iter()
+            .map(|(value, active)| PicklistValue {
+                value: value.to_string(),
+                label: None,
+                active: *active,
+            })
+            .collect();
+        fields[7].picklist_values = ["APAC", "EMEA"]
+            .iter()
+            .map(|value| PicklistValue {
+                value: value.to_string(),
+                label: None,
+                active: true,
+            })
+            .collect();
+
+        fields[6].reference_to = vec![String::from("User")];
+        fields[6].relationship_name = Some(String::from("Owner"));
+
+        let user_fields = vec![
+            Field {
+                name: String::from("IsActive"),
+                field_type: String::from("boolean"),
+                ..Default::default()
+            },
+            Field {
+                name: String::from("Id"),
+                field_type: String::from("id"),
+                ..Default::default()
+            },
+        ];
+
+        HashMap::from([
+            (String::from("Account"), fields),
+            (String::from("User"), user_fields),
+        ])
+    }
+
+    fn validate_input(input: &str) -> Result<Vec<String>, ValidationError> {
+        let tokens = tokenize(input).unwrap();
+        let mut parser = Parser::new(tokens);
+        let program = parser.parse().unwrap();
+        validate(&program, &objects(), &object_fields())
+    }
+
+    #[test]
+    fn test_validate_matching_types() {
+        let input = "Account.where(Name = 'test' AND AnnualRevenue > -1000 AND IsDeleted = false AND CreatedDate >= 2024-01-01T00:00:00Z OR Name != NULL)";
+        assert!(validate_input(input).is_ok());
+    }
+
+    #[test]
+    fn test_validate_type_mismatch() {
+        let input = "Account.where(Name = 'test').andWhere(AnnualRevenue > '1000')";
+        let err = validate_input(input).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Type mismatch: AnnualRevenue is a currency field. got '1000'"
+        );
+
+        assert!(validate_input("Account.where(IsDeleted = 1)").is_err());
+        assert!(validate_input("Account.where(CreatedDate = LAST_N_FISCAL_YEARS:2)").is_ok());
+
+        let err = validate_input("Account.where(Name = THIS_FISCAL_QUARTER)").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Type mismatch: Name is a string field. got THIS_FISCAL_QUARTER"
+        );
+        assert!(validate_input("Account.where(CreatedDate > '2024-01-01')").is_err());
+    }
+
+    #[test]
+    fn test_validate_unknown_object_or_field() {
+        assert!(validate_input("Contact.where(Name = 1)").is_ok());
+        assert!(validate_input("Account.where(Unknown__c = 1)").is_ok());
+        assert!(validate_input("Account.where(Parent.Name = 1)").is_ok());
+    }
+
+    #[test]
+    fn test_validate_parent_field() {
+        assert!(validate_input("Account.where(Owner.IsActive = true)").is_ok());
+
+        let err = validate_input("Account.where(owner.IsActive = 'yes')").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Type mismatch: IsActive is a boolean field. got 'yes'"
+        );
+
+        let err = validate_input("Account.where(Owner.Id = '0015g00000XyZabAAB')").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Id prefix mismatch: Id expects an Id of User (005). got '0015g00000XyZabAAB' which is a Account Id"
+        );
+    }
+
+    #[test]
+    fn test_validate_picklist_value() {
+        let warnings = validate_input("Account.where(Rating = 'Hot' OR Rating != 'hot')").unwrap();
+        assert!(warnings.is_empty());
+
+        let warnings = validate_input("Account.where(Rating = 'hot')").unwrap();
+        assert_eq!(
+            warnings,
+            vec!["'hot' differs in case from the picklist value 'Hot' of Rating"]
+        );
+
+        let warnings = validate_input("Account.where(Rating = 'Cold')").unwrap();
+        assert_eq!(
+            warnings,
+            vec!["'Cold' is an inactive picklist value of Rating"]
+        );
+
+        let warnings = validate_input("Account.where(Rating = 'Boiling')").unwrap();
+        assert_eq!(
+            warnings,
+            vec!["'Boiling' is not a picklist value of Rating (expected one of: Hot, Warm)"]
+        );
+
+        let warnings = validate_input("Account.where(Regions__c = 'APAC;EMEA')").unwrap();
+        assert!(warnings.is_empty());
+        let warnings = validate_input("Account.where(Regions__c = 'APAC; Americas')").unwrap();
+        assert_eq!(
+            warnings,
+            vec!["'Americas' is not a picklist value of Regions__c (expected one of: APAC, EMEA)"]
+        );
+    }
+
+    #[test]
+    fn test_validate_id_prefix() {
+        let input = "Account.where(Id = '0015g00000XyZabAAB' AND OwnerId = '0055g00000AbCdE')";
+        assert!(validate_input(input).is_ok());
+
+        let err = validate_input("Account.where(Id = '0035g00000XyZabAAB')").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Id prefix mismatch: Id expects an Id of Account (001). got '0035g00000XyZabAAB' which is a Contact Id"
+        );
+
+        let err = validate_input("Account.where(OwnerId = 'a015g00000AbCdE')").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Id prefix mismatch: OwnerId expects an Id of User (005). got 'a015g00000AbCdE'"
+        );
+
+        // case: partial ids are not checked
+        assert!(validate_input("Account.where(Id LIKE '003%')").is_ok());
+    }
+}
@@ -0,0 +1,254 @@
+use crate::cache::Field;
+use crate::helper::DynError;
+use crate::output::value_at;
+use crate::result_schema::{infer_columns, Column, ColumnType};
+use chrono::{DateTime, NaiveDate, NaiveTime, Timelike};
+use parquet::basic::{Compression, LogicalType, Repetition, TimeUnit, Type as PhysicalType};
+use parquet::data_type::{BoolType, ByteArray, ByteArrayType, DoubleType, Int32Type, Int64Type};
+use parquet::file::properties::WriterProperties;
+use parquet::file::writer::SerializedFileWriter;
+use parquet::format::MilliSeconds;
+use parquet::schema::types::Type;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::io::Write;
+use std::sync::Arc;
+
+// Writes records as a Parquet file with one nullable column per result column. The
+// column types come from the cached describe metadata (see infer_columns), so that
+// dates, timestamps and numbers keep their types in DuckDB or Spark.
+pub fn write_parquet<W: Write + Send>(
+    records: &[Value],
+    object_fields: &HashMap<String, Vec<Field>>,
+    fields: &[String],
+    out: W,
+) -> Result<(), DynError> {
+    let columns = infer_columns(records, object_fields, fields);
+    let fields = columns
+        .iter()
+        .map(|column| parquet_type(column).map(Arc::new))
+        .collect::<Result<Vec<_>, _>>()?;
+    let schema = Type::group_type_builder("schema")
+        .with_fields(fields)
+        .build()?;
+    let properties = WriterProperties::builder()
+        .set_compression(Compression::SNAPPY)
+        .build();
+
+    let mut writer = SerializedFileWriter::new(out, Arc::new(schema), Arc::new(properties))?;
+    let mut row_group = writer.next_row_group()?;
+    for column in &columns {
+        let mut column_writer = row_group
+            .next_column()?
+            .ok_or("Parquet schema has fewer columns than the result")?;
+        let values: Vec<Option<&Value>> = records
+            .iter()
+            .map(|record| value_at(record, &column.name))
+            .collect();
+
+        match column.column_type {
+            ColumnType::Text => {
+                let (values, levels) = collect(&values, |value| {
+                    let text = match value {
+                        Value::String(s) => s.clone(),
+                        value => value.to_string(),
+                    };
+                    Some(ByteArray::from(text.into_bytes()))
+                });
+                column_writer
+                    .typed::<ByteArrayType>()
+                    .write_batch(&values, Some(&levels), None)?;
+            }
+            ColumnType::Integer => {
+                let (values, levels) = collect(&values, |value| {
+                    value.as_i64().or_else(|| value.as_f64().map(|n| n as i64))
+                });
+                column_writer
+                    .typed::<Int64Type>()
+                    .write_batch(&values, Some(&levels), None)?;
+            }
+            ColumnType::Decimal => {
+                let (values, levels) = collect(&values, Value::as_f64);
+                column_writer
+                    .typed::<DoubleType>()
+                    .write_batch(&values, Some(&levels), None)?;
+            }
+            ColumnType::Boolean => {
+                let (values, levels) = collect(&values, Value::as_bool);
+                column_writer
+                    .typed::<BoolType>()
+                    .write_batch(&values, Some(&levels), None)?;
+            }
+            ColumnType::Date => {
+                let (values, levels) = collect(&values, |value| {
+                    let date = NaiveDate::parse_from_str(value.as_str()?, "%Y-%m-%d").ok()?;
+                    Some((date - NaiveDate::default()).num_days() as i32)
+                });
+                column_writer
+                    .typed::<Int32Type>()
+                    .write_batch(&values, Some(&levels), None)?;
+            }
+            ColumnType::DateTime => {
+                let (values, levels) = collect(&values, |value| {
+                    DateTime::parse_from_str(value.as_str()?, "%Y-%m-%dT%H:%M:%S%.f%z")
+                        .ok()
+                        .map(|datetime| datetime.timestamp_millis())
+                });
+                column_writer
+                    .typed::<Int64Type>()
+                    .write_batch(&values, Some(&levels), None)?;
+            }
+            ColumnType::Time => {
+                let (values, levels) = collect(&values, |value| {
+                    let time = NaiveTime::parse_from_str(value.as_str()?, "%H:%M:%S%.fZ").ok()?;
+                    Some(
+                        (time.num_seconds_from_midnight() * 1000 + time.nanosecond() / 1_000_000)
+                            as i32,
+                    )
+                });
+                column_writer
+                    .typed::<Int32Type>()
+                    .write_batch(&values, Some(&levels), None)?;
+            }
+        }
+        column_writer.close()?;
+    }
+    row_group.close()?;
+    writer.close()?;
+
+    Ok(())
+}
+
+fn parquet_type(column: &Column) -> Result<Type, DynError> {
+    let (physical_type, logical_type) = match column.column_type {
+        ColumnType::Text => (PhysicalType::BYTE_ARRAY, Some(LogicalType::String)),
+        ColumnType::Integer => (PhysicalType::INT64, None),
+        ColumnType::Decimal => (PhysicalType::DOUBLE, None),
+        ColumnType::Boolean => (PhysicalType::BOOLEAN, None),
+        ColumnType::Date => (PhysicalType::INT32, Some(LogicalType::Date)),
+        ColumnType::DateTime => (
+            PhysicalType::INT64,
+            Some(LogicalType::Timestamp {
+                is_adjusted_to_u_t_c: true,
+                unit: TimeUnit::MILLIS(MilliSeconds {}),
+            }),
+        ),
+        ColumnType::Time => (
+            PhysicalType::INT32,
+            Some(LogicalType::Time {
+                is_adjusted_to_u_t_c: true,
+                unit: TimeUnit::MILLIS(MilliSeconds {}),
+            }),
+        ),
+    };
+
+    Ok(Type::primitive_type_builder(&column.name, physical_type)
+        .with_repetition(Repetition::OPTIONAL)
+        .with_logical_type(logical_type)
+        .build()?)
+}
+
+// Splits the values of a column into the non-null values and the definition levels
+// Parquet uses to mark nulls. Values that do not convert are written as null.
+fn collect<T>(
+    values: &[Option<&Value>],
+    convert: impl Fn(&Value) -> Option<T>,
+) -> (Vec<T>, Vec<i16>) {
+    let mut converted = Vec::new();
+    let mut levels = Vec::new();
+    for value in values {
+        match value.filter(|v| !v.is_null()).and_then(&convert) {
+            Some(value) => {
+                converted.push(value);
+                levels.push(1);
+            }
+            None => levels.push(0),
+        }
+    }
+    (converted, levels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+    use serde_json::json;
+    use std::fs::File;
+
+    #[test]
+    fn test_write_parquet() {
+        let object_fields = HashMap::from([(
+            String::from("Opportunity"),
+            [
+                ("Name", "string"),
+                ("Amount", "currency"),
+                ("CloseDate", "date"),
+                ("CreatedDate", "datetime"),
+                ("IsWon", "boolean"),
+            ]
+            .iter()
+            .map(|(name, field_type)| Field {
+                name: name.to_string(),
+                field_type: field_type.to_string(),
+                ..Default::default()
+            })
+            .collect(),
+        )]);
+        let records = vec![
+            json!({
+                "attributes": { "type": "Opportunity" },
+                "Name": "Big deal",
+                "Amount": 1000,
+                "CloseDate": "2024-01-02",
+                "CreatedDate": "2024-01-01T09:00:00.000+0000",
+                "IsWon": true,
+            }),
+            json!({
+                "attributes": { "type": "Opportunity" },
+                "Name": "Small deal",
+                "Amount": null,
+                "CloseDate": null,
+                "CreatedDate": "2024-01-01T10:30:00.000+0000",
+                "IsWon": false,
+            }),
+        ];
+
+        let path = std::env::temp_dir().join("soql_generator_test_write_parquet.parquet");
+        write_parquet(&records, &object_fields, &[], File::create(&path).unwrap()).unwrap();
+
+        let reader = SerializedFileReader::new(File::open(&path).unwrap()).unwrap();
+        assert_eq!(reader.metadata().file_metadata().num_rows(), 2);
+        let schema: Vec<String> = reader
+            .metadata()
+            .file_metadata()
+            .schema_descr()
+            .columns()
+            .iter()
+            .map(|c| format!("{} {}", c.name(), c.physical_type()))
+            .collect();
+        assert_eq!(
+            schema,
+            vec![
+                "Amount DOUBLE",
+                "CloseDate INT32",
+                "CreatedDate INT64",
+                "IsWon BOOLEAN",
+                "Name BYTE_ARRAY",
+            ]
+        );
+
+        let rows: Vec<String> = reader
+            .get_row_iter(None)
+            .unwrap()
+            .map(|row| row.unwrap().to_string())
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                "{Amount: 1000.0, CloseDate: 2024-01-02, CreatedDate: 2024-01-01 09:00:00 +00:00, IsWon: true, Name: \"Big deal\"}",
+                "{Amount: null, CloseDate: null, CreatedDate: 2024-01-01 10:30:00 +00:00, IsWon: false, Name: \"Small deal\"}",
+            ]
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,156 @@
+use crate::helper::DynError;
+use jaq_core::load::{Arena, File, Loader};
+use jaq_core::{load, Compiler, Ctx, RcIter};
+use jaq_json::Val;
+use serde_json::Value;
+
+// Applies a jq expression to a query response, e.g. `.records[] | {Name, Contacts:
+// [.Contacts.records?[].Email]}`, and returns every value it outputs.
+pub fn apply_filter(expr: &str, input: &Value) -> Result<Vec<Value>, DynError> {
+    let program = File {
+        code: expr,
+        path: (),
+    };
+    let loader = Loader::new(jaq_std::defs().chain(jaq_json::defs()));
+    let arena = Arena::default();
+    let modules = loader
+        .load(&arena, program)
+        .map_err(|errors| load_error(expr, errors))?;
+    let filter = Compiler::default()
+        .with_funs(jaq_std::funs().chain(jaq_json::funs()))
+        .compile(modules)
+        .map_err(|errors| {
+            let undefined: Vec<String> = errors
+                .into_iter()
+                .flat_map(|(_, errors)| errors)
+                .map(|(name, undefined)| format!("undefined {} {}", undefined.as_str(), name))
+                .collect();
+            format!("Invalid filter '{}': {}", expr, undefined.join(", "))
+        })?;
+
+    let inputs = RcIter::new(core::iter::empty());
+    filter
+        .run((Ctx::new([], &inputs), Val::from(input.clone())))
+        .map(|value| {
+            value
+                .map(Value::from)
+                .map_err(|e| format!("Filter failed: {}", e).into())
+        })
+        .collect()
+}
+
+// Splits a `| jq '<expr>'` suffix off a query line. The bar has to be outside of quoted
+// values, and the expression may be quoted with single quotes like in a shell.
+pub fn split_filter(line: &str) -> (&str, Option<String>) {
+    let mut quoted = false;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '\'' => quoted = !quoted,
+            '|' if !quoted => {
+                let rest = line[i + 1..].trim_start();
+                let expr = match rest.strip_prefix("jq") {
+                    Some(expr) if expr.starts_with(char::is_whitespace) => expr.trim(),
+                    _ => continue,
+                };
+                let expr = expr
+                    .strip_prefix('\'')
+                    .and_then(|expr| expr.strip_suffix('\''))
+                    .unwrap_or(expr);
+                return (line[..i].trim_end(), Some(expr.to_string()));
+            }
+            _ => {}
+        }
+    }
+    (line, None)
+}
+
+fn load_error(expr: &str, errors: load::Errors<&str, ()>) -> DynError {
+    let reasons: Vec<String> = errors
+        .into_iter()
+        .flat_map(|(_, error)| match error {
+            load::Error::Io(errors) => errors.into_iter().map(|(_, e)| e).collect(),
+            load::Error::Lex(errors) => errors
+                .into_iter()
+                .map(|(expected, found)| format!("expected {} at '{}'", expected.as_str(), found))
+                .collect(),
+            load::Error::Parse(errors) => errors
+                .into_iter()
+                .map(|(expected, found)| format!("expected {} at '{}'", expected.as_str(), found))
+                .collect::<Vec<String>>(),
+        })
+        .collect();
+    format!("Invalid filter '{}': {}", expr, reasons.join(", ")).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_apply_filter() {
+        let response = json!({
+            "totalSize": 2,
+            "done": true,
+            "records": [
+                {
+                    "attributes": { "type": "Account" },
+                    "Name": "Acme",
+                    "Contacts": {
+                        "totalSize": 2,
+                        "done": true,
+                        "records": [
+                            { "attributes": { "type": "Contact" }, "Email": "a@example.com" },
+                            { "attributes": { "type": "Contact" }, "Email": "b@example.com" },
+                        ],
+                    },
+                },
+                { "attributes": { "type": "Account" }, "Name": "Globex", "Contacts": null },
+            ],
+        });
+
+        assert_eq!(
+            apply_filter(
+                ".records[] | {Name, Emails: [.Contacts.records?[].Email]}",
+                &response
+            )
+            .unwrap(),
+            vec![
+                json!({ "Name": "Acme", "Emails": ["a@example.com", "b@example.com"] }),
+                json!({ "Name": "Globex", "Emails": [] }),
+            ]
+        );
+        assert_eq!(
+            apply_filter(".records | map(.Name) | length", &response).unwrap(),
+            vec![json!(2)]
+        );
+        assert!(apply_filter(".records[", &response).is_err());
+        assert!(apply_filter("nosuchfunction", &response).is_err());
+        assert!(apply_filter(".totalSize[]", &response).is_err());
+    }
+
+    #[test]
+    fn test_split_filter() {
+        assert_eq!(
+            split_filter("Account.select(Name) | jq '.records[].Name'"),
+            (
+                "Account.select(Name)",
+                Some(String::from(".records[].Name"))
+            )
+        );
+        assert_eq!(
+            split_filter("Account.where(Name = 'a | jq b')|jq .totalSize"),
+            (
+                "Account.where(Name = 'a | jq b')",
+                Some(String::from(".totalSize"))
+            )
+        );
+        assert_eq!(
+            split_filter("Account.select(Name)"),
+            ("Account.select(Name)", None)
+        );
+    }
+}
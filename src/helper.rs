@@ -1 +1,46 @@
+use crate::config::Profile;
+use directories::ProjectDirs;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
 pub type DynError = Box<dyn std::error::Error + Send + Sync + 'static>;
+
+// The metadata cache and the audit log, e.g. in ~/.cache/soql-generator on Linux,
+// ~/Library/Caches/soql-generator on macOS and %LOCALAPPDATA%\soql-generator\cache on
+// Windows.
+pub fn app_cache_dir(profile: &Profile) -> Result<PathBuf, DynError> {
+    app_dir(
+        profile.cache_dir.as_ref(),
+        project_dirs().map(|dirs| dirs.cache_dir().to_path_buf()),
+    )
+}
+
+// The REPL history, e.g. in ~/.local/share/soql-generator on Linux,
+// ~/Library/Application Support/soql-generator on macOS and
+// %APPDATA%\soql-generator\data on Windows.
+pub fn app_data_dir(profile: &Profile) -> Result<PathBuf, DynError> {
+    app_dir(
+        profile.data_dir.as_ref(),
+        project_dirs().map(|dirs| dirs.data_dir().to_path_buf()),
+    )
+}
+
+// Named by the bare application name, so that macOS does not get a bundle identifier.
+fn project_dirs() -> Option<ProjectDirs> {
+    ProjectDirs::from_path(PathBuf::from("soql-generator"))
+}
+
+fn app_dir(configured: Option<&PathBuf>, platform: Option<PathBuf>) -> Result<PathBuf, DynError> {
+    let dir = match (configured, platform) {
+        (Some(dir), _) => dir.clone(),
+        (None, Some(dir)) => dir,
+        (None, None) => env::temp_dir().join("soql-generator"),
+    };
+
+    if !dir.exists() {
+        fs::create_dir_all(&dir)?;
+    }
+
+    Ok(dir)
+}
@@ -1,3 +1,5 @@
+use crate::cache::{parent_object, ChildRelationship, Field, SObject};
+use crate::engine::{self, Incomplete};
 use crate::salesforce::Connection;
 
 use rustyline::completion::{Completer, Pair};
@@ -6,39 +8,191 @@ use rustyline::hint::{Hint, Hinter};
 use rustyline::{Context, Helper, Result, Validator};
 use std::borrow::Cow;
 use std::cell::RefCell;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use termion::{color, style};
 
+// Holds a copy of the cached metadata, so that the hinter can be replaced when the
+// connection changes, e.g. after \org.
 #[derive(Helper, Validator)]
-pub struct QueryHinter<'a> {
-    pub connection: &'a Connection,
+pub struct QueryHinter {
+    objects: Vec<SObject>,
+    object_fields: HashMap<String, Vec<Field>>,
+    child_relationships: HashMap<String, Vec<ChildRelationship>>,
     pub hints: RefCell<HashSet<QueryHint>>,
+    // offer objects that cannot be queried as well, from \set allobjects
+    pub all_objects: bool,
+    // the object of \use, which lines starting with a dot run against
+    pub object: Option<String>,
 }
 
-impl<'a> QueryHinter<'a> {
-    pub fn new(connection: &'a Connection) -> Self {
-        let objects = connection.get_cached_objects();
-        let hints = HashSet::from_iter(objects.iter().map(|s| QueryHint::new(s))).into();
-        QueryHinter { connection, hints }
+impl QueryHinter {
+    pub fn new(connection: &Connection) -> Self {
+        let hinter = QueryHinter {
+            objects: connection.objects.clone(),
+            object_fields: connection.object_fields.clone(),
+            child_relationships: connection.child_relationships.clone(),
+            hints: RefCell::default(),
+            all_objects: false,
+            object: None,
+        };
+        *hinter.hints.borrow_mut() = hinter.object_hints();
+        hinter
+    }
+
+    fn object_hints(&self) -> HashSet<QueryHint> {
+        self.objects
+            .iter()
+            .filter(|o| o.queryable || self.all_objects)
+            .map(|o| QueryHint::new(&o.name))
+            .collect()
+    }
+
+    // The API name of an object written by its API name in any case or its label.
+    fn object_name<'a>(&'a self, object: &'a str) -> &'a str {
+        self.objects
+            .iter()
+            .find(|o| o.name.eq_ignore_ascii_case(object) || o.label == object)
+            .map_or(object, |o| o.name.as_str())
+    }
+
+    // The cached fields at the end of a relationship path of an object, e.g. the fields
+    // of User for the path Account.Owner of Opportunity.
+    fn fields(&self, object: &str, path: &[&str]) -> &[Field] {
+        let object = path
+            .iter()
+            .try_fold(self.object_name(object), |object, relationship| {
+                parent_object(&self.object_fields, object, relationship)
+            });
+        object
+            .and_then(|object| self.object_fields.get(object))
+            .map_or(&[][..], |fields| fields.as_slice())
+    }
+
+    // The cached field of a path such as Account.Owner.Name.
+    fn field(&self, object: &str, path: &str) -> Option<&Field> {
+        let mut path: Vec<&str> = path.split('.').collect();
+        let name = path.pop().unwrap_or_default();
+        self.fields(object, &path)
+            .iter()
+            .find(|field| field.name.eq_ignore_ascii_case(name))
+    }
+
+    // The child relationships of an object, or of every object when it is not known.
+    fn child_relationship_hints(&self, object: Option<&str>) -> HashSet<QueryHint> {
+        let relationships: Vec<&ChildRelationship> = match object {
+            Some(object) => self
+                .child_relationships
+                .get(self.object_name(object))
+                .into_iter()
+                .flatten()
+                .collect(),
+            None => self.child_relationships.values().flatten().collect(),
+        };
+        relationships
+            .iter()
+            .filter_map(|r| r.relationship_name.as_deref())
+            .map(QueryHint::new)
+            .collect()
+    }
+
+    // Hints for the SOQL of \from-soql: objects after the FROM of the query, and child
+    // relationships after the FROM of a subquery. The object of the query is written
+    // after its subqueries, so those of the object of \use, or else of every object, are
+    // offered.
+    fn soql_hints(&self, soql: &str) -> HashSet<QueryHint> {
+        let before = soql[..word_start(soql)].trim_end();
+        if !last_word(before).eq_ignore_ascii_case("from") {
+            return HashSet::new();
+        }
+        let depth = before.chars().fold(0, |depth, c| match c {
+            '(' => depth + 1,
+            ')' => depth - 1,
+            _ => depth,
+        });
+        match depth {
+            0 => self.object_hints(),
+            _ => self.child_relationship_hints(self.object.as_deref()),
+        }
     }
 
     fn update_hints(&self, line: &str) {
-        let dot_boundary = line.rfind('.').unwrap_or(0);
-        let bracket_comma_boundary = line.rfind(|c: char| c == ',' || c == '(').unwrap_or(0);
+        if let Some(soql) = line.trim_start().strip_prefix("\\from-soql ") {
+            *self.hints.borrow_mut() = self.soql_hints(soql);
+            return;
+        }
+        let line = match &self.object {
+            Some(object) if line.starts_with('.') => Cow::Owned(format!("{}{}", object, line)),
+            _ => Cow::Borrowed(line),
+        };
+        // keep the previous hints when the line does not parse
+        let partial = match engine::parse_partial(&line) {
+            Some(partial) => partial,
+            None => return,
+        };
 
         let mut hints = self.hints.borrow_mut();
-        if dot_boundary > 0 {
-            if bracket_comma_boundary > dot_boundary {
-                let object_name = line.split('.').next().unwrap().trim();
-                *hints = HashSet::from_iter(
-                    self.connection
-                        .get_cached_object_fields(object_name)
-                        .iter()
-                        .map(|s| QueryHint::new(s)),
+        match partial.incomplete {
+            Some(Incomplete::Object) => *hints = self.object_hints(),
+            Some(Incomplete::Method) => *hints = method_hints(),
+            Some(Incomplete::Arguments(method)) if method.eq_ignore_ascii_case("withcount") => {
+                *hints = self.child_relationship_hints(partial.object.as_deref());
+            }
+            // limit(), out() and the like take no fields
+            Some(Incomplete::Arguments(method)) if !takes_fields(&method) => hints.clear(),
+            // a value compared with a picklist, e.g. StageName = 'Pro
+            Some(Incomplete::Arguments(_)) if open_string(&line).is_some() => {
+                let object = partial.object.unwrap_or_default();
+                let quote = open_string(&line).unwrap_or_default() - 1;
+                *hints = self
+                    .field(&object, compared_field(&line[..quote]))
+                    .map_or(&[][..], |field| field.picklist_values.as_slice())
+                    .iter()
+                    .filter(|v| v.active)
+                    .map(|v| QueryHint::new(&format!("{}'", escape(&v.value))))
+                    .collect();
+            }
+            Some(Incomplete::Arguments(method)) => {
+                let object = partial.object.unwrap_or_default();
+                let typed_from = word_start(&line);
+                let before = line[..typed_from].trim_end();
+                let after_space = before.len() < typed_from;
+                let in_condition = matches!(
+                    method.to_lowercase().as_str(),
+                    "where" | "andwhere" | "orwhere"
                 );
-            } else {
-                *hints = method_hints();
+                let after_value = before
+                    .ends_with(|c: char| c == '\'' || c == ')' || c.is_ascii_digit())
+                    || ["true", "false", "null"]
+                        .iter()
+                        .any(|value| last_word(before).eq_ignore_ascii_case(value));
+
+                if in_condition && after_space && self.field(&object, last_word(before)).is_some() {
+                    *hints = operator_hints();
+                } else if before.ends_with(['=', '<', '>']) {
+                    // the value of a date field can be a date literal
+                    let is_date = self
+                        .field(&object, compared_field(before))
+                        .is_some_and(|f| matches!(f.field_type.as_str(), "date" | "datetime"));
+                    *hints = match is_date {
+                        true => date_literal_hints(),
+                        false => HashSet::new(),
+                    };
+                } else if in_condition && after_space && after_value {
+                    *hints = HashSet::from([QueryHint::new("and"), QueryHint::new("or")]);
+                } else {
+                    // the relationships before the last dot of the word being typed lead
+                    // to the object whose fields are offered, e.g. User for Account.Owner.
+                    let mut path: Vec<&str> = last_word(&line).split('.').collect();
+                    path.pop();
+                    // objects that are not cached have no fields to offer
+                    *hints = HashSet::from_iter(
+                        self.fields(&object, &path)
+                            .iter()
+                            .map(|f| QueryHint::new(&f.name)),
+                    );
+                }
             }
+            None => {}
         }
     }
 }
@@ -80,7 +234,7 @@ impl QueryHint {
     }
 }
 
-impl Hinter for QueryHinter<'_> {
+impl Hinter for QueryHinter {
     type Hint = QueryHint;
 
     fn hint(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> Option<QueryHint> {
@@ -90,11 +244,7 @@ impl Hinter for QueryHinter<'_> {
 
         self.update_hints(line);
 
-        let last_word_boundary = line
-            .rfind(|c: char| c.is_whitespace() || c == '.' || c == '(' || c == ',')
-            .map(|idx| idx + 1)
-            .unwrap_or(0);
-        let line_suffix = &line[last_word_boundary..];
+        let line_suffix = &line[word_start(line)..];
 
         let hints = self.hints.borrow();
 
@@ -111,7 +261,7 @@ impl Hinter for QueryHinter<'_> {
     }
 }
 
-impl Highlighter for QueryHinter<'_> {
+impl Highlighter for QueryHinter {
     fn highlight_hint<'h>(&self, hint: &'h str) -> Cow<'h, str> {
         let styled_hint = format!(
             "{}{}{}{}",
@@ -124,7 +274,7 @@ impl Highlighter for QueryHinter<'_> {
     }
 }
 
-impl<'a> Completer for QueryHinter<'a> {
+impl Completer for QueryHinter {
     type Candidate = Pair;
 
     fn complete(
@@ -135,10 +285,7 @@ impl<'a> Completer for QueryHinter<'a> {
     ) -> Result<(usize, Vec<Pair>)> {
         self.update_hints(line);
 
-        let last_word_boundary = line
-            .rfind(|c: char| c.is_whitespace() || c == '.' || c == '(' || c == ',')
-            .map(|idx| idx + 1)
-            .unwrap_or(0);
+        let last_word_boundary = word_start(line);
         let line_suffix = &line[last_word_boundary..];
 
         let hints = self.hints.borrow();
@@ -155,13 +302,233 @@ impl<'a> Completer for QueryHinter<'a> {
     }
 }
 
+// Where the word being typed starts, or the value of a string literal still being typed,
+// which may have spaces in it.
+fn word_start(line: &str) -> usize {
+    open_string(line).unwrap_or_else(|| {
+        line.rfind(|c: char| c.is_whitespace() || c == '.' || c == '(' || c == ',')
+            .map(|idx| idx + 1)
+            .unwrap_or(0)
+    })
+}
+
+// The word at the end of the text, including the dots of a relationship path.
+fn last_word(text: &str) -> &str {
+    let text = text.trim_end();
+    let start = text
+        .rfind(|c: char| c.is_whitespace() || c == '(' || c == ',')
+        .map_or(0, |idx| idx + 1);
+    &text[start..]
+}
+
+// The field compared in a condition that ends with its operator, e.g. StageName of
+// `where(StageName =`.
+fn compared_field(condition: &str) -> &str {
+    last_word(condition.trim_end().trim_end_matches(['=', '!', '<', '>']))
+}
+
+// The offset after the opening quote of a string literal left open at the end of the line.
+fn open_string(line: &str) -> Option<usize> {
+    let mut start = None;
+    let mut chars = line.char_indices();
+    while let Some((idx, c)) = chars.next() {
+        match c {
+            '\\' if start.is_some() => {
+                chars.next();
+            }
+            '\'' if start.is_some() => start = None,
+            '\'' => start = Some(idx + 1),
+            _ => {}
+        }
+    }
+    start
+}
+
+// A picklist value as it is written in a string literal.
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('\'', "\\'")
+}
+
+fn takes_fields(method: &str) -> bool {
+    matches!(
+        method.to_lowercase().as_str(),
+        "select"
+            | "where"
+            | "andwhere"
+            | "orwhere"
+            | "orderby"
+            | "groupby"
+            | "contains"
+            | "startswith"
+            | "endswith"
+            | "on"
+            | "insert"
+            | "updateall"
+            | "upsert"
+    )
+}
+
 pub fn method_hints() -> HashSet<QueryHint> {
     let mut set = HashSet::new();
     set.insert(QueryHint::new("select("));
     set.insert(QueryHint::new("where("));
+    set.insert(QueryHint::new("andWhere("));
+    set.insert(QueryHint::new("orWhere("));
     set.insert(QueryHint::new("limit("));
     set.insert(QueryHint::new("orderby("));
     set.insert(QueryHint::new("open("));
+    set.insert(QueryHint::new("openQuery("));
+    set.insert(QueryHint::new("out("));
+    set.insert(QueryHint::new("distinct("));
+    set.insert(QueryHint::new("all("));
+    set.insert(QueryHint::new("bulk("));
+    set.insert(QueryHint::new("explain("));
+    set.insert(QueryHint::new("count("));
+    set.insert(QueryHint::new("insert("));
+    set.insert(QueryHint::new("updateAll("));
+    set.insert(QueryHint::new("upsert("));
+    set.insert(QueryHint::new("delete("));
+    set.insert(QueryHint::new("contains("));
+    set.insert(QueryHint::new("startswith("));
+    set.insert(QueryHint::new("endswith("));
+    set.insert(QueryHint::new("withcount("));
+    set.insert(QueryHint::new("listview("));
+    set.insert(QueryHint::new("mine("));
+    set.insert(QueryHint::new("ownedby("));
+    set.insert(QueryHint::new("createdby("));
+    set.insert(QueryHint::new("on("));
 
     set
 }
+
+// The operators written after the field of a condition.
+pub fn operator_hints() -> HashSet<QueryHint> {
+    let mut set = HashSet::new();
+    set.insert(QueryHint::new("="));
+    set.insert(QueryHint::new("!="));
+    set.insert(QueryHint::new("<>"));
+    set.insert(QueryHint::new(">"));
+    set.insert(QueryHint::new(">="));
+    set.insert(QueryHint::new("<"));
+    set.insert(QueryHint::new("<="));
+    set.insert(QueryHint::new("like"));
+    set.insert(QueryHint::new("in("));
+    set.insert(QueryHint::new("not in("));
+    set.insert(QueryHint::new("includes("));
+    set.insert(QueryHint::new("between("));
+    set.insert(QueryHint::new("isNull()"));
+    set.insert(QueryHint::new("isNotNull()"));
+    set.insert(QueryHint::new("in_file('"));
+
+    set
+}
+
+// Those taking a number are offered up to their colon, e.g. `LAST_N_DAYS:`.
+pub fn date_literal_hints() -> HashSet<QueryHint> {
+    engine::date_literals()
+        .map(|keyword| match keyword.contains("_N_") {
+            true => QueryHint::new(&format!("{}:", keyword)),
+            false => QueryHint::new(keyword),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hinter() -> QueryHinter {
+        let relationship = |child: &str, name: &str| ChildRelationship {
+            child_object: child.to_string(),
+            field: String::from("AccountId"),
+            relationship_name: Some(name.to_string()),
+        };
+        QueryHinter {
+            objects: ["Account", "Contact"]
+                .iter()
+                .map(|name| SObject {
+                    name: name.to_string(),
+                    queryable: true,
+                    ..Default::default()
+                })
+                .collect(),
+            object_fields: HashMap::new(),
+            child_relationships: HashMap::from([
+                (
+                    String::from("Account"),
+                    vec![
+                        relationship("Contact", "Contacts"),
+                        relationship("Opportunity", "Opportunities"),
+                    ],
+                ),
+                (String::from("Contact"), vec![relationship("Case", "Cases")]),
+            ]),
+            hints: RefCell::default(),
+            all_objects: false,
+            object: None,
+        }
+    }
+
+    fn hints(hinter: &QueryHinter, line: &str) -> Vec<String> {
+        hinter.update_hints(line);
+        let mut hints: Vec<String> = hinter
+            .hints
+            .borrow()
+            .iter()
+            .map(|hint| hint.display.clone())
+            .collect();
+        hints.sort();
+        hints
+    }
+
+    #[test]
+    fn test_operator_and_date_literal_hints() {
+        let mut hinter = hinter();
+        let field = |name: &str, field_type: &str| Field {
+            name: name.to_string(),
+            field_type: field_type.to_string(),
+            ..Default::default()
+        };
+        hinter.object_fields.insert(
+            String::from("Account"),
+            vec![field("Name", "string"), field("CreatedDate", "datetime")],
+        );
+
+        let operators = hints(&hinter, "Account.where(Name ");
+        for operator in ["=", "!=", "<>", "like", "in(", "not in(", "includes("] {
+            assert!(operators.contains(&operator.to_string()), "{}", operator);
+        }
+        let literals = hints(&hinter, "Account.where(CreatedDate >= ");
+        assert!(literals.contains(&String::from("TODAY")));
+        assert!(literals.contains(&String::from("LAST_N_DAYS:")));
+        assert!(literals.contains(&String::from("THIS_FISCAL_YEAR")));
+        assert!(hints(&hinter, "Account.where(Name = ").is_empty());
+    }
+
+    #[test]
+    fn test_child_relationship_hints() {
+        let mut hinter = hinter();
+        assert_eq!(
+            hints(&hinter, "Account.withcount("),
+            vec!["Contacts", "Opportunities"]
+        );
+        assert_eq!(
+            hints(&hinter, "\\from-soql SELECT Id, (SELECT Id FROM "),
+            vec!["Cases", "Contacts", "Opportunities"]
+        );
+        assert_eq!(
+            hints(
+                &hinter,
+                "\\from-soql SELECT Id, (SELECT Id FROM Contacts) FROM "
+            ),
+            vec!["Account", "Contact"]
+        );
+        assert!(hints(&hinter, "\\from-soql SELECT Id, (SELECT ").is_empty());
+
+        hinter.object = Some(String::from("account"));
+        assert_eq!(
+            hints(&hinter, "\\from-soql SELECT Id, (select Id from Con"),
+            vec!["Contacts", "Opportunities"]
+        );
+    }
+}
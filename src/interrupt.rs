@@ -0,0 +1,78 @@
+use crate::helper::DynError;
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio::sync::Notify;
+
+// The number of requests Ctrl-C would cancel right now.
+static WAITING: AtomicUsize = AtomicUsize::new(0);
+static CANCEL: Notify = Notify::const_new();
+
+// Decrements WAITING however the request ends, cancelled futures included.
+struct Waiting;
+
+impl Waiting {
+    fn new() -> Self {
+        WAITING.fetch_add(1, Ordering::SeqCst);
+        Waiting
+    }
+}
+
+impl Drop for Waiting {
+    fn drop(&mut self) {
+        WAITING.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+// Handles Ctrl-C for the rest of the REPL with one task. Once tokio listens for SIGINT
+// the signal no longer ends the process, so a Ctrl-C while no request can be cancelled
+// exits like it would without the handler. The prompt reads Ctrl-C as a key instead.
+pub fn listen() {
+    tokio::spawn(async {
+        while tokio::signal::ctrl_c().await.is_ok() {
+            if WAITING.load(Ordering::SeqCst) == 0 {
+                std::process::exit(130);
+            }
+            CANCEL.notify_waiters();
+        }
+    });
+}
+
+// Waits for `future` unless Ctrl-C is pressed first, which gives None. Without `listen`
+// Ctrl-C keeps its default of ending the process.
+pub async fn cancellable<F: Future>(future: F) -> Option<F::Output> {
+    let _waiting = Waiting::new();
+    tokio::select! {
+        output = future => Some(output),
+        _ = CANCEL.notified() => None,
+    }
+}
+
+// Runs a request that Ctrl-C cancels with the error `cancelled`.
+pub async fn cancellable_request<T, F>(future: F, cancelled: &str) -> Result<T, DynError>
+where
+    F: Future<Output = Result<T, DynError>>,
+{
+    cancellable(future)
+        .await
+        .unwrap_or_else(|| Err(cancelled.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_cancellable() {
+        assert_eq!(cancellable(async { 1 }).await, Some(1));
+        assert_eq!(WAITING.load(Ordering::SeqCst), 0);
+
+        let pending = tokio::spawn(cancellable(tokio::time::sleep(Duration::from_secs(60))));
+        while WAITING.load(Ordering::SeqCst) == 0 {
+            tokio::task::yield_now().await;
+        }
+        CANCEL.notify_waiters();
+        assert_eq!(pending.await.unwrap(), None);
+        assert_eq!(WAITING.load(Ordering::SeqCst), 0);
+    }
+}
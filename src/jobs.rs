@@ -0,0 +1,122 @@
+use crate::helper::DynError;
+use serde_json::Value;
+use std::future::Future;
+use std::time::Instant;
+use tokio::task::JoinHandle;
+
+// A query started with \bg. The responses are kept until the job is brought back with \fg.
+struct Job {
+    id: usize,
+    line: String,
+    count_relationships: Vec<String>,
+    fields: Vec<String>,
+    started: Instant,
+    handle: JoinHandle<Result<Vec<Value>, DynError>>,
+}
+
+#[derive(Debug)]
+pub struct FinishedJob {
+    pub responses: Vec<Value>,
+    pub count_relationships: Vec<String>,
+    pub fields: Vec<String>,
+}
+
+#[derive(Default)]
+pub struct Jobs {
+    last_id: usize,
+    jobs: Vec<Job>,
+}
+
+impl Jobs {
+    pub fn spawn<F>(
+        &mut self,
+        line: &str,
+        count_relationships: &[String],
+        fields: &[String],
+        future: F,
+    ) -> usize
+    where
+        F: Future<Output = Result<Vec<Value>, DynError>> + Send + 'static,
+    {
+        self.last_id += 1;
+        self.jobs.push(Job {
+            id: self.last_id,
+            line: line.to_string(),
+            count_relationships: count_relationships.to_vec(),
+            fields: fields.to_vec(),
+            started: Instant::now(),
+            handle: tokio::spawn(future),
+        });
+        self.last_id
+    }
+
+    // One line per job, e.g. `[1] running 12s  Account.select(Id)`.
+    pub fn list(&self) -> Vec<String> {
+        self.jobs
+            .iter()
+            .map(|job| {
+                let state = if job.handle.is_finished() {
+                    String::from("done")
+                } else {
+                    format!("running {}s", job.started.elapsed().as_secs())
+                };
+                format!("[{}] {}  {}", job.id, state, job.line)
+            })
+            .collect()
+    }
+
+    // Waits for the job to finish and removes it from the list.
+    pub async fn wait(&mut self, id: usize) -> Result<FinishedJob, DynError> {
+        let index = self
+            .jobs
+            .iter()
+            .position(|job| job.id == id)
+            .ok_or_else(|| format!("No such job: {}", id))?;
+        let job = self.jobs.remove(index);
+
+        let responses = job
+            .handle
+            .await
+            .map_err(|e| format!("Job {} failed: {}", id, e))??;
+        Ok(FinishedJob {
+            responses,
+            count_relationships: job.count_relationships,
+            fields: job.fields,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_jobs() {
+        let mut jobs = Jobs::default();
+        let id = jobs.spawn("Account.select(Id)", &[], &[], async {
+            Ok(vec![json!({ "totalSize": 0 })])
+        });
+        let failing = jobs.spawn("Account.select(Foo)", &[], &[], async {
+            Err("bad field".into())
+        });
+        assert_eq!((id, failing), (1, 2));
+
+        tokio::task::yield_now().await;
+        assert!(jobs.list()[0].starts_with("[1] "));
+        assert!(jobs.list()[0].ends_with("  Account.select(Id)"));
+
+        let job = jobs.wait(id).await.unwrap();
+        assert_eq!(job.responses, vec![json!({ "totalSize": 0 })]);
+        assert_eq!(jobs.list().len(), 1);
+
+        assert_eq!(
+            jobs.wait(failing).await.unwrap_err().to_string(),
+            "bad field"
+        );
+        assert_eq!(
+            jobs.wait(id).await.unwrap_err().to_string(),
+            "No such job: 1"
+        );
+    }
+}
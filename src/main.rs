@@ -1,21 +1,63 @@
+mod api_error;
+mod api_usage;
+mod audit;
+mod auth;
 mod cache;
+mod chart;
+mod config;
 mod engine;
+mod export;
+mod filter;
 mod helper;
 mod hint;
+mod interrupt;
+mod jobs;
+mod meta;
+mod output;
+mod pager;
+mod result_cache;
+mod result_schema;
 mod salesforce;
+mod saved_query;
+mod sensitive;
+mod settings;
 
-use crate::cache::{load_cache_from_file, save_cache_to_file};
-use crate::salesforce::Connection;
+use crate::audit::{AuditEntry, AuditLog};
+use crate::cache::{
+    diff_schema, export_schema, load_cache_from_file, load_schema_bundle, save_cache_to_file,
+    CacheData, CacheKey, Field, CACHE_EXPIRATION_DAYS,
+};
+use crate::config::{Config, Profile};
+use crate::engine::{Dml, UserIds};
+use crate::salesforce::{Connection, MAX_COLLECTION_RECORDS};
 use chrono::Utc;
-use clap::Parser;
-use dirs_next::cache_dir;
-use helper::DynError;
+use chrono_tz::Tz;
+use clap::{Parser, Subcommand};
+use helper::{app_cache_dir, app_data_dir, DynError};
 use hint::QueryHinter;
+use interrupt::cancellable_request;
+use jobs::Jobs;
+use meta::{MetaCommand, COMMANDS};
+use output::{Format, RenderOptions};
+use pager::Pager;
+use result_cache::ResultCache;
+use result_schema::infer_columns;
 use rustyline::error::ReadlineError;
 use rustyline::history::DefaultHistory;
 use rustyline::Editor;
-use std::fs;
-use std::path::PathBuf;
+use saved_query::{saved_query_path, SavedQuery};
+use sensitive::{is_sensitive, sensitive_objects};
+use serde_json::Value;
+use settings::{Backend, Settings};
+use std::collections::hash_map::Entry;
+use std::env;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::Duration;
+use termion::terminal_size;
+use tokio::task::JoinHandle;
 
 /// Tool for interactively executing SOQL queries
 #[derive(Parser, Debug)]
@@ -24,83 +66,711 @@ struct Args {
     /// query for std out mode
     #[arg(short, long)]
     query: Option<String>,
+
+    /// query Tooling API objects (ApexClass, CustomField, ...) instead of data
+    #[arg(long, global = true)]
+    tooling: bool,
+
+    /// table, json, csv, jsonl, markdown or parquet (json by default with -q and run, table in the
+    /// REPL). parquet is written to stdout and has to be redirected to a file
+    #[arg(long, global = true)]
+    format: Option<Format>,
+
+    /// show datetimes in the local timezone and numbers with thousands separators in tables
+    /// and Markdown
+    #[arg(long, global = true)]
+    localize: bool,
+
+    /// keep the attributes (type and URL) of records in JSON and JSON Lines output
+    #[arg(long, global = true)]
+    raw: bool,
+
+    /// fetch every record of a result instead of the first batch of 2000, like all()
+    #[arg(long, global = true)]
+    all: bool,
+
+    /// run queries as Bulk API 2.0 jobs and write their result as CSV, like bulk(). For
+    /// extracts of millions of records
+    #[arg(long, global = true)]
+    bulk: bool,
+
+    /// rest (the default) or graphql, which runs queries on the GraphQL API and pages
+    /// their records by cursor
+    #[arg(long, global = true)]
+    backend: Option<Backend>,
+
+    /// print only the number of records, like count()
+    #[arg(long, global = true)]
+    count: bool,
+
+    /// jq expression applied to each response before it is formatted, like `| jq` in the
+    /// REPL, e.g. '.records[] | {Name, Emails: [.Contacts.records?[].Email]}'
+    #[arg(long, global = true)]
+    filter: Option<String>,
+
+    /// org of config.yaml to use instead of the default one
+    #[arg(long, global = true)]
+    org: Option<String>,
+
+    /// print the API usage of the org after every request
+    #[arg(short, long, global = true)]
+    verbose: bool,
+
+    /// seconds a request may take before it fails, 0 for no limit (120 by default)
+    #[arg(long, global = true)]
+    timeout: Option<u64>,
+
+    /// only generate SOQL, from the schema bundle of `schema pull`, without logging in
+    #[arg(long, global = true)]
+    offline: bool,
+
+    /// run queries on sensitive objects in production without asking
+    #[arg(long, global = true)]
+    yes: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Log in through the browser and keep the session in the OS keyring for later runs,
+    /// for orgs where logging in with a password is blocked
+    Login {
+        /// show a code to enter on another device instead of opening the browser
+        #[arg(long, conflicts_with = "password")]
+        device: bool,
+        /// store a username, password and consumer secret in the keyring instead of
+        /// exporting them in the shell
+        #[arg(long)]
+        password: bool,
+    },
+    /// Remove the session and passwords stored by login from the keyring
+    Logout,
+    /// Run a query saved in a .soqlgen file
+    Run {
+        file: PathBuf,
+        /// override a parameter of the file, e.g. --param stage=Prospecting
+        #[arg(short, long = "param")]
+        params: Vec<String>,
+    },
+    /// Work with the cached org metadata
+    Schema {
+        #[command(subcommand)]
+        command: SchemaCommand,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum SchemaCommand {
+    /// Write all cached objects and fields to a JSON file
+    Export {
+        #[arg(long)]
+        out: PathBuf,
+    },
+    /// Describe every object into the schema bundle that --offline works from
+    Pull,
+    /// Periodically describe the org and log fields that changed since the last check
+    Watch {
+        /// e.g. 30s, 15m, 1h, 1d
+        #[arg(long, default_value = "1h", value_parser = parse_interval)]
+        interval: Duration,
+    },
 }
 
 #[tokio::main]
 async fn main() -> Result<(), DynError> {
     let args = Args::parse();
+    let settings = settings(&args);
+    let mut config = Config::load()?;
+    config.timeout_override = args.timeout;
 
-    if let Some(query) = args.query {
-        let conn = Connection::new().await?;
-        let (parsed_query, _open_browser) = engine::build_query(&query)?;
-        conn.call_query(&parsed_query, false).await?;
+    if let Some(Command::Login { device, password }) = args.command {
+        let profile = config.profile(args.org.as_deref())?;
+        let instance_url = if password {
+            auth::store_password(&profile)?;
+            Connection::new(args.tooling, profile).await?.instance_url()
+        } else if device {
+            auth::login_with_device(&profile).await?.instance_url
+        } else {
+            auth::login_with_browser(&profile).await?.instance_url
+        };
+        println!("Logged in to {}", instance_url);
+    } else if let Some(Command::Logout) = args.command {
+        auth::logout(&config.profile(args.org.as_deref())?)?;
+        println!("Removed the stored credentials");
+    } else if let Some(Command::Schema { command }) = args.command {
+        let profile = config.profile(args.org.as_deref())?;
+        run_schema_command(command, args.tooling, profile).await?;
+    } else if let Some(Command::Run { file, params }) = args.command {
+        let mut saved_query = SavedQuery::load(&file)?;
+        saved_query.set_params(&params)?;
+        if let Some(name) = &saved_query.name {
+            eprintln!(
+                "-- {}{}",
+                name,
+                saved_query
+                    .org
+                    .as_ref()
+                    .map_or_else(String::new, |org| format!(" (org: {})", org))
+            );
+        }
+        let format = match args.format {
+            Some(format) => format,
+            None => saved_query.format()?.unwrap_or(Format::Json),
+        };
+        // --org overrides the org the query was saved for
+        let profile = config.profile(args.org.as_deref().or(saved_query.org.as_deref()))?;
+        run_once(
+            connect(args.tooling, profile, args.offline).await?,
+            &saved_query.render()?,
+            format,
+            args.yes,
+            settings,
+            args.all,
+            args.count,
+        )
+        .await?;
+    } else if let Some(query) = args.query {
+        let profile = config.profile(args.org.as_deref())?;
+        run_once(
+            connect(args.tooling, profile, args.offline).await?,
+            &query,
+            args.format.unwrap_or(Format::Json),
+            args.yes,
+            settings,
+            args.all,
+            args.count,
+        )
+        .await?;
     } else {
-        run().await?;
+        let profile = config.profile(args.org.as_deref())?;
+        run(
+            connect(args.tooling, profile, args.offline).await?,
+            &config,
+            args.format.unwrap_or(Format::Table),
+            settings,
+            args.all,
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+// Logs in to the org, or with --offline only sets up a connection for the schema bundle.
+async fn connect(tooling: bool, profile: Profile, offline: bool) -> Result<Connection, DynError> {
+    if offline {
+        return Ok(Connection::offline(tooling, profile));
+    }
+    Connection::new(tooling, profile).await
+}
+
+// The settings given on the command line, for -q and run as well as the REPL.
+fn settings(args: &Args) -> Settings {
+    Settings {
+        localize: args.localize,
+        raw: args.raw,
+        filter: args.filter.clone(),
+        bulk: args.bulk,
+        backend: args.backend.unwrap_or_default(),
+        verbose: args.verbose,
+        ..Default::default()
+    }
+}
+
+async fn run_once(
+    mut conn: Connection,
+    line: &str,
+    format: Format,
+    yes: bool,
+    settings: Settings,
+    all: bool,
+    count: bool,
+) -> Result<(), DynError> {
+    let (line, filter) = filter::split_filter(line);
+    let filter = filter.or_else(|| settings.filter.clone());
+    conn.verbose = settings.verbose;
+    // offline, the bundle is all there is to validate the query with
+    if conn.is_offline() {
+        load_cache(&mut conn).await?;
+    }
+    let mut user_ids = UserIds {
+        current: conn.user_id(),
+        ..Default::default()
+    };
+    resolve_user_ids(&conn, line, &mut user_ids).await?;
+    let timezone = timezone(&conn).await?;
+    // values are shown in full outside the REPL
+    let settings = Settings {
+        max_width: 0,
+        ..settings
+    };
+    let (mut query, evaluation) = engine::build_query(
+        line,
+        &conn.objects,
+        &conn.object_fields,
+        &user_ids,
+        timezone,
+        false,
+    )?;
+    query.read_in_file()?;
+    for warning in &evaluation.warnings {
+        eprintln!("Warning: {}", warning);
+    }
+    if count {
+        query.set_count();
+    }
+    if settings.bulk {
+        query.set_bulk();
+    }
+    if settings.backend == Backend::Graphql {
+        query.set_graphql();
+    }
+    if conn.is_offline() {
+        for soql in query.generate_chunks() {
+            println!("{}", soql);
+        }
+        return Ok(());
+    }
+    if let Some(name) = query.list_view() {
+        let soql = list_view_query(&conn, query.object(), name).await?;
+        query.set_list_view_query(soql);
+    }
+    if let Some(object) = sensitive_object(&conn, query.object()).await? {
+        if !yes {
+            return Err(format!(
+                "{} is a sensitive object and this is a production org. Pass --yes to run the query",
+                object
+            )
+            .into());
+        }
+    }
+    log_query(&audit_log(&conn.profile)?, &conn, line, &query);
+    if let Some(dml) = query.dml() {
+        let mut confirm = |action: &str| {
+            if !yes {
+                eprintln!("{} records? Pass --yes to confirm", action);
+            }
+            yes
+        };
+        return run_dml(&conn, &query, dml, &mut confirm).await;
+    }
+    if query.opens_query() {
+        return conn.open_query(&query.generate());
+    }
+    if query.explains() {
+        return print_plans(&conn, &query, &settings, timezone, format).await;
+    }
+    if query.uses_bulk() {
+        if filter.is_some() {
+            return Err("A filter can not be used with bulk()".into());
+        }
+        return write_bulk(&conn, &query, query.output_path().map(Path::new)).await;
+    }
+    if query.counts() {
+        println!("{}", count_records(&conn, &query).await?);
+        return Ok(());
+    }
+    let fetch_all = all || query.fetches_all();
+    if let Some(expr) = filter {
+        if query.output_path().is_some() {
+            return Err("A filter can not be used with out()".into());
+        }
+        for soql in query.generate_chunks() {
+            let response = fetch(&conn, &query, &soql, fetch_all).await?;
+            print_filtered(
+                &conn,
+                &response,
+                &expr,
+                query.fields(),
+                &settings,
+                timezone,
+                format,
+            )?;
+        }
+        return Ok(());
+    }
+    if let Some(path) = query.output_path() {
+        let mut responses = Vec::new();
+        for soql in query.generate_chunks() {
+            responses.push(fetch(&conn, &query, &soql, fetch_all).await?);
+        }
+        return write_responses(
+            &conn,
+            Path::new(path),
+            &responses,
+            query.fields(),
+            settings.raw,
+        );
+    }
+    if format.is_binary() && termion::is_tty(&std::io::stdout()) {
+        return Err(format!(
+            "{} output can not be written to a terminal, redirect it to a file",
+            format
+        )
+        .into());
+    }
+    // a Parquet file holds the records of every chunk
+    let mut records = Vec::new();
+    for soql in query.generate_chunks() {
+        if fetch_all && !format.is_binary() && !query.uses_graphql() {
+            stream_response(&conn, &soql, &query, &settings, timezone, format).await?;
+            continue;
+        }
+        let response = fetch(&conn, &query, &soql, fetch_all).await?;
+        match format {
+            Format::Parquet => {
+                records.extend(response["records"].as_array().cloned().unwrap_or_default())
+            }
+            _ => {
+                let options = render_options(&conn, &response, query.fields(), &settings, timezone);
+                print_response(&response, &options, format)?
+            }
+        }
+    }
+    if format == Format::Parquet {
+        let mut buffer = Vec::new();
+        export::write_parquet(&records, &conn.object_fields, query.fields(), &mut buffer)?;
+        std::io::stdout().write_all(&buffer)?;
     }
 
     Ok(())
 }
 
-async fn run() -> Result<(), DynError> {
-    let cache_dir = match cache_dir() {
-        Some(cache_dir) => cache_dir.join("soql-generator"),
-        None => PathBuf::from("/tmp/soql-generator"),
+async fn run_schema_command(
+    command: SchemaCommand,
+    tooling: bool,
+    profile: Profile,
+) -> Result<(), DynError> {
+    match command {
+        SchemaCommand::Export { out } => {
+            let mut conn = Connection::new(tooling, profile).await?;
+            let cache_data = load_cache(&mut conn).await?;
+            export_schema(&cache_data, &out)?;
+            println!(
+                "Exported {} objects to {}",
+                cache_data.objects.len(),
+                out.display()
+            );
+        }
+        SchemaCommand::Pull => {
+            let mut conn = Connection::new(tooling, profile).await?;
+            let path = pull_schema(&mut conn).await?;
+            println!(
+                "Saved the describes of {} objects to {}",
+                conn.objects.len(),
+                path.display()
+            );
+        }
+        SchemaCommand::Watch { interval } => {
+            let mut conn = Connection::new(tooling, profile.clone()).await?;
+            let mut cache_data = load_cache(&mut conn).await?;
+            let cache_data_path = cache_data_path(&conn)?;
+            let key = cache_key(&conn)?;
+
+            loop {
+                tokio::time::sleep(interval).await;
+
+                // log in again every round, the session may have expired while sleeping
+                let mut conn = Connection::new(tooling, profile.clone()).await?;
+                conn.get_all_objects_and_fields().await?;
+                let latest = conn.cache_data();
+
+                let changes = diff_schema(&cache_data, &latest);
+                if changes.is_empty() {
+                    println!("[{}] no changes", latest.last_cached.to_rfc3339());
+                }
+                for change in changes {
+                    println!("[{}] {}", latest.last_cached.to_rfc3339(), change);
+                }
+
+                save_cache_to_file(&latest, &cache_data_path, key.as_ref())?;
+                cache_data = latest;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn parse_interval(interval: &str) -> Result<Duration, String> {
+    let unit_at = interval
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(interval.len());
+    let (value, unit) = interval.split_at(unit_at);
+    let value: u64 = value
+        .parse()
+        .map_err(|_| format!("invalid interval: {}", interval))?;
+
+    let seconds = match unit {
+        "s" => value,
+        "m" => value * 60,
+        "h" | "" => value * 60 * 60,
+        "d" => value * 60 * 60 * 24,
+        _ => return Err(format!("invalid interval unit: {}", unit)),
+    };
+    if seconds == 0 {
+        return Err(String::from("interval must be greater than 0"));
+    }
+
+    Ok(Duration::from_secs(seconds))
+}
+
+// Every query run is logged with the user and its SOQL, for reviewing what was run
+// against shared orgs.
+fn audit_log(profile: &Profile) -> Result<AuditLog, DynError> {
+    Ok(AuditLog::new(&app_cache_dir(profile)?.join("audit.jsonl")))
+}
+
+// Most entries kept in the REPL history unless history_size is set.
+const DEFAULT_HISTORY_SIZE: usize = 100;
+
+// The history was kept in the cache directory before, it is moved over the first time.
+fn history_path(profile: &Profile) -> Result<PathBuf, DynError> {
+    if let Some(history_file) = &profile.history_file {
+        if let Some(parent) = history_file.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        return Ok(history_file.clone());
+    }
+    let history_path = app_data_dir(profile)?.join("history.txt");
+    let old_path = app_cache_dir(profile)?.join("history.txt");
+    if !history_path.exists() && old_path.exists() {
+        fs::rename(&old_path, &history_path)
+            .or_else(|_| fs::copy(&old_path, &history_path).map(|_| ()))?;
+    }
+    Ok(history_path)
+}
+
+// A query that could not be logged is still run.
+fn log_query(audit_log: &AuditLog, conn: &Connection, line: &str, query: &engine::Query) {
+    let entry = AuditEntry {
+        time: Utc::now().to_rfc3339(),
+        user_id: conn.user_id(),
+        query: line.trim().to_string(),
+        soql: query.generate_chunks(),
+        why: None,
     };
+    if let Err(e) = audit_log.append(&entry) {
+        eprintln!("Failed to write the audit log: {}", e);
+    }
+}
 
-    if !cache_dir.exists() {
-        fs::create_dir_all(&cache_dir)?;
+// None unless encrypt_cache is set for the org.
+fn cache_key(conn: &Connection) -> Result<Option<CacheKey>, DynError> {
+    match conn.profile.encrypt_cache {
+        Some(true) => Ok(Some(auth::cache_key(&conn.profile)?)),
+        _ => Ok(None),
     }
+}
 
-    let history_path = cache_dir.join("history.txt");
-    let cache_data_path = cache_dir.join("cache_data.json");
+// Tooling objects and every org of config.yaml are cached separately so that hints and
+// validation follow the mode and the org.
+fn cache_data_path(conn: &Connection) -> Result<PathBuf, DynError> {
+    org_file_path(conn, "cache_data.json")
+}
 
-    let mut conn = Connection::new().await?;
-    let cache_data = match load_cache_from_file(&cache_data_path)? {
-        Some(data) => data,
-        None => {
+fn schema_bundle_path(conn: &Connection) -> Result<PathBuf, DynError> {
+    org_file_path(conn, "schema_bundle.json")
+}
+
+fn org_file_path(conn: &Connection, name: &str) -> Result<PathBuf, DynError> {
+    let mut file_name = String::from(name);
+    if let Some(org) = &conn.profile.name {
+        file_name = format!("{}_{}", org, file_name);
+    }
+    if conn.tooling {
+        file_name = format!("tooling_{}", file_name);
+    }
+    Ok(app_cache_dir(&conn.profile)?.join(file_name))
+}
+
+const DESCRIBING_MESSAGE: &str =
+    "Retrieving fields for the object. This process may take several minutes to complete.";
+
+// A background refresh of an expired metadata cache, see load_cache_or_refresh.
+type CacheRefresh = JoinHandle<Result<CacheData, DynError>>;
+
+// Loads the object metadata into the connection, describing the org when the cache is
+// missing, expired or was built from another org or API version.
+async fn load_cache(conn: &mut Connection) -> Result<CacheData, DynError> {
+    if conn.is_offline() {
+        let bundle = load_schema_bundle(&schema_bundle_path(conn)?, cache_key(conn)?.as_ref())?;
+        conn.objects = bundle.objects.clone();
+        conn.object_fields = bundle.object_fields.clone();
+        conn.child_relationships = bundle.child_relationships.clone();
+        return Ok(bundle);
+    }
+    let cache_data_path = cache_data_path(conn)?;
+    let key = cache_key(conn)?;
+
+    let cache_data = match load_cache_from_file(&cache_data_path, key.as_ref())? {
+        Some(data) if !data.is_expired() && conn.built_cache(&data) => data,
+        _ => {
+            println!("{}", DESCRIBING_MESSAGE);
             conn.get_all_objects_and_fields().await?;
-            let cache_data = cache::CacheData {
-                objects: conn.objects.clone(),
-                object_fields: conn.object_fields.clone(),
-                last_cached: Utc::now(),
-            };
-            save_cache_to_file(&cache_data, &cache_data_path)?;
+            let cache_data = conn.cache_data();
+            save_cache_to_file(&cache_data, &cache_data_path, key.as_ref())?;
             cache_data
         }
     };
+    conn.objects = cache_data.objects.clone();
+    conn.object_fields = cache_data.object_fields.clone();
+    conn.child_relationships = cache_data.child_relationships.clone();
+
+    Ok(cache_data)
+}
+
+// In the REPL an expired cache is used as it is while the org is described again in the
+// background, so that the prompt comes up right away. Returns the refresh, whose
+// metadata replaces the expired one when it finishes, see finish_refresh.
+async fn load_cache_or_refresh(conn: &mut Connection) -> Result<Option<CacheRefresh>, DynError> {
+    if !conn.is_offline() {
+        let stale = load_cache_from_file(&cache_data_path(conn)?, cache_key(conn)?.as_ref())?
+            .filter(|stale| stale.is_expired() && conn.built_cache(stale));
+        if let Some(stale) = stale {
+            conn.objects = stale.objects;
+            conn.object_fields = stale.object_fields;
+            conn.child_relationships = stale.child_relationships;
+            let mut session = conn.session();
+            return Ok(Some(tokio::spawn(async move {
+                session.get_all_objects_and_fields().await?;
+                Ok(session.cache_data())
+            })));
+        }
+    }
+    load_cache(conn).await?;
+    Ok(None)
+}
+
+// Saves the metadata of a finished background refresh and moves the connection to it.
+async fn finish_refresh(conn: &mut Connection, refresh: CacheRefresh) -> Result<(), DynError> {
+    let cache_data = refresh.await??;
+    save_cache_to_file(
+        &cache_data,
+        &cache_data_path(conn)?,
+        cache_key(conn)?.as_ref(),
+    )?;
     conn.objects = cache_data.objects;
     conn.object_fields = cache_data.object_fields;
+    conn.child_relationships = cache_data.child_relationships;
+    Ok(())
+}
+
+// Describes every object of the org again and saves them as the schema bundle, and as the
+// cache since they are the latest. Returns the path of the bundle.
+async fn pull_schema(conn: &mut Connection) -> Result<PathBuf, DynError> {
+    println!("{}", DESCRIBING_MESSAGE);
+    conn.get_all_objects_and_fields().await?;
+    let cache_data = conn.cache_data();
+    let path = schema_bundle_path(conn)?;
+    let key = cache_key(conn)?;
+    save_cache_to_file(&cache_data, &path, key.as_ref())?;
+    save_cache_to_file(&cache_data, &cache_data_path(conn)?, key.as_ref())?;
+    Ok(path)
+}
+
+// Describes one object, or every object, again and rewrites the cache with them. Returns
+// the number of objects described.
+async fn refresh_cache(conn: &mut Connection, object: Option<&str>) -> Result<usize, DynError> {
+    let described = match object {
+        Some(object) => {
+            let name = conn
+                .objects
+                .iter()
+                .find(|o| o.name.eq_ignore_ascii_case(object))
+                .map(|o| o.name.clone())
+                .ok_or_else(|| format!("Unknown object: {}", object))?;
+            conn.get_object_fields(&name).await?;
+            1
+        }
+        None => {
+            println!("{}", DESCRIBING_MESSAGE);
+            conn.get_all_objects_and_fields().await?;
+            conn.objects.len()
+        }
+    };
+    let cache_data = conn.cache_data();
+    save_cache_to_file(
+        &cache_data,
+        &cache_data_path(conn)?,
+        cache_key(conn)?.as_ref(),
+    )?;
+    Ok(described)
+}
+
+async fn run(
+    mut conn: Connection,
+    config: &Config,
+    format: Format,
+    settings: Settings,
+    all: bool,
+) -> Result<(), DynError> {
+    if format.is_binary() {
+        return Err(format!("{} can only be written with -q or run", format).into());
+    }
+    let history_path = history_path(&conn.profile)?;
+    let audit_log = audit_log(&conn.profile)?;
+
+    let refresh = load_cache_or_refresh(&mut conn).await?;
+
+    let user_ids = UserIds {
+        current: conn.user_id(),
+        ..Default::default()
+    };
+
+    let timezone = timezone(&conn).await?;
 
     let hinter = QueryHinter::new(&conn);
 
-    let mut rl: Editor<QueryHinter, DefaultHistory> = Editor::new()?;
+    // consecutive duplicates are kept once
+    let editor_config = rustyline::Config::builder()
+        .max_history_size(conn.profile.history_size.unwrap_or(DEFAULT_HISTORY_SIZE))?
+        .history_ignore_dups(true)?
+        .build();
+    let mut rl: Editor<QueryHinter, DefaultHistory> = Editor::with_config(editor_config)?;
     rl.set_helper(Some(hinter));
 
     if rl.load_history(&history_path).is_err() {
         println!("No previous history.");
     }
 
+    let mut repl = Repl {
+        conn,
+        config,
+        format,
+        settings,
+        all,
+        audit_log,
+        rl,
+        refresh,
+        user_ids,
+        timezone,
+        pager: None,
+        last_result: None,
+        jobs: Jobs::default(),
+        results: ResultCache::default(),
+        current_object: None,
+        output_file: None,
+        draft: None,
+    };
+
+    interrupt::listen();
     println!("Welcome to SOQL Generator");
     println!("Type 'exit' to quit");
     loop {
-        let readline = rl.readline("SOQLGenerator >>> ");
-        match readline {
+        match repl.readline().await {
             Ok(line) => {
-                rl.add_history_entry(line.as_str())?;
+                repl.rl.add_history_entry(line.as_str())?;
 
                 if line.trim() == "exit" {
                     break;
                 }
 
-                let (query, open_browser) = match engine::build_query(&line) {
-                    Ok(v) => v,
-                    Err(e) => {
-                        eprintln!("{}", e);
-                        continue;
-                    }
-                };
-
-                conn.call_query(&query, open_browser).await?;
+                if let Err(e) = repl.run_line(line).await {
+                    eprintln!("{}", e);
+                }
             }
             Err(ReadlineError::Interrupted) => {
                 println!("CTRL-C");
@@ -117,9 +787,1236 @@ async fn run() -> Result<(), DynError> {
         }
     }
 
-    if let Err(e) = rl.save_history(&history_path) {
+    if let Err(e) = repl.rl.save_history(&history_path) {
         eprintln!("Failed to save history: {}", e);
     }
 
     Ok(())
 }
+
+// What the lines and meta-commands entered at the REPL prompt change.
+struct Repl<'a> {
+    conn: Connection,
+    config: &'a Config,
+    format: Format,
+    settings: Settings,
+    all: bool,
+    audit_log: AuditLog,
+    rl: Editor<QueryHinter, DefaultHistory>,
+    refresh: Option<CacheRefresh>,
+    user_ids: UserIds,
+    timezone: Tz,
+    pager: Option<Pager>,
+    last_result: Option<LastResult>,
+    jobs: Jobs,
+    results: ResultCache,
+    // set by \use, bare method chains are run against it
+    current_object: Option<String>,
+    // set by \o, results are written to it instead of the terminal
+    output_file: Option<PathBuf>,
+    // set by \import-listview, prefilled at the next prompt for editing
+    draft: Option<String>,
+}
+
+impl Repl<'_> {
+    // Reads the next line, once the metadata cache a finished refresh fetched is in use.
+    async fn readline(&mut self) -> Result<String, ReadlineError> {
+        self.conn.verbose = self.settings.verbose;
+        if let Some(finished) = self.refresh.take_if(|refresh| refresh.is_finished()) {
+            match finish_refresh(&mut self.conn, finished).await {
+                Ok(()) => self.rl.set_helper(Some(QueryHinter::new(&self.conn))),
+                Err(e) => eprintln!("Failed to refresh the metadata cache: {}", e),
+            }
+        }
+        if let Some(hinter) = self.rl.helper_mut() {
+            hinter.all_objects = self.settings.all_objects;
+            hinter.object = self.current_object.clone();
+        }
+        let org = self
+            .conn
+            .profile
+            .name
+            .as_ref()
+            .map_or_else(String::new, |org| format!(" [{}]", org));
+        let prompt = match &self.current_object {
+            Some(object) => format!("SOQLGenerator{} ({}) >>> ", org, object),
+            None => format!("SOQLGenerator{} >>> ", org),
+        };
+        match self.draft.take() {
+            Some(draft) => self.rl.readline_with_initial(&prompt, (&draft, "")),
+            None => self.rl.readline(&prompt),
+        }
+    }
+
+    // Runs a meta-command, pages the last result for :n and :p, or runs a query.
+    async fn run_line(&mut self, line: String) -> Result<(), DynError> {
+        if let Some(meta_command) = MetaCommand::parse(&line) {
+            return self.run_meta_command(meta_command?).await;
+        }
+
+        if line.trim() == ":n" || line.trim() == ":p" {
+            let page = match self.pager.as_mut() {
+                Some(pager) if line.trim() == ":n" => pager.next_page(&self.conn).await?,
+                Some(pager) => pager.previous_page(),
+                None => None,
+            };
+            match page {
+                Some(page) => println!("{}", page),
+                None => println!("No more pages."),
+            }
+            return Ok(());
+        }
+
+        self.run_query(line, false).await
+    }
+
+    async fn run_meta_command(&mut self, meta_command: MetaCommand) -> Result<(), DynError> {
+        let conn = &self.conn;
+        match meta_command {
+            MetaCommand::FromSoql(soql) => {
+                println!("{}", engine::from_soql(&soql)?);
+            }
+            MetaCommand::Help => {
+                println!("{}\n\nCommands:", engine::help());
+                let width = COMMANDS
+                    .iter()
+                    .map(|(usage, _)| usage.len())
+                    .max()
+                    .unwrap_or(0);
+                for (usage, description) in COMMANDS {
+                    println!("  {:width$}  {}", usage, description, width = width);
+                }
+            }
+            MetaCommand::Edit(name) => {
+                let path = saved_query_path(&name);
+                edit_file(&path)?;
+                let saved_query = SavedQuery::load(&path)?;
+                self.run_query(saved_query.render()?, false).await?;
+            }
+            MetaCommand::Types => {
+                let last_result = self
+                    .last_result
+                    .as_ref()
+                    .ok_or("No query has been run yet")?;
+                let records = last_result.response["records"]
+                    .as_array()
+                    .map_or(&[][..], |r| r);
+                for column in infer_columns(records, &conn.object_fields, &last_result.fields) {
+                    println!("{}\t{}", column.name, column.column_type);
+                }
+            }
+            MetaCommand::Chart => {
+                let last_result = self
+                    .last_result
+                    .as_ref()
+                    .ok_or("No query has been run yet")?;
+                let records = last_result.response["records"]
+                    .as_array()
+                    .map_or(&[][..], |r| r);
+                let width = terminal_size().map_or(80, |(columns, _)| columns as usize);
+                println!(
+                    "{}",
+                    chart::render_bar_chart(records, &last_result.fields, width)?
+                );
+            }
+            MetaCommand::Open(number) => {
+                let last_result = self
+                    .last_result
+                    .as_ref()
+                    .ok_or("No query has been run yet")?;
+                open_record(conn, &last_result.response, number)?;
+            }
+            MetaCommand::Sort(column, descending) => {
+                let last = self
+                    .last_result
+                    .as_mut()
+                    .ok_or("No query has been run yet")?;
+                let response = &mut last.response;
+                if let Some(records) = response["records"].as_array_mut() {
+                    output::sort_records(records, &column, descending)?;
+                }
+                // the batches Salesforce has not returned yet can not be sorted in
+                if let Some(response) = response.as_object_mut() {
+                    if response.remove("nextRecordsUrl").is_some() {
+                        eprintln!("Warning: only the fetched records are sorted, use all() to sort every record");
+                    }
+                }
+                let options =
+                    render_options(conn, response, &last.fields, &self.settings, self.timezone);
+                self.pager = show_response(response, &[], &options, self.format)?;
+            }
+            MetaCommand::WhoAmI => {
+                let identity = conn.identity().await?;
+                if let Some(org) = &conn.profile.name {
+                    println!("Org:          {}", org);
+                }
+                println!("Username:     {}", identity.username);
+                println!("User Id:      {}", identity.user_id);
+                println!("Org Id:       {}", identity.org_id);
+                println!("Profile:      {}", identity.profile);
+                println!("Instance URL: {}", identity.instance_url);
+                if let Some(usage) = conn.api_usage() {
+                    println!("API usage:    {}", usage);
+                }
+            }
+            MetaCommand::Use(Some(object)) => {
+                let object = conn
+                    .objects
+                    .iter()
+                    .find(|o| o.name.eq_ignore_ascii_case(&object))
+                    .ok_or_else(|| format!("Unknown object: {}", object))?;
+                self.current_object = Some(object.name.clone());
+            }
+            MetaCommand::Use(None) => self.current_object = None,
+            MetaCommand::Org(Some(name)) => {
+                let profile = self.config.profile(Some(&name))?;
+                let (conn, timezone, refresh) =
+                    switch_org(conn.tooling, profile, conn.is_offline()).await?;
+                self.results.clear();
+                // the refresh of the previous org would overwrite its cache with the
+                // metadata of the new one
+                if let Some(refresh) = self.refresh.take() {
+                    refresh.abort();
+                }
+                self.refresh = refresh;
+                self.conn = conn;
+                self.timezone = timezone;
+                self.user_ids = UserIds {
+                    current: self.conn.user_id(),
+                    ..Default::default()
+                };
+                self.rl.set_helper(Some(QueryHinter::new(&self.conn)));
+                self.pager = None;
+                self.last_result = None;
+                self.current_object = None;
+                println!("Connected to {} ({})", name, self.conn.instance_url());
+            }
+            MetaCommand::Org(None) => {
+                if self.config.orgs.is_empty() {
+                    eprintln!(
+                        "No orgs are defined in {}",
+                        config::config_path()?.display()
+                    );
+                }
+                for name in self.config.orgs.keys() {
+                    let current = conn.profile.name.as_ref() == Some(name);
+                    println!("{} {}", if current { "*" } else { " " }, name);
+                }
+            }
+            MetaCommand::Output(Some(path)) => {
+                let path = PathBuf::from(path);
+                Format::from_path(&path)?;
+                self.output_file = Some(path);
+            }
+            MetaCommand::Output(None) => self.output_file = None,
+            MetaCommand::CacheStats => {
+                let path = cache_data_path(conn)?;
+                println!("Path:         {}", path.display());
+                let cache_data = match load_cache_from_file(&path, cache_key(conn)?.as_ref())? {
+                    Some(cache_data) => cache_data,
+                    None => {
+                        println!("No cache, the org is described at the next start");
+                        return Ok(());
+                    }
+                };
+                println!("Size:         {} KB", fs::metadata(&path)?.len() / 1024);
+                println!("Objects:      {}", cache_data.objects.len());
+                println!("Described:    {}", cache_data.object_fields.len());
+                println!(
+                    "Built from:   {} with API {}",
+                    cache_data.org_id.as_deref().unwrap_or("unknown org"),
+                    cache_data.api_version
+                );
+                let expiry = match cache_data.is_expired() {
+                    true => String::from("expired"),
+                    false => format!("expires after {} days", CACHE_EXPIRATION_DAYS),
+                };
+                println!(
+                    "Last cached:  {} ({} days ago, {})",
+                    cache_data.last_cached.to_rfc3339(),
+                    (Utc::now() - cache_data.last_cached).num_days(),
+                    expiry
+                );
+            }
+            MetaCommand::CacheClear => {
+                let path = cache_data_path(conn)?;
+                if path.exists() {
+                    fs::remove_file(&path)?;
+                }
+                println!(
+                    "Removed {}, the org is described again at the next start",
+                    path.display()
+                );
+            }
+            MetaCommand::SchemaPull => {
+                if let Some(refresh) = self.refresh.take() {
+                    refresh.abort();
+                }
+                let path = cancellable_request(pull_schema(&mut self.conn), "Cancelled").await?;
+                self.rl.set_helper(Some(QueryHinter::new(&self.conn)));
+                println!(
+                    "Saved the describes of {} objects to {}",
+                    self.conn.objects.len(),
+                    path.display()
+                );
+            }
+            MetaCommand::Refresh(object) => {
+                // describing every object makes the background refresh redundant
+                if let Some(refresh) = self.refresh.take_if(|_| object.is_none()) {
+                    refresh.abort();
+                }
+                let refreshed = refresh_cache(&mut self.conn, object.as_deref());
+                let described = cancellable_request(refreshed, "Cancelled").await?;
+                self.rl.set_helper(Some(QueryHinter::new(&self.conn)));
+                println!("Refreshed the metadata of {} objects", described);
+            }
+            MetaCommand::Objects(pattern) => {
+                let objects = engine::find_objects(
+                    pattern.as_deref().unwrap_or("*"),
+                    &conn.objects,
+                    self.settings.all_objects,
+                );
+                let records = output::object_records(&objects);
+                let response =
+                    serde_json::json!({ "totalSize": records.len(), "records": records });
+                let columns: Vec<String> = output::OBJECT_COLUMNS
+                    .iter()
+                    .map(|c| c.to_string())
+                    .collect();
+                let options =
+                    render_options(conn, &response, &columns, &self.settings, self.timezone);
+                print_response(&response, &options, self.format)?;
+            }
+            MetaCommand::Describe(object) => {
+                print_fields(
+                    conn,
+                    &object,
+                    None,
+                    &self.settings,
+                    self.timezone,
+                    self.format,
+                )
+                .await?;
+            }
+            MetaCommand::Fields(object, pattern) => {
+                print_fields(
+                    conn,
+                    &object,
+                    Some(pattern.as_deref().unwrap_or("*")),
+                    &self.settings,
+                    self.timezone,
+                    self.format,
+                )
+                .await?;
+            }
+            MetaCommand::FindLabel(text) => {
+                for (name, label) in engine::find_labels(&text, &conn.objects, &conn.object_fields)
+                {
+                    println!("{}\t{}", name, label);
+                }
+            }
+            MetaCommand::ListViews(object) => {
+                for list_view in conn.list_views(&object).await? {
+                    println!(
+                        "{}\t{}\t{}",
+                        list_view.id, list_view.developer_name, list_view.label
+                    );
+                }
+            }
+            MetaCommand::ImportListView(id) => {
+                self.draft = Some(import_list_view(conn, &id).await?);
+            }
+            MetaCommand::Sosl(sosl) => {
+                let results =
+                    print_search_results(conn, &sosl, &self.settings, self.timezone, self.format);
+                cancellable_request(results, "Search cancelled").await?;
+            }
+            MetaCommand::Report(report) => {
+                let response =
+                    cancellable_request(conn.run_report(&report), "Report cancelled").await?;
+                if response["allData"] == false {
+                    eprintln!("Warning: only the first 2000 rows of the report are shown");
+                }
+                let (fields, records) = output::report_records(&response);
+                let response =
+                    serde_json::json!({ "totalSize": records.len(), "records": records });
+                let options =
+                    render_options(conn, &response, &fields, &self.settings, self.timezone);
+                self.pager = show_response(&response, &[], &options, self.format)?;
+                self.last_result = Some(LastResult { response, fields });
+            }
+            MetaCommand::Subscribe(channel) => {
+                println!("Listening to {}, press Ctrl-C to stop", channel);
+                let print_event = |event: &Value| {
+                    let text = serde_json::to_string_pretty(event).unwrap_or_default();
+                    println!("[{}] {}", Utc::now().to_rfc3339(), text);
+                };
+                match interrupt::cancellable(conn.subscribe(&channel, print_event)).await {
+                    Some(result) => result?,
+                    None => println!("Stopped listening to {}", channel),
+                }
+            }
+            MetaCommand::Why(comment) => {
+                self.audit_log.annotate_last(&comment)?;
+            }
+            MetaCommand::Expanded => {
+                self.settings.expanded = !self.settings.expanded;
+                println!(
+                    "Expanded display is {}",
+                    if self.settings.expanded { "on" } else { "off" }
+                );
+            }
+            MetaCommand::Set(Some((name, value))) => {
+                self.settings.set(&name, &value)?;
+            }
+            MetaCommand::Set(None) => {
+                for setting in self.settings.list() {
+                    println!("{}", setting);
+                }
+            }
+            MetaCommand::Format(format) => {
+                self.format = format;
+                println!("Output format is {}", self.format);
+            }
+            MetaCommand::Background(query) => {
+                self.run_query(query, true).await?;
+            }
+            MetaCommand::Jobs => {
+                for job in self.jobs.list() {
+                    println!("{}", job);
+                }
+            }
+            MetaCommand::Foreground(id) => {
+                let job = self.jobs.wait(id).await?;
+                if let Some(path) = &self.output_file {
+                    let written =
+                        write_responses(conn, path, &job.responses, &job.fields, self.settings.raw);
+                    if let Err(e) = written {
+                        eprintln!("{}", e);
+                    }
+                    self.last_result = job.responses.last().map(|response| LastResult {
+                        response: response.clone(),
+                        fields: job.fields.clone(),
+                    });
+                    return Ok(());
+                }
+                for response in job.responses {
+                    let options =
+                        render_options(conn, &response, &job.fields, &self.settings, self.timezone);
+                    self.pager =
+                        show_response(&response, &job.count_relationships, &options, self.format)?;
+                    self.last_result = Some(LastResult {
+                        response,
+                        fields: job.fields.clone(),
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Runs a method chain, in the background for \bg. A chain starting with ! is fetched
+    // again rather than answered from the result cache.
+    async fn run_query(&mut self, line: String, background: bool) -> Result<(), DynError> {
+        let conn = &self.conn;
+        let settings = &self.settings;
+        let timezone = self.timezone;
+        let format = self.format;
+        let all = self.all;
+
+        let (line, fresh) = match line.trim_start().strip_prefix('!') {
+            Some(query) => (query.to_string(), true),
+            None => (line, false),
+        };
+        let (line, filter) = match filter::split_filter(&line) {
+            (query, Some(expr)) => (query.to_string(), Some(expr)),
+            (_, None) => (line, None),
+        };
+        let line = match &self.current_object {
+            Some(object) if line.trim_start().starts_with('.') => {
+                format!("{}{}", object, line.trim_start())
+            }
+            _ => line,
+        };
+
+        resolve_user_ids(conn, &line, &mut self.user_ids).await?;
+
+        let (mut query, evaluation) = engine::build_query(
+            &line,
+            &conn.objects,
+            &conn.object_fields,
+            &self.user_ids,
+            timezone,
+            settings.autocorrect,
+        )?;
+        query.read_in_file()?;
+        for default in &evaluation.defaults {
+            eprintln!("Note: added {}", default);
+        }
+        if settings.bulk {
+            query.set_bulk();
+        }
+        if settings.backend == Backend::Graphql {
+            query.set_graphql();
+        }
+        if conn.is_offline() {
+            for soql in query.generate_chunks() {
+                println!("{}", soql);
+            }
+            return Ok(());
+        }
+        if let Some(name) = query.list_view() {
+            let soql = list_view_query(conn, query.object(), name).await?;
+            query.set_list_view_query(soql);
+        }
+        for warning in &evaluation.warnings {
+            eprintln!("Warning: {}", warning);
+        }
+
+        if let Some(object) = sensitive_object(conn, query.object()).await? {
+            let question = format!(
+                "{} is a sensitive object and this is a production org. Run the query? [y/N] ",
+                object
+            );
+            let answer = self.rl.readline(&question).unwrap_or_default();
+            if !matches!(answer.trim(), "y" | "Y" | "yes") {
+                println!("Cancelled.");
+                return Ok(());
+            }
+        }
+
+        log_query(&self.audit_log, conn, &line, &query);
+        if let Some(dml) = query.dml() {
+            if background {
+                return Err(format!("{} can not be used with \\bg", dml.method()).into());
+            }
+            let rl = &mut self.rl;
+            let mut confirm = |action: &str| {
+                let answer = rl
+                    .readline(&format!("{}? [y/N] ", action))
+                    .unwrap_or_default();
+                matches!(answer.trim(), "y" | "Y" | "yes")
+            };
+            let dml = run_dml(conn, &query, dml, &mut confirm);
+            let changed = cancellable_request(dml, "Cancelled").await;
+            self.results.clear();
+            return changed;
+        }
+        let count_relationships = query.count_relationships();
+        if background && query.output_path().is_some() {
+            return Err("out() can not be used with \\bg, use \\o instead".into());
+        }
+        if background && query.uses_bulk() {
+            return Err("bulk() can not be used with \\bg".into());
+        }
+        if background && query.uses_graphql() {
+            return Err("\\bg can not be used with the GraphQL backend".into());
+        }
+        if filter.is_some()
+            && (background || query.output_path().is_some() || self.output_file.is_some())
+        {
+            return Err("| jq can only be used on results shown in the terminal".into());
+        }
+        if background {
+            let session = conn.session();
+            let soqls = query.generate_chunks();
+            let job_count_relationships = count_relationships.to_vec();
+            let fetch_all = all || query.fetches_all();
+            let id = self
+                .jobs
+                .spawn(&line, count_relationships, query.fields(), async move {
+                    let mut responses = Vec::new();
+                    for soql in soqls {
+                        responses.push(
+                            session
+                                .call_query(&soql, &job_count_relationships, fetch_all)
+                                .await?,
+                        );
+                    }
+                    Ok(responses)
+                });
+            println!("[{}] started", id);
+            return Ok(());
+        }
+
+        if query.opens_query() {
+            return conn.open_query(&query.generate());
+        }
+        if query.explains() {
+            let plans = print_plans(conn, &query, settings, timezone, format);
+            return cancellable_request(plans, "Query cancelled").await;
+        }
+        if query.uses_bulk() {
+            let path = query
+                .output_path()
+                .map(PathBuf::from)
+                .or_else(|| self.output_file.clone())
+                .ok_or("bulk() writes its result to a file, add out('<file>.csv') or use \\o")?;
+            let written = write_bulk(conn, &query, Some(&path));
+            return cancellable_request(written, "Query cancelled").await;
+        }
+        if query.counts() {
+            let count = cancellable_request(count_records(conn, &query), "Query cancelled").await?;
+            println!("{}", count);
+            return Ok(());
+        }
+        let output_path = query
+            .output_path()
+            .map(PathBuf::from)
+            .or_else(|| self.output_file.clone());
+        if let Some(path) = output_path {
+            let mut responses = Vec::new();
+            for soql in query.generate_chunks() {
+                let fetched = fetch_cached(
+                    conn,
+                    &query,
+                    &soql,
+                    all || query.fetches_all(),
+                    settings.cache.then_some(&mut self.results),
+                    fresh,
+                );
+                match fetched.await {
+                    Ok(response) => responses.push(response),
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        break;
+                    }
+                }
+            }
+            if responses.is_empty() {
+                return Ok(());
+            }
+            if let Err(e) = write_responses(conn, &path, &responses, query.fields(), settings.raw) {
+                eprintln!("{}", e);
+            }
+            if query.opens_browser() {
+                if let Err(e) = open_record(conn, &responses[0], 1) {
+                    eprintln!("{}", e);
+                }
+            }
+            self.pager = None;
+            self.last_result = responses.pop().map(|response| LastResult {
+                response,
+                fields: query.fields().to_vec(),
+            });
+            return Ok(());
+        }
+
+        for (i, soql) in query.generate_chunks().iter().enumerate() {
+            let fetched = fetch_cached(
+                conn,
+                &query,
+                soql,
+                all || query.fetches_all(),
+                settings.cache.then_some(&mut self.results),
+                fresh,
+            );
+            let response = fetched.await?;
+            if let Some(expr) = &filter {
+                self.pager = None;
+                let printed = print_filtered(
+                    conn,
+                    &response,
+                    expr,
+                    query.fields(),
+                    settings,
+                    timezone,
+                    format,
+                );
+                if let Err(e) = printed {
+                    eprintln!("{}", e);
+                }
+            } else {
+                let options = render_options(conn, &response, query.fields(), settings, timezone);
+                self.pager = show_response(&response, count_relationships, &options, format)?;
+            }
+            if query.opens_browser() && i == 0 {
+                if let Err(e) = open_record(conn, &response, 1) {
+                    eprintln!("{}", e);
+                }
+            }
+            self.last_result = Some(LastResult {
+                response,
+                fields: query.fields().to_vec(),
+            });
+        }
+        Ok(())
+    }
+}
+
+// Logs in to another org for \org and loads its metadata cache.
+async fn switch_org(
+    tooling: bool,
+    profile: Profile,
+    offline: bool,
+) -> Result<(Connection, Tz, Option<CacheRefresh>), DynError> {
+    let mut conn = connect(tooling, profile, offline).await?;
+    let refresh = load_cache_or_refresh(&mut conn).await?;
+    let timezone = timezone(&conn).await?;
+    Ok((conn, timezone, refresh))
+}
+
+// The last result shown in the REPL, with the select() fields its columns follow.
+struct LastResult {
+    response: Value,
+    fields: Vec<String>,
+}
+
+// Prints the response, or its first page when it does not fit on the screen.
+fn show_response(
+    response: &Value,
+    count_relationships: &[String],
+    options: &RenderOptions,
+    format: Format,
+) -> Result<Option<Pager>, DynError> {
+    let height = terminal_size().map_or(24, |(_, rows)| rows as usize);
+    let pager = Pager::new(
+        response.clone(),
+        count_relationships,
+        options,
+        height,
+        format,
+    );
+    if format.is_paged() && !pager.fits_on_screen() {
+        println!("{}", pager.current_page());
+        return Ok(Some(pager));
+    }
+
+    print_response(response, options, format)?;
+    Ok(None)
+}
+
+// Prints what a jq expression makes of the response. Objects are formatted like records,
+// e.g. as a table, and any other output as JSON, one value at a time like jq does.
+fn print_filtered(
+    conn: &Connection,
+    response: &Value,
+    expr: &str,
+    fields: &[String],
+    settings: &Settings,
+    timezone: Tz,
+    format: Format,
+) -> Result<(), DynError> {
+    let options = render_options(conn, response, fields, settings, timezone);
+    let values = filter::apply_filter(expr, &output::for_output(response, &options))?;
+    if format != Format::Json && values.iter().all(Value::is_object) {
+        let response = serde_json::json!({ "records": values });
+        let options = render_options(conn, &response, fields, settings, timezone);
+        return print_response(&response, &options, format);
+    }
+    for value in values {
+        println!("{}", serde_json::to_string_pretty(&value)?);
+    }
+    Ok(())
+}
+
+// Columns are typed by the cached field metadata so that datetimes and numbers can be
+// localized.
+fn render_options(
+    conn: &Connection,
+    response: &Value,
+    fields: &[String],
+    settings: &Settings,
+    timezone: Tz,
+) -> RenderOptions {
+    let records = response["records"].as_array().map_or(&[][..], |r| r);
+    RenderOptions {
+        fields: fields.to_vec(),
+        column_types: infer_columns(records, &conn.object_fields, fields)
+            .into_iter()
+            .map(|column| (column.name, column.column_type))
+            .collect(),
+        localize: settings.localize.then_some(timezone),
+        max_width: settings.max_width,
+        wrap: settings.wrap,
+        columns: None,
+        raw: settings.raw,
+        expanded: settings.expanded,
+        record_offset: 0,
+    }
+}
+
+// The number of records a count() query matched, over every chunk of an in_file() list.
+async fn count_records(conn: &Connection, query: &engine::Query) -> Result<u64, DynError> {
+    let mut count = 0;
+    for soql in query.generate_chunks() {
+        let response = fetch(conn, query, &soql, false).await?;
+        count += response["totalSize"]
+            .as_u64()
+            .ok_or_else(|| format!("Failed to count the records: {}", response))?;
+    }
+    Ok(count)
+}
+
+// Runs one chunk of a query, on the GraphQL API when it is the backend. Ctrl-C cancels
+// it in the REPL.
+async fn fetch(
+    conn: &Connection,
+    query: &engine::Query,
+    soql: &str,
+    all: bool,
+) -> Result<Value, DynError> {
+    let response = async {
+        if query.uses_graphql() {
+            return conn.graphql_query(&query.to_graphql()?, all).await;
+        }
+        conn.call_query(soql, query.count_relationships(), all)
+            .await
+    };
+    cancellable_request(response, "Query cancelled").await
+}
+
+// Answers the query from the result cache of the REPL when it holds its response, unless
+// `fresh` asks to fetch it again. Without a cache the query is just run.
+async fn fetch_cached(
+    conn: &Connection,
+    query: &engine::Query,
+    soql: &str,
+    all: bool,
+    results: Option<&mut ResultCache>,
+    fresh: bool,
+) -> Result<Value, DynError> {
+    let results = match results {
+        Some(results) => results,
+        None => return fetch(conn, query, soql, all).await,
+    };
+    let key = ResultCache::key(soql, all, query.uses_graphql());
+    if !fresh {
+        if let Some(response) = results.get(&key) {
+            eprintln!("Note: cached result, start the query with ! to fetch it again");
+            return Ok(response);
+        }
+    }
+    let response = fetch(conn, query, soql, all).await?;
+    results.insert(key, response.clone());
+    Ok(response)
+}
+
+// Prints the result of an all() query a batch at a time as Salesforce returns them, so
+// that large extracts do not have to fit in memory. The progress is shown on stderr
+// when it is a terminal.
+async fn stream_response(
+    conn: &Connection,
+    soql: &str,
+    query: &engine::Query,
+    settings: &Settings,
+    timezone: Tz,
+    format: Format,
+) -> Result<(), DynError> {
+    let count_relationships = query.count_relationships();
+    let mut response = conn.call_query(soql, count_relationships, false).await?;
+    let total = response["totalSize"].as_u64().unwrap_or(0);
+    let progress = termion::is_tty(&std::io::stderr());
+    let mut columns = None;
+    let mut fetched = 0;
+
+    // the records of every batch make up a single response
+    if format == Format::Json {
+        print!("{{\"totalSize\":{},\"done\":true,\"records\":[", total);
+    }
+    loop {
+        let mut options = render_options(conn, &response, query.fields(), settings, timezone);
+        options.columns = columns.clone();
+        options.record_offset = fetched;
+        let records = response["records"].as_array().map_or(&[][..], |r| r);
+        match format {
+            Format::Json => {
+                for (i, record) in records.iter().enumerate() {
+                    let separator = if fetched + i > 0 { "," } else { "" };
+                    print!("{}{}", separator, output::for_output(record, &options));
+                }
+            }
+            Format::Table => {
+                if !records.is_empty() {
+                    println!("{}", format.render(records, &options));
+                }
+            }
+            _ => print_response(&response, &options, format)?,
+        }
+        if columns.is_none() && !records.is_empty() {
+            columns = Some(output::columns(records, &options));
+        }
+        fetched += records.len();
+        std::io::stdout().flush()?;
+        if progress {
+            eprint!("\rFetched {} of {} records", fetched, total);
+        }
+
+        let next_records_url = match response["nextRecordsUrl"].as_str() {
+            Some(url) => url.to_string(),
+            None => break,
+        };
+        response = conn
+            .next_records(&next_records_url, count_relationships)
+            .await?;
+    }
+    match format {
+        Format::Json => println!("]}}"),
+        Format::Table => println!("({} records)", fetched),
+        _ => {}
+    }
+    if progress {
+        eprintln!();
+    }
+
+    Ok(())
+}
+
+// Runs a bulk() query and writes its CSV to `path`, or to stdout without one. Every chunk
+// of an in_file() list is a job of its own.
+async fn write_bulk(
+    conn: &Connection,
+    query: &engine::Query,
+    path: Option<&Path>,
+) -> Result<(), DynError> {
+    if query.counts() || !query.count_relationships().is_empty() {
+        return Err("bulk() can not be used with count() or withcount()".into());
+    }
+    let mut out: Box<dyn Write> = match path {
+        Some(path) => {
+            if Format::from_path(path)? != Format::Csv {
+                return Err("bulk() results are CSV, write them to a .csv file".into());
+            }
+            let file = File::create(path)
+                .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+            Box::new(BufWriter::new(file))
+        }
+        None => Box::new(BufWriter::new(std::io::stdout())),
+    };
+
+    let mut count = 0;
+    for (i, soql) in query.generate_chunks().iter().enumerate() {
+        count += conn.bulk_query(soql, &mut out, i == 0).await?;
+    }
+    out.flush()?;
+
+    if let Some(path) = path {
+        eprintln!("Wrote {} records to {}", count, path.display());
+    }
+    Ok(())
+}
+
+// Writes the records of every response to a file in the format of its extension, e.g.
+// results.csv. Values are written in full as Salesforce returns them.
+fn write_responses(
+    conn: &Connection,
+    path: &Path,
+    responses: &[Value],
+    fields: &[String],
+    raw: bool,
+) -> Result<(), DynError> {
+    let format = Format::from_path(path)?;
+    let records: Vec<Value> = responses
+        .iter()
+        .flat_map(|response| response["records"].as_array().cloned().unwrap_or_default())
+        .collect();
+    let failed = |e: &dyn std::fmt::Display| format!("Failed to write {}: {}", path.display(), e);
+
+    if format.is_binary() {
+        let file = File::create(path).map_err(|e| failed(&e))?;
+        export::write_parquet(&records, &conn.object_fields, fields, file)
+            .map_err(|e| failed(&e))?;
+    } else {
+        let options = RenderOptions {
+            fields: fields.to_vec(),
+            column_types: infer_columns(&records, &conn.object_fields, fields)
+                .into_iter()
+                .map(|column| (column.name, column.column_type))
+                .collect(),
+            raw,
+            ..Default::default()
+        };
+        let mut content = format.render(&records, &options);
+        if !content.is_empty() && !content.ends_with('\n') {
+            content.push('\n');
+        }
+        fs::write(path, content).map_err(|e| failed(&e))?;
+    }
+
+    eprintln!("Wrote {} records to {}", records.len(), path.display());
+    Ok(())
+}
+
+fn print_response(
+    response: &Value,
+    options: &RenderOptions,
+    format: Format,
+) -> Result<(), DynError> {
+    let records = response["records"].as_array().map_or(&[][..], |r| r);
+    match format {
+        Format::Json => println!(
+            "{}",
+            serde_json::to_string_pretty(&output::for_output(response, options))?
+        ),
+        Format::Table => {
+            if !records.is_empty() {
+                println!("{}", format.render(records, options));
+            }
+            println!("({} records)", records.len());
+        }
+        Format::Csv => print!("{}", format.render(records, options)),
+        Format::Jsonl | Format::Markdown => {
+            if !records.is_empty() {
+                println!("{}", format.render(records, options));
+            }
+        }
+        Format::Parquet => {
+            return Err(format!("{} can only be written with -q or run", format).into())
+        }
+    }
+    Ok(())
+}
+
+// Inserts or upserts a record, or updates or deletes the records the query matches once
+// `confirm` agrees to it. More records than one sObject Collections request changes are
+// refused, the query has to be narrowed down with where() or limit() instead.
+async fn run_dml(
+    conn: &Connection,
+    query: &engine::Query,
+    dml: &Dml,
+    confirm: &mut dyn FnMut(&str) -> bool,
+) -> Result<(), DynError> {
+    let object = query.object();
+    let (fields, action) = match dml {
+        Dml::Insert(fields) => {
+            println!("Inserted {}", conn.insert(object, fields).await?);
+            return Ok(());
+        }
+        Dml::Upsert(field, value, fields) => {
+            let (id, created) = conn.upsert(object, (field, value), fields).await?;
+            println!("{} {}", if created { "Inserted" } else { "Updated" }, id);
+            return Ok(());
+        }
+        Dml::Update(fields) => (Some(fields), "Update"),
+        Dml::Delete => (None, "Delete"),
+    };
+
+    let response = conn.call_query(&query.generate(), &[], false).await?;
+    let total = response["totalSize"].as_u64().unwrap_or(0) as usize;
+    if total == 0 {
+        println!("No records match");
+        return Ok(());
+    }
+    if total > MAX_COLLECTION_RECORDS {
+        return Err(format!(
+            "{} matches {} records, more than the {} that can be changed at once. Narrow it down with where() or limit()",
+            dml.method(),
+            total,
+            MAX_COLLECTION_RECORDS
+        )
+        .into());
+    }
+    let ids: Vec<String> = response["records"]
+        .as_array()
+        .map_or(&[][..], |r| r)
+        .iter()
+        .filter_map(|record| record["Id"].as_str().map(String::from))
+        .collect();
+    if !confirm(&format!("{} {} {}", action, ids.len(), object)) {
+        return Err("Cancelled.".into());
+    }
+
+    match fields {
+        Some(fields) => conn.update_records(object, &ids, fields).await?,
+        None => conn.delete_records(&ids).await?,
+    }
+    println!("{}d {} records", action, ids.len());
+    Ok(())
+}
+
+// Shows the query plans of a query instead of running it. A query split over an
+// in_file() list is explained by its first chunk.
+async fn print_plans(
+    conn: &Connection,
+    query: &engine::Query,
+    settings: &Settings,
+    timezone: Tz,
+    format: Format,
+) -> Result<(), DynError> {
+    let soql = query.generate_chunks().remove(0);
+    let plans = output::plan_records(&conn.explain(&soql).await?);
+    let response = serde_json::json!({ "totalSize": plans.len(), "records": plans });
+    let fields: Vec<String> = output::PLAN_COLUMNS.iter().map(|c| c.to_string()).collect();
+    let options = render_options(conn, &response, &fields, settings, timezone);
+    print_response(&response, &options, format)
+}
+
+// Shows the cached fields of an object, or for \fields only those matching a glob pattern.
+// For \d, an object with date fields is followed by the fiscal year of the org, which
+// fiscal date literals such as THIS_FISCAL_YEAR depend on.
+async fn print_fields(
+    conn: &Connection,
+    object: &str,
+    pattern: Option<&str>,
+    settings: &Settings,
+    timezone: Tz,
+    format: Format,
+) -> Result<(), DynError> {
+    let (name, fields) = conn
+        .object_fields
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(object))
+        .ok_or_else(|| format!("Unknown object: {}", object))?;
+    let matched: Vec<Field> = match pattern {
+        Some(pattern) => engine::find_fields(pattern, fields)
+            .into_iter()
+            .cloned()
+            .collect(),
+        None => fields.clone(),
+    };
+    if let (Some(pattern), true) = (pattern, matched.is_empty()) {
+        return Err(format!("No fields of {} match {}", name, pattern).into());
+    }
+    let records = output::field_records(&matched);
+    let response = serde_json::json!({ "totalSize": records.len(), "records": records });
+    let columns: Vec<String> = output::FIELD_COLUMNS
+        .iter()
+        .map(|c| c.to_string())
+        .collect();
+    let options = render_options(conn, &response, &columns, settings, timezone);
+    println!("{}", name);
+    print_response(&response, &options, format)?;
+
+    let has_dates = fields
+        .iter()
+        .any(|field| matches!(field.field_type.as_str(), "date" | "datetime"));
+    if has_dates && pattern.is_none() && !conn.is_offline() {
+        let (start_month, named_by_start) = conn.fiscal_year().await?;
+        let month = chrono::Month::try_from(start_month as u8)
+            .map_or_else(|_| start_month.to_string(), |m| m.name().to_string());
+        println!(
+            "Fiscal years start in {} and are named after the year they {}",
+            month,
+            if named_by_start { "start" } else { "end" }
+        );
+    }
+    Ok(())
+}
+
+// Runs a SOSL search and shows the records found of each object as a result of its own.
+async fn print_search_results(
+    conn: &Connection,
+    sosl: &str,
+    settings: &Settings,
+    timezone: Tz,
+    format: Format,
+) -> Result<(), DynError> {
+    let response = conn.search(sosl).await?;
+    let records = response["searchRecords"].as_array().map_or(&[][..], |r| r);
+    let groups = output::group_by_type(records);
+    if groups.is_empty() {
+        println!("(0 records)");
+    }
+    for (object, records) in groups {
+        let response = serde_json::json!({ "totalSize": records.len(), "records": records });
+        let options = render_options(conn, &response, &[], settings, timezone);
+        println!("{}", object);
+        print_response(&response, &options, format)?;
+    }
+    Ok(())
+}
+
+// The query of an object's list view, found by its label or API name.
+async fn list_view_query(conn: &Connection, object: &str, name: &str) -> Result<String, DynError> {
+    let list_views = conn.list_views(object).await?;
+    let list_view = list_views
+        .iter()
+        .find(|list_view| {
+            list_view.label.eq_ignore_ascii_case(name)
+                || list_view.developer_name.eq_ignore_ascii_case(name)
+        })
+        .ok_or_else(|| {
+            format!(
+                "Unknown list view of {}: {}. \\listviews {} lists them",
+                object, name, object
+            )
+        })?;
+    conn.list_view_query(&list_view.id).await
+}
+
+async fn import_list_view(conn: &Connection, id: &str) -> Result<String, DynError> {
+    let soql = conn.list_view_query(id).await?;
+    engine::from_soql(&soql).map_err(|e| format!("{}\nList view query: {}", e, soql).into())
+}
+
+// Opens the file in $EDITOR (vi by default) and waits for the editor to exit.
+// A missing file is created from a template first.
+fn edit_file(path: &Path) -> Result<(), DynError> {
+    if !path.exists() {
+        let name = path.file_stem().unwrap_or_default().to_string_lossy();
+        fs::write(
+            path,
+            format!(
+                "---\nname: {}\nparams: {{}}\n---\nAccount.select(Id)\n",
+                name
+            ),
+        )?;
+    }
+
+    let editor = env::var("EDITOR").unwrap_or_else(|_| String::from("vi"));
+    let mut words = editor.split_whitespace();
+    let program = words.next().ok_or("EDITOR is empty")?;
+    let status = process::Command::new(program)
+        .args(words)
+        .arg(path)
+        .status()?;
+    if !status.success() {
+        return Err(format!("{} exited with {}", editor, status).into());
+    }
+
+    Ok(())
+}
+
+// Opens the record at a 1-based position of a query response.
+fn open_record(conn: &Connection, response: &Value, number: usize) -> Result<(), DynError> {
+    let record = response["records"]
+        .as_array()
+        .and_then(|records| records.get(number.checked_sub(1)?))
+        .ok_or_else(|| format!("No record {} in the last result", number))?;
+    conn.open_record(record)
+}
+
+// Returns the object when it is sensitive and the org is not a sandbox, in which case the
+// query has to be confirmed. The profile of the org in config.yaml may list its own
+// sensitive objects in place of SFDC_SENSITIVE_OBJECTS, and say whether it is production
+// instead of asking Salesforce.
+async fn sensitive_object(conn: &Connection, object: &str) -> Result<Option<String>, DynError> {
+    let objects = match &conn.profile.sensitive_objects {
+        Some(objects) => objects.clone(),
+        None => sensitive_objects(env::var("SFDC_SENSITIVE_OBJECTS").ok().as_deref()),
+    };
+    if !is_sensitive(object, &objects) {
+        return Ok(None);
+    }
+    let production = match conn.profile.production {
+        Some(production) => production,
+        None => !conn.is_sandbox().await?,
+    };
+    Ok(production.then(|| object.to_string()))
+}
+
+async fn resolve_user_ids(
+    conn: &Connection,
+    line: &str,
+    user_ids: &mut UserIds,
+) -> Result<(), DynError> {
+    for alias in engine::user_aliases(line) {
+        if let Entry::Vacant(entry) = user_ids.aliases.entry(alias) {
+            let id = conn.find_user_id(entry.key()).await?;
+            entry.insert(id);
+        }
+    }
+    Ok(())
+}
+
+// SFDC_TIMEZONE overrides the org's default timezone used to expand on().
+async fn timezone(conn: &Connection) -> Result<Tz, DynError> {
+    let name = match env::var("SFDC_TIMEZONE") {
+        Ok(name) => name,
+        // the org can not be asked offline
+        Err(_) if conn.is_offline() => String::from("UTC"),
+        Err(_) => conn.org_timezone().await?,
+    };
+    name.parse::<Tz>()
+        .map_err(|e| format!("Invalid timezone {}: {}", name, e).into())
+}
@@ -0,0 +1,499 @@
+use crate::output::Format;
+use std::{
+    error::Error,
+    fmt::{self, Display},
+};
+
+// Backslash commands entered at the REPL prompt.
+#[derive(Debug, PartialEq)]
+pub enum MetaCommand {
+    FromSoql(String),
+    Edit(String),
+    Types,
+    WhoAmI,
+    Chart,
+    // toggles the expanded display
+    Expanded,
+    // the column, and whether to sort in descending order
+    Sort(String, bool),
+    Background(String),
+    Jobs,
+    Foreground(usize),
+    Format(Format),
+    Open(usize),
+    FindLabel(String),
+    ListViews(String),
+    Why(String),
+    ImportListView(String),
+    // a SOSL search, e.g. FIND {Acme} IN NAME FIELDS RETURNING Account(Name), Contact
+    Sosl(String),
+    // the Id or name of a report to run
+    Report(String),
+    // a streaming channel to tail, e.g. /data/AccountChangeEvent
+    Subscribe(String),
+    // describes every object into the schema bundle for offline mode
+    SchemaPull,
+    // describes one object again, or every object when None
+    Refresh(Option<String>),
+    CacheStats,
+    CacheClear,
+    // the object whose fields to list
+    Describe(String),
+    // a glob pattern the objects to list match, None for every object
+    Objects(Option<String>),
+    // an object and a glob pattern its fields to list match, None for every field
+    Fields(String, Option<String>),
+    // None leaves the current object
+    Use(Option<String>),
+    // None lists the orgs of config.yaml
+    Org(Option<String>),
+    // None writes results to the terminal again
+    Output(Option<String>),
+    // None lists the settings
+    Set(Option<(String, String)>),
+    // prints the grammar of method chains and these commands
+    Help,
+}
+
+// Every command with its arguments and what it does, for \?.
+pub const COMMANDS: [(&str, &str); 30] = [
+    ("\\? (or help)", "show this help"),
+    (
+        "\\d <object> (or \\describe)",
+        "list the fields of an object",
+    ),
+    (
+        "\\fields <object> [pattern]",
+        "list the fields matching a glob, e.g. *phone*",
+    ),
+    (
+        "\\objects [pattern] (or \\dt)",
+        "list the cached objects matching a glob",
+    ),
+    ("\\find label <text>", "find objects and fields by label"),
+    (
+        "\\use [object]",
+        "run chains starting with a dot against the object",
+    ),
+    ("\\org [name]", "switch to another org, or list them"),
+    ("\\whoami", "show the user and org of the session"),
+    ("\\set [name value]", "change a setting, or list them"),
+    (
+        "\\format <table|json|csv|jsonl|markdown>",
+        "choose how results are printed",
+    ),
+    ("\\x", "toggle the expanded display"),
+    (
+        "\\o [file]",
+        "write the results to a file, or to the terminal again",
+    ),
+    ("\\sort <column> [asc|desc]", "sort the last result"),
+    ("\\types", "show the column types of the last result"),
+    ("\\chart", "draw the last grouped result as a bar chart"),
+    ("\\open <n>", "open the n-th record of the last result"),
+    (
+        "\\from-soql <SOQL>",
+        "print the method chain of a SOQL statement",
+    ),
+    ("\\edit <saved-query>", "edit a saved query and run it"),
+    ("\\listviews <object>", "list the list views of an object"),
+    (
+        "\\import-listview <list view Id>",
+        "edit the query of a list view",
+    ),
+    ("\\sosl <SOSL>", "run a SOSL search"),
+    ("\\report <report Id or name>", "run a report"),
+    (
+        "\\subscribe <channel>",
+        "tail the events of a streaming channel",
+    ),
+    ("\\why <comment>", "comment the last query in the audit log"),
+    ("\\bg <query>", "run a query in the background"),
+    ("\\jobs", "list the background queries"),
+    ("\\fg <job id>", "wait for a background query"),
+    (
+        "\\refresh [object]",
+        "describe the org, or an object, again",
+    ),
+    ("\\schema pull", "save the schema bundle for --offline"),
+    ("\\cache [stats|clear]", "show or remove the metadata cache"),
+];
+
+#[derive(Debug, PartialEq)]
+pub enum MetaCommandError {
+    UnknownCommand(String),
+    MissingArgument(String),
+    InvalidArgument(String),
+}
+
+impl Display for MetaCommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MetaCommandError::UnknownCommand(command) => {
+                write!(f, "Unknown command: \\{}", command)
+            }
+            MetaCommandError::MissingArgument(usage) => {
+                write!(f, "Missing argument: usage: {}", usage)
+            }
+            MetaCommandError::InvalidArgument(usage) => {
+                write!(f, "Invalid argument: usage: {}", usage)
+            }
+        }
+    }
+}
+
+impl Error for MetaCommandError {}
+
+impl MetaCommand {
+    // Returns None when the line is not a meta-command.
+    pub fn parse(line: &str) -> Option<Result<MetaCommand, MetaCommandError>> {
+        // help alone is the same as \?
+        if line.trim() == "help" {
+            return Some(Ok(MetaCommand::Help));
+        }
+        let line = line.trim().strip_prefix('\\')?;
+        let (command, argument) = match line.split_once(char::is_whitespace) {
+            Some((command, argument)) => (command, argument.trim()),
+            None => (line, ""),
+        };
+
+        let required = |usage: &str| {
+            if argument.is_empty() {
+                Err(MetaCommandError::MissingArgument(usage.to_string()))
+            } else {
+                Ok(argument.to_string())
+            }
+        };
+
+        let meta_command = match command {
+            "from-soql" => required("\\from-soql <SOQL>").map(MetaCommand::FromSoql),
+            "edit" => required("\\edit <saved-query>").map(MetaCommand::Edit),
+            "types" => Ok(MetaCommand::Types),
+            "whoami" => Ok(MetaCommand::WhoAmI),
+            "x" => Ok(MetaCommand::Expanded),
+            "chart" => Ok(MetaCommand::Chart),
+            "sort" => {
+                let usage = "\\sort <column> [asc|desc]";
+                let mut words = argument.split_whitespace();
+                match (words.next(), words.next(), words.next()) {
+                    (None, _, _) => Err(MetaCommandError::MissingArgument(usage.to_string())),
+                    (Some(column), order, None) => match order.map(str::to_ascii_lowercase) {
+                        None => Ok(MetaCommand::Sort(column.to_string(), false)),
+                        Some(order) if order == "asc" => {
+                            Ok(MetaCommand::Sort(column.to_string(), false))
+                        }
+                        Some(order) if order == "desc" => {
+                            Ok(MetaCommand::Sort(column.to_string(), true))
+                        }
+                        Some(_) => Err(MetaCommandError::InvalidArgument(usage.to_string())),
+                    },
+                    _ => Err(MetaCommandError::InvalidArgument(usage.to_string())),
+                }
+            }
+            "bg" => required("\\bg <query>").map(MetaCommand::Background),
+            "jobs" => Ok(MetaCommand::Jobs),
+            "format" => required("\\format <table|json|csv|jsonl|markdown>").and_then(|format| {
+                // binary formats can only be written with -q or run
+                format
+                    .parse::<Format>()
+                    .ok()
+                    .filter(|format| !format.is_binary())
+                    .map(MetaCommand::Format)
+                    .ok_or_else(|| {
+                        MetaCommandError::InvalidArgument(String::from(
+                            "\\format <table|json|csv|jsonl|markdown>",
+                        ))
+                    })
+            }),
+            "open" => required("\\open <record number>").and_then(|number| {
+                number.parse().map(MetaCommand::Open).map_err(|_| {
+                    MetaCommandError::InvalidArgument(String::from("\\open <record number>"))
+                })
+            }),
+            "find" => match argument.split_once(char::is_whitespace) {
+                Some(("label", text)) => Ok(MetaCommand::FindLabel(
+                    text.trim().trim_matches('"').to_string(),
+                )),
+                _ => Err(MetaCommandError::InvalidArgument(String::from(
+                    "\\find label <text>",
+                ))),
+            },
+            "listviews" => required("\\listviews <object>").map(MetaCommand::ListViews),
+            "import-listview" => {
+                required("\\import-listview <list view Id>").map(MetaCommand::ImportListView)
+            }
+            "sosl" => required("\\sosl FIND {<text>} [IN ... FIELDS] [RETURNING ...]")
+                .map(MetaCommand::Sosl),
+            "report" => required("\\report <report Id or name>").map(|report| {
+                MetaCommand::Report(report.trim_matches(|c| c == '"' || c == '\'').to_string())
+            }),
+            "schema" => match argument {
+                "pull" => Ok(MetaCommand::SchemaPull),
+                _ => Err(MetaCommandError::InvalidArgument(String::from(
+                    "\\schema pull",
+                ))),
+            },
+            "?" => Ok(MetaCommand::Help),
+            "dt" | "objects" => Ok(MetaCommand::Objects(
+                Some(argument.to_string()).filter(|pattern| !pattern.is_empty()),
+            )),
+            "fields" => match argument.split_once(char::is_whitespace) {
+                Some((object, pattern)) => Ok(MetaCommand::Fields(
+                    object.to_string(),
+                    Some(pattern.trim().to_string()),
+                )),
+                None => required("\\fields <object> [pattern]")
+                    .map(|object| MetaCommand::Fields(object, None)),
+            },
+            "d" | "describe" => required("\\d <object>").map(MetaCommand::Describe),
+            "cache" => match argument {
+                "" | "stats" => Ok(MetaCommand::CacheStats),
+                "clear" => Ok(MetaCommand::CacheClear),
+                _ => Err(MetaCommandError::InvalidArgument(String::from(
+                    "\\cache [stats|clear]",
+                ))),
+            },
+            "refresh" => Ok(MetaCommand::Refresh(
+                Some(argument.to_string()).filter(|object| !object.is_empty()),
+            )),
+            "subscribe" => required("\\subscribe <channel>").map(MetaCommand::Subscribe),
+            "why" => required("\\why <comment>")
+                .map(|comment| MetaCommand::Why(comment.trim_matches('"').to_string())),
+            "o" => Ok(MetaCommand::Output(
+                Some(argument.trim_matches(|c| c == '"' || c == '\'').to_string())
+                    .filter(|path| !path.is_empty()),
+            )),
+            "use" => Ok(MetaCommand::Use(
+                Some(argument.to_string()).filter(|object| !object.is_empty()),
+            )),
+            "org" => Ok(MetaCommand::Org(
+                Some(argument.to_string()).filter(|org| !org.is_empty()),
+            )),
+            "set" => match argument.split_once(char::is_whitespace) {
+                Some((name, value)) => Ok(MetaCommand::Set(Some((
+                    name.to_string(),
+                    value.trim().to_string(),
+                )))),
+                None if argument.is_empty() => Ok(MetaCommand::Set(None)),
+                None => Err(MetaCommandError::MissingArgument(String::from(
+                    "\\set <name> <value>",
+                ))),
+            },
+            "fg" => required("\\fg <job id>").and_then(|id| {
+                id.parse()
+                    .map(MetaCommand::Foreground)
+                    .map_err(|_| MetaCommandError::InvalidArgument(String::from("\\fg <job id>")))
+            }),
+            _ => Err(MetaCommandError::UnknownCommand(command.to_string())),
+        };
+
+        Some(meta_command)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_commands_are_known() {
+        for (usage, _) in COMMANDS {
+            let command = usage.split_whitespace().next().unwrap();
+            assert_ne!(
+                MetaCommand::parse(command),
+                Some(Err(MetaCommandError::UnknownCommand(
+                    command.trim_start_matches('\\').to_string()
+                ))),
+                "{}",
+                usage
+            );
+        }
+        assert_eq!(MetaCommand::parse(" help "), Some(Ok(MetaCommand::Help)));
+        assert_eq!(MetaCommand::parse("\\?"), Some(Ok(MetaCommand::Help)));
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(MetaCommand::parse("Account.select(Id)"), None);
+        assert_eq!(
+            MetaCommand::parse("\\from-soql SELECT Id FROM Account"),
+            Some(Ok(MetaCommand::FromSoql(String::from(
+                "SELECT Id FROM Account"
+            ))))
+        );
+        assert_eq!(
+            MetaCommand::parse("\\from-soql"),
+            Some(Err(MetaCommandError::MissingArgument(String::from(
+                "\\from-soql <SOQL>"
+            ))))
+        );
+        assert_eq!(MetaCommand::parse("\\types"), Some(Ok(MetaCommand::Types)));
+        assert_eq!(
+            MetaCommand::parse("\\sosl FIND {Acme} RETURNING Account(Name), Contact"),
+            Some(Ok(MetaCommand::Sosl(String::from(
+                "FIND {Acme} RETURNING Account(Name), Contact"
+            ))))
+        );
+        assert_eq!(MetaCommand::parse("\\x"), Some(Ok(MetaCommand::Expanded)));
+        assert_eq!(
+            MetaCommand::parse("\\whoami"),
+            Some(Ok(MetaCommand::WhoAmI))
+        );
+        assert_eq!(
+            MetaCommand::parse("\\report \"Open Cases by Owner\""),
+            Some(Ok(MetaCommand::Report(String::from("Open Cases by Owner"))))
+        );
+        assert_eq!(
+            MetaCommand::parse("\\subscribe /data/AccountChangeEvent"),
+            Some(Ok(MetaCommand::Subscribe(String::from(
+                "/data/AccountChangeEvent"
+            ))))
+        );
+        assert!(matches!(MetaCommand::parse("\\subscribe"), Some(Err(_))));
+        assert_eq!(
+            MetaCommand::parse("\\schema pull"),
+            Some(Ok(MetaCommand::SchemaPull))
+        );
+        assert!(matches!(MetaCommand::parse("\\schema push"), Some(Err(_))));
+        assert_eq!(
+            MetaCommand::parse("\\refresh Account"),
+            Some(Ok(MetaCommand::Refresh(Some(String::from("Account")))))
+        );
+        assert_eq!(
+            MetaCommand::parse("\\objects opp*"),
+            Some(Ok(MetaCommand::Objects(Some(String::from("opp*")))))
+        );
+        assert_eq!(
+            MetaCommand::parse("\\fields Account *phone*"),
+            Some(Ok(MetaCommand::Fields(
+                String::from("Account"),
+                Some(String::from("*phone*"))
+            )))
+        );
+        assert_eq!(
+            MetaCommand::parse("\\fields"),
+            Some(Err(MetaCommandError::MissingArgument(String::from(
+                "\\fields <object> [pattern]"
+            ))))
+        );
+        assert_eq!(
+            MetaCommand::parse("\\dt"),
+            Some(Ok(MetaCommand::Objects(None)))
+        );
+        assert_eq!(
+            MetaCommand::parse("\\d Account"),
+            Some(Ok(MetaCommand::Describe(String::from("Account"))))
+        );
+        assert_eq!(
+            MetaCommand::parse("\\describe"),
+            Some(Err(MetaCommandError::MissingArgument(String::from(
+                "\\d <object>"
+            ))))
+        );
+        assert_eq!(
+            MetaCommand::parse("\\cache"),
+            Some(Ok(MetaCommand::CacheStats))
+        );
+        assert_eq!(
+            MetaCommand::parse("\\cache clear"),
+            Some(Ok(MetaCommand::CacheClear))
+        );
+        assert!(matches!(MetaCommand::parse("\\cache drop"), Some(Err(_))));
+        assert_eq!(
+            MetaCommand::parse("\\refresh"),
+            Some(Ok(MetaCommand::Refresh(None)))
+        );
+        assert_eq!(MetaCommand::parse("\\chart"), Some(Ok(MetaCommand::Chart)));
+        assert_eq!(
+            MetaCommand::parse("\\sort Description DESC"),
+            Some(Ok(MetaCommand::Sort(String::from("Description"), true)))
+        );
+        assert_eq!(
+            MetaCommand::parse("\\sort Name"),
+            Some(Ok(MetaCommand::Sort(String::from("Name"), false)))
+        );
+        assert!(matches!(
+            MetaCommand::parse("\\sort Name up"),
+            Some(Err(MetaCommandError::InvalidArgument(_)))
+        ));
+        assert_eq!(
+            MetaCommand::parse("\\open 3"),
+            Some(Ok(MetaCommand::Open(3)))
+        );
+        assert_eq!(
+            MetaCommand::parse("\\find label \"商談\""),
+            Some(Ok(MetaCommand::FindLabel(String::from("商談"))))
+        );
+        assert_eq!(
+            MetaCommand::parse("\\listviews Account"),
+            Some(Ok(MetaCommand::ListViews(String::from("Account"))))
+        );
+        assert_eq!(
+            MetaCommand::parse("\\import-listview 00B5g00000AbCdE"),
+            Some(Ok(MetaCommand::ImportListView(String::from(
+                "00B5g00000AbCdE"
+            ))))
+        );
+        assert_eq!(
+            MetaCommand::parse("\\why \"checking duplicate leads for INC-1234\""),
+            Some(Ok(MetaCommand::Why(String::from(
+                "checking duplicate leads for INC-1234"
+            ))))
+        );
+        assert_eq!(
+            MetaCommand::parse("\\o 'results.csv'"),
+            Some(Ok(MetaCommand::Output(Some(String::from("results.csv")))))
+        );
+        assert_eq!(
+            MetaCommand::parse("\\o"),
+            Some(Ok(MetaCommand::Output(None)))
+        );
+        assert_eq!(
+            MetaCommand::parse("\\use Opportunity"),
+            Some(Ok(MetaCommand::Use(Some(String::from("Opportunity")))))
+        );
+        assert_eq!(
+            MetaCommand::parse("\\use"),
+            Some(Ok(MetaCommand::Use(None)))
+        );
+        assert_eq!(
+            MetaCommand::parse("\\org uat"),
+            Some(Ok(MetaCommand::Org(Some(String::from("uat")))))
+        );
+        assert_eq!(
+            MetaCommand::parse("\\org"),
+            Some(Ok(MetaCommand::Org(None)))
+        );
+        assert_eq!(
+            MetaCommand::parse("\\set autocorrect on"),
+            Some(Ok(MetaCommand::Set(Some((
+                String::from("autocorrect"),
+                String::from("on")
+            )))))
+        );
+        assert_eq!(
+            MetaCommand::parse("\\fg 2"),
+            Some(Ok(MetaCommand::Foreground(2)))
+        );
+        assert_eq!(
+            MetaCommand::parse("\\format json"),
+            Some(Ok(MetaCommand::Format(Format::Json)))
+        );
+        assert!(matches!(
+            MetaCommand::parse("\\format parquet"),
+            Some(Err(MetaCommandError::InvalidArgument(_)))
+        ));
+        assert_eq!(
+            MetaCommand::parse("\\fg x"),
+            Some(Err(MetaCommandError::InvalidArgument(String::from(
+                "\\fg <job id>"
+            ))))
+        );
+        assert_eq!(
+            MetaCommand::parse("\\unknown"),
+            Some(Err(MetaCommandError::UnknownCommand(String::from(
+                "unknown"
+            ))))
+        );
+    }
+}
@@ -0,0 +1,1075 @@
+use crate::cache::{Field, SObject};
+use crate::result_schema::{infer_columns, Column, ColumnType};
+use chrono::DateTime;
+use chrono_tz::Tz;
+use serde_json::Value;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+use std::path::Path;
+use std::str::FromStr;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Table,
+    Json,
+    Csv,
+    Jsonl,
+    Markdown,
+    Parquet,
+}
+
+// How records are rendered, besides the format.
+#[derive(Debug, Clone, Default)]
+pub struct RenderOptions {
+    // the fields of select(), in the order their columns are rendered
+    pub fields: Vec<String>,
+    // column types from the cached field metadata, see result_schema::infer_columns
+    pub column_types: HashMap<String, ColumnType>,
+    // show datetimes in this timezone and numbers with thousands separators in
+    // tables and Markdown
+    pub localize: Option<Tz>,
+    // widest a table cell may be, 0 for no limit
+    pub max_width: usize,
+    // wrap cells wider than max_width instead of cutting them
+    pub wrap: bool,
+    // the columns of the first batch of a streamed result. Later batches are rendered in
+    // them, and CSV and Markdown leave out the header
+    pub columns: Option<Vec<Column>>,
+    // keep the `attributes` (type and URL) of records in JSON output
+    pub raw: bool,
+    // tables show each record as a block of `Field: value` lines
+    pub expanded: bool,
+    // records before these ones, so that expanded blocks are numbered across pages
+    pub record_offset: usize,
+}
+
+impl Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Format::Table => "table",
+            Format::Json => "json",
+            Format::Csv => "csv",
+            Format::Jsonl => "jsonl",
+            Format::Markdown => "markdown",
+            Format::Parquet => "parquet",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "table" => Ok(Format::Table),
+            "json" => Ok(Format::Json),
+            "csv" => Ok(Format::Csv),
+            "jsonl" => Ok(Format::Jsonl),
+            "markdown" => Ok(Format::Markdown),
+            "parquet" => Ok(Format::Parquet),
+            _ => Err(format!(
+                "Unknown format: {} (expected table, json, csv, jsonl, markdown or parquet)",
+                s
+            )),
+        }
+    }
+}
+
+impl Format {
+    // The format of a file written by out() or \o, from its extension.
+    pub fn from_path(path: &Path) -> Result<Format, String> {
+        let extension = path
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase());
+        match extension.as_deref() {
+            Some("txt") => Ok(Format::Table),
+            Some("json") => Ok(Format::Json),
+            Some("csv") => Ok(Format::Csv),
+            Some("jsonl") | Some("ndjson") => Ok(Format::Jsonl),
+            Some("md") | Some("markdown") => Ok(Format::Markdown),
+            Some("parquet") => Ok(Format::Parquet),
+            _ => Err(format!(
+                "Unknown format of {} (expected .txt, .json, .csv, .jsonl, .md or .parquet)",
+                path.display()
+            )),
+        }
+    }
+
+    pub fn render(&self, records: &[Value], options: &RenderOptions) -> String {
+        match self {
+            Format::Table if options.expanded => render_expanded(records, options),
+            Format::Table => render_table(records, options),
+            Format::Json => {
+                let records = records.iter().map(|record| for_output(record, options));
+                serde_json::to_string_pretty(&records.collect::<Vec<Value>>()).unwrap_or_default()
+            }
+            Format::Csv => render_csv(records, options),
+            Format::Jsonl => render_jsonl(records, options),
+            Format::Markdown => render_markdown(records, options),
+            // binary, written by export::write_parquet
+            Format::Parquet => String::new(),
+        }
+    }
+
+    // Line oriented formats are printed in full so that they can be piped or copied.
+    pub fn is_paged(&self) -> bool {
+        matches!(self, Format::Table | Format::Json)
+    }
+
+    pub fn is_binary(&self) -> bool {
+        matches!(self, Format::Parquet)
+    }
+}
+
+// Renders records as an aligned table with one row per record. Parent fields become
+// dotted columns (Account.Name) and numbers are right-aligned. A record takes several
+// lines when its cells are wrapped.
+pub fn render_table(records: &[Value], options: &RenderOptions) -> String {
+    let columns = columns(records, options);
+    if columns.is_empty() {
+        return String::new();
+    }
+
+    let rows: Vec<Vec<Vec<String>>> = records
+        .iter()
+        .map(|record| {
+            columns
+                .iter()
+                .map(|column| cell_lines(record, column, options))
+                .collect()
+        })
+        .collect();
+
+    let widths: Vec<usize> = columns
+        .iter()
+        .enumerate()
+        .map(|(i, column)| {
+            rows.iter()
+                .flat_map(|row| row[i].iter().map(|line| line.width()))
+                .chain([column.name.width()])
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    let border = format!(
+        "+{}+",
+        widths
+            .iter()
+            .map(|width| "-".repeat(width + 2))
+            .collect::<Vec<String>>()
+            .join("+")
+    );
+    let line = |cells: Vec<(&str, bool)>| {
+        let cells: Vec<String> = cells
+            .iter()
+            .zip(&widths)
+            .map(|((text, right), width)| {
+                let padding = " ".repeat(width - text.width());
+                if *right {
+                    format!(" {}{} ", padding, text)
+                } else {
+                    format!(" {}{} ", text, padding)
+                }
+            })
+            .collect();
+        format!("|{}|", cells.join("|"))
+    };
+
+    let mut table = vec![border.clone()];
+    table.push(line(
+        columns.iter().map(|c| (c.name.as_str(), false)).collect(),
+    ));
+    table.push(border.clone());
+    for row in &rows {
+        let height = row.iter().map(|lines| lines.len()).max().unwrap_or(1);
+        for i in 0..height {
+            table.push(line(
+                row.iter()
+                    .zip(&columns)
+                    .map(|(lines, column)| {
+                        let numeric = matches!(
+                            column.column_type,
+                            ColumnType::Integer | ColumnType::Decimal
+                        );
+                        (lines.get(i).map_or("", |text| text.as_str()), numeric)
+                    })
+                    .collect(),
+            ));
+        }
+    }
+    table.push(border);
+
+    table.join("\n")
+}
+
+// Renders each record as a numbered block of `Field: value` lines, like psql's \x, for
+// objects too wide for a table. Wrapped values continue under the value column.
+pub fn render_expanded(records: &[Value], options: &RenderOptions) -> String {
+    let columns = columns(records, options);
+    if columns.is_empty() {
+        return String::new();
+    }
+    let name_width = columns.iter().map(|c| c.name.width()).max().unwrap_or(0);
+
+    let mut lines = Vec::new();
+    for (i, record) in records.iter().enumerate() {
+        lines.push(format!("-[ RECORD {} ]-", options.record_offset + i + 1));
+        for column in &columns {
+            let padding = " ".repeat(name_width - column.name.width());
+            for (n, line) in cell_lines(record, column, options).iter().enumerate() {
+                let label = if n == 0 {
+                    format!("{}:{}", column.name, padding)
+                } else {
+                    " ".repeat(name_width + 1)
+                };
+                lines.push(format!("{} {}", label, line).trim_end().to_string());
+            }
+        }
+    }
+
+    lines.join("\n")
+}
+
+// Renders records as RFC 4180 CSV with a header row. Every line, including the last,
+// ends with CRLF. Parent fields are flattened the same way as in tables.
+pub fn render_csv(records: &[Value], options: &RenderOptions) -> String {
+    let columns = columns(records, options);
+    if columns.is_empty() {
+        return String::new();
+    }
+
+    let mut csv = String::new();
+    let mut push_line = |fields: Vec<String>| {
+        let fields: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+        csv.push_str(&fields.join(","));
+        csv.push_str("\r\n");
+    };
+
+    if options.columns.is_none() {
+        push_line(columns.iter().map(|c| c.name.clone()).collect());
+    }
+    for record in records {
+        push_line(
+            columns
+                .iter()
+                .map(|column| text(value_at(record, &column.name)))
+                .collect(),
+        );
+    }
+
+    csv
+}
+
+// Renders records as a GitHub flavored Markdown table. Numeric columns are
+// right-aligned and pipes and line breaks in values are escaped.
+pub fn render_markdown(records: &[Value], options: &RenderOptions) -> String {
+    let columns = columns(records, options);
+    if columns.is_empty() {
+        return String::new();
+    }
+
+    let row = |cells: Vec<String>| format!("| {} |", cells.join(" | "));
+    let mut lines = Vec::new();
+    if options.columns.is_none() {
+        lines.push(row(columns
+            .iter()
+            .map(|c| markdown_cell(&c.name))
+            .collect()));
+        lines.push(row(columns
+            .iter()
+            .map(|c| match c.column_type {
+                ColumnType::Integer | ColumnType::Decimal => String::from("---:"),
+                _ => String::from("---"),
+            })
+            .collect()));
+    }
+    for record in records {
+        lines.push(row(columns
+            .iter()
+            .map(|column| markdown_cell(&display_text(record, column, options)))
+            .collect()));
+    }
+
+    lines.join("\n")
+}
+
+fn markdown_cell(text: &str) -> String {
+    text.replace('|', "\\|")
+        .replace("\r\n", "<br>")
+        .replace(['\r', '\n'], "<br>")
+}
+
+// One compact JSON object per record.
+pub fn render_jsonl(records: &[Value], options: &RenderOptions) -> String {
+    records
+        .iter()
+        .map(|record| for_output(record, options).to_string())
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+// A response or record as printed: without the `attributes` of records and their parent
+// and child records, unless raw output is asked for.
+pub fn for_output(value: &Value, options: &RenderOptions) -> Value {
+    if options.raw {
+        value.clone()
+    } else {
+        strip_attributes(value.clone())
+    }
+}
+
+pub fn strip_attributes(mut value: Value) -> Value {
+    match &mut value {
+        Value::Object(map) => {
+            map.remove("attributes");
+            for (_, v) in map.iter_mut() {
+                *v = strip_attributes(v.take());
+            }
+        }
+        Value::Array(values) => {
+            for v in values.iter_mut() {
+                *v = strip_attributes(v.take());
+            }
+        }
+        _ => {}
+    }
+    value
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+// The detail rows of a report run through the Analytics API as records keyed by the
+// column labels, with the values formatted like in Salesforce, and the labels in the
+// order of the report's columns. Grouped reports keep the rows of each group in the fact
+// map under keys such as 0!T and 0_1!T; their subtotals are left out.
+pub fn report_records(response: &Value) -> (Vec<String>, Vec<Value>) {
+    let columns: Vec<(String, String)> = response["reportMetadata"]["detailColumns"]
+        .as_array()
+        .map_or(&[][..], |columns| columns)
+        .iter()
+        .filter_map(Value::as_str)
+        .map(|name| {
+            let label = response["reportExtendedMetadata"]["detailColumnInfo"][name]["label"]
+                .as_str()
+                .unwrap_or(name);
+            (name.to_string(), label.to_string())
+        })
+        .collect();
+
+    let mut records = Vec::new();
+    if let Some(fact_map) = response["factMap"].as_object() {
+        for fact in fact_map.values() {
+            for row in fact["rows"].as_array().map_or(&[][..], |rows| rows) {
+                let cells = row["dataCells"].as_array().map_or(&[][..], |cells| cells);
+                let record: serde_json::Map<String, Value> = columns
+                    .iter()
+                    .zip(cells)
+                    .map(|((_, label), cell)| (label.clone(), cell["label"].clone()))
+                    .collect();
+                records.push(Value::Object(record));
+            }
+        }
+    }
+    (
+        columns.into_iter().map(|(_, label)| label).collect(),
+        records,
+    )
+}
+
+// The columns of plan_records, in the order they are shown.
+pub const PLAN_COLUMNS: [&str; 7] = [
+    "LeadingOperationType",
+    "Cardinality",
+    "SobjectCardinality",
+    "RelativeCost",
+    "SobjectType",
+    "Fields",
+    "Notes",
+];
+
+// The plans of an explain response as records to render, cheapest first as Salesforce
+// returns them. A relative cost above 1 means the query is not selective.
+pub fn plan_records(response: &Value) -> Vec<Value> {
+    let join = |values: &Value, key: Option<&str>| {
+        values.as_array().map(|values| {
+            values
+                .iter()
+                .filter_map(|value| match key {
+                    Some(key) => value[key].as_str(),
+                    None => value.as_str(),
+                })
+                .collect::<Vec<&str>>()
+                .join(", ")
+        })
+    };
+    response["plans"].as_array().map_or_else(Vec::new, |plans| {
+        plans
+            .iter()
+            .map(|plan| {
+                serde_json::json!({
+                    "LeadingOperationType": plan["leadingOperationType"],
+                    "Cardinality": plan["cardinality"],
+                    "SobjectCardinality": plan["sobjectCardinality"],
+                    "RelativeCost": plan["relativeCost"],
+                    "SobjectType": plan["sobjectType"],
+                    "Fields": join(&plan["fields"], None),
+                    "Notes": join(&plan["notes"], Some("description")),
+                })
+            })
+            .collect()
+    })
+}
+
+// The columns of object_records, in the order they are shown.
+pub const OBJECT_COLUMNS: [&str; 3] = ["Name", "Label", "Custom"];
+
+// Objects as records to render for \objects.
+pub fn object_records(objects: &[&SObject]) -> Vec<Value> {
+    objects
+        .iter()
+        .map(|object| {
+            serde_json::json!({
+                "Name": object.name,
+                "Label": object.label,
+                "Custom": object.custom,
+            })
+        })
+        .collect()
+}
+
+// The columns of field_records, in the order they are shown.
+pub const FIELD_COLUMNS: [&str; 6] = ["Name", "Label", "Type", "Length", "Nillable", "Filterable"];
+
+// The fields of an object as records to render for \d, in the order of the describe.
+// References show the objects they point to, e.g. reference(User), and the length is
+// left empty for fields that have none.
+pub fn field_records(fields: &[Field]) -> Vec<Value> {
+    fields
+        .iter()
+        .map(|field| {
+            let field_type = match field.reference_to.is_empty() {
+                true => field.field_type.clone(),
+                false => format!("{}({})", field.field_type, field.reference_to.join(", ")),
+            };
+            serde_json::json!({
+                "Name": field.name,
+                "Label": field.label,
+                "Type": field_type,
+                "Length": Some(field.length).filter(|length| *length > 0),
+                "Nillable": field.nillable,
+                "Filterable": field.filterable,
+            })
+        })
+        .collect()
+}
+
+// Splits the records of a SOSL search, which mixes objects, into the records of each
+// object in the order the objects first appear.
+pub fn group_by_type(records: &[Value]) -> Vec<(String, Vec<Value>)> {
+    let mut groups: Vec<(String, Vec<Value>)> = Vec::new();
+    for record in records {
+        let object = record["attributes"]["type"].as_str().unwrap_or_default();
+        match groups.iter_mut().find(|(name, _)| name == object) {
+            Some((_, group)) => group.push(record.clone()),
+            None => groups.push((object.to_string(), vec![record.clone()])),
+        }
+    }
+    groups
+}
+
+// Sorts fetched records by a column, e.g. one Salesforce can not sort such as a long
+// text area. Numbers compare by value and everything else by its text; nulls come
+// first in ascending order like in SOQL.
+pub fn sort_records(records: &mut [Value], column: &str, descending: bool) -> Result<(), String> {
+    let column = infer_columns(records, &HashMap::new(), &[])
+        .into_iter()
+        .find(|c| c.name.eq_ignore_ascii_case(column))
+        .ok_or_else(|| format!("Unknown column: {}", column))?
+        .name;
+
+    records.sort_by(|a, b| {
+        let ordering = compare_values(value_at(a, &column), value_at(b, &column));
+        if descending {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    });
+    Ok(())
+}
+
+fn compare_values(a: Option<&Value>, b: Option<&Value>) -> Ordering {
+    let a = a.filter(|v| !v.is_null());
+    let b = b.filter(|v| !v.is_null());
+    match (a, b) {
+        (None, None) => Ordering::Equal,
+        (None, Some(_)) => Ordering::Less,
+        (Some(_), None) => Ordering::Greater,
+        (Some(Value::Number(a)), Some(Value::Number(b))) => a
+            .as_f64()
+            .partial_cmp(&b.as_f64())
+            .unwrap_or(Ordering::Equal),
+        (a, b) => text(a).cmp(&text(b)),
+    }
+}
+
+// The value at a dotted column of a record, e.g. Owner.Alias.
+pub fn value_at<'a>(record: &'a Value, column: &str) -> Option<&'a Value> {
+    column
+        .split('.')
+        .try_fold(record, |value, key| value.get(key))
+}
+
+// Strings are shown without quotes and null as empty.
+fn text(value: Option<&Value>) -> String {
+    match value {
+        None | Some(Value::Null) => String::new(),
+        Some(Value::String(s)) => s.clone(),
+        Some(value) => value.to_string(),
+    }
+}
+
+// The lines of a table cell. Without wrapping, line breaks are flattened so that every
+// record stays on one line and long values are cut with an ellipsis. With wrapping, line
+// breaks are kept and long lines are broken at spaces where possible.
+fn cell_lines(record: &Value, column: &Column, options: &RenderOptions) -> Vec<String> {
+    let text = display_text(record, column, options);
+    if !options.wrap {
+        return vec![truncate(
+            &text.replace(['\r', '\n'], " "),
+            options.max_width,
+        )];
+    }
+
+    let lines: Vec<String> = text
+        .lines()
+        .flat_map(|line| wrap(line, options.max_width))
+        .collect();
+    if lines.is_empty() {
+        vec![String::new()]
+    } else {
+        lines
+    }
+}
+
+fn truncate(text: &str, max_width: usize) -> String {
+    if max_width == 0 || text.width() <= max_width {
+        return text.to_string();
+    }
+
+    let mut truncated = String::new();
+    let mut width = 0;
+    for c in text.chars() {
+        width += c.width().unwrap_or(0);
+        if width > max_width - 1 {
+            break;
+        }
+        truncated.push(c);
+    }
+    truncated.push('…');
+    truncated
+}
+
+fn wrap(line: &str, max_width: usize) -> Vec<String> {
+    if max_width == 0 || line.width() <= max_width {
+        return vec![line.to_string()];
+    }
+
+    let mut lines = Vec::new();
+    let mut current = String::new();
+    for word in line.split(' ') {
+        let separator = usize::from(!current.is_empty());
+        if current.width() + separator + word.width() <= max_width {
+            if separator == 1 {
+                current.push(' ');
+            }
+            current.push_str(word);
+            continue;
+        }
+        if !current.is_empty() {
+            lines.push(std::mem::take(&mut current));
+        }
+        // a word wider than the cell is broken wherever it has to be
+        for c in word.chars() {
+            if current.width() + c.width().unwrap_or(0) > max_width {
+                lines.push(std::mem::take(&mut current));
+            }
+            current.push(c);
+        }
+    }
+    lines.push(current);
+    lines
+}
+
+// The columns of the records, typed by the metadata in the options where there is any.
+pub fn columns(records: &[Value], options: &RenderOptions) -> Vec<Column> {
+    if let Some(columns) = &options.columns {
+        return columns.clone();
+    }
+    let mut columns = infer_columns(records, &HashMap::new(), &options.fields);
+    for column in &mut columns {
+        if let Some(column_type) = options.column_types.get(&column.name) {
+            column.column_type = *column_type;
+        }
+    }
+    columns
+}
+
+// The text of a cell for people to read, localized when the options ask for it.
+fn display_text(record: &Value, column: &Column, options: &RenderOptions) -> String {
+    let text = text(value_at(record, &column.name));
+    match (options.localize, column.column_type) {
+        (Some(timezone), ColumnType::DateTime) => {
+            DateTime::parse_from_str(&text, "%Y-%m-%dT%H:%M:%S%.f%z")
+                .map(|datetime| {
+                    datetime
+                        .with_timezone(&timezone)
+                        .format("%Y-%m-%d %H:%M:%S %Z")
+                        .to_string()
+                })
+                .unwrap_or(text)
+        }
+        (Some(_), ColumnType::Integer | ColumnType::Decimal) => group_thousands(&text),
+        _ => text,
+    }
+}
+
+// 1234567.5 becomes 1,234,567.5. Anything that is not a plain decimal number is kept.
+fn group_thousands(number: &str) -> String {
+    let (sign, unsigned) = match number.strip_prefix('-') {
+        Some(unsigned) => ("-", unsigned),
+        None => ("", number),
+    };
+    let (integer, fraction) = match unsigned.split_once('.') {
+        Some((integer, fraction)) => (integer, format!(".{}", fraction)),
+        None => (unsigned, String::new()),
+    };
+    if integer.is_empty() || !integer.chars().all(|c| c.is_ascii_digit()) {
+        return number.to_string();
+    }
+
+    let digits: Vec<char> = integer.chars().collect();
+    let grouped: Vec<String> = digits
+        .rchunks(3)
+        .rev()
+        .map(|chunk| chunk.iter().collect())
+        .collect();
+    format!("{}{}{}", sign, grouped.join(","), fraction)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_format_from_path() {
+        assert_eq!(
+            Format::from_path(Path::new("out/results.CSV")),
+            Ok(Format::Csv)
+        );
+        assert_eq!(
+            Format::from_path(Path::new("results.md")),
+            Ok(Format::Markdown)
+        );
+        assert!(Format::from_path(Path::new("results")).is_err());
+    }
+
+    #[test]
+    fn test_render_table() {
+        let records = vec![
+            json!({
+                "attributes": { "type": "Account" },
+                "Name": "商談テスト",
+                "NumberOfEmployees": 5,
+                "Owner": { "attributes": { "type": "User" }, "Alias": "sfdc" },
+            }),
+            json!({
+                "attributes": { "type": "Account" },
+                "Name": "Acme\nInc",
+                "NumberOfEmployees": 1200,
+                "Owner": null,
+            }),
+        ];
+
+        assert_eq!(
+            render_table(&records, &RenderOptions::default()),
+            [
+                "+------------+-------------------+-------------+",
+                "| Name       | NumberOfEmployees | Owner.Alias |",
+                "+------------+-------------------+-------------+",
+                "| 商談テスト |                 5 | sfdc        |",
+                "| Acme Inc   |              1200 |             |",
+                "+------------+-------------------+-------------+",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn test_render_expanded() {
+        let records = vec![
+            json!({
+                "attributes": { "type": "Account" },
+                "Name": "Acme",
+                "Description": "line 1\nline 2",
+                "Owner": { "attributes": { "type": "User" }, "Alias": "sfdc" },
+            }),
+            json!({ "attributes": { "type": "Account" }, "Name": "Globex", "Description": null, "Owner": null }),
+        ];
+        let options = RenderOptions {
+            fields: vec![String::from("Name"), String::from("Description")],
+            wrap: true,
+            record_offset: 10,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            Format::Table.render(
+                &records,
+                &RenderOptions {
+                    expanded: true,
+                    ..options
+                }
+            ),
+            [
+                "-[ RECORD 11 ]-",
+                "Name:        Acme",
+                "Description: line 1",
+                "             line 2",
+                "Owner.Alias: sfdc",
+                "-[ RECORD 12 ]-",
+                "Name:        Globex",
+                "Description:",
+                "Owner.Alias:",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn test_sort_records() {
+        let mut records = vec![
+            json!({ "Name": "b", "Amount": 100, "Owner": { "Alias": "z" } }),
+            json!({ "Name": "a", "Amount": null, "Owner": null }),
+            json!({ "Name": "c", "Amount": 20.5, "Owner": { "Alias": "y" } }),
+        ];
+        let names = |records: &[Value]| -> Vec<String> {
+            records.iter().map(|r| text(r.get("Name"))).collect()
+        };
+
+        sort_records(&mut records, "amount", false).unwrap();
+        assert_eq!(names(&records), vec!["a", "c", "b"]);
+        sort_records(&mut records, "Owner.Alias", true).unwrap();
+        assert_eq!(names(&records), vec!["b", "c", "a"]);
+        assert_eq!(
+            sort_records(&mut records, "Email", false),
+            Err(String::from("Unknown column: Email"))
+        );
+    }
+
+    #[test]
+    fn test_render_csv() {
+        let records = vec![
+            json!({
+                "attributes": { "type": "Account" },
+                "Name": "Acme, \"Inc\"",
+                "Description": "line 1\nline 2",
+                "Owner": { "attributes": { "type": "User" }, "Alias": "sfdc" },
+            }),
+            json!({
+                "attributes": { "type": "Account" },
+                "Name": "Globex",
+                "Description": null,
+                "Owner": null,
+            }),
+        ];
+
+        assert_eq!(
+            render_csv(&records, &RenderOptions::default()),
+            "Description,Name,Owner.Alias\r\n\"line 1\nline 2\",\"Acme, \"\"Inc\"\"\",sfdc\r\n,Globex,\r\n"
+        );
+        assert_eq!(
+            render_csv(
+                &records,
+                &RenderOptions {
+                    fields: vec![String::from("Owner.Alias"), String::from("Name")],
+                    ..Default::default()
+                }
+            ),
+            "Owner.Alias,Name,Description\r\nsfdc,\"Acme, \"\"Inc\"\"\",\"line 1\nline 2\"\r\n,Globex,\r\n"
+        );
+        assert_eq!(render_csv(&[], &RenderOptions::default()), "");
+
+        // a later batch of a streamed result, whose first batch had a parent
+        let options = RenderOptions {
+            columns: Some(columns(&records, &RenderOptions::default())),
+            ..Default::default()
+        };
+        assert_eq!(render_csv(&records[1..], &options), ",Globex,\r\n");
+    }
+
+    #[test]
+    fn test_render_jsonl() {
+        let records = vec![
+            json!({
+                "attributes": { "type": "Account", "url": "/services/data/v51.0/sobjects/Account/001" },
+                "Name": "Acme",
+                "Owner": { "attributes": { "type": "User" }, "Alias": "sfdc" },
+                "Contacts": { "totalSize": 1, "records": [{ "attributes": { "type": "Contact" }, "Id": "003" }] },
+            }),
+            json!({ "attributes": { "type": "Account" }, "Name": "Globex", "Owner": null, "Contacts": null }),
+        ];
+
+        assert_eq!(
+            render_jsonl(&records, &RenderOptions::default()),
+            [
+                r#"{"Contacts":{"records":[{"Id":"003"}],"totalSize":1},"Name":"Acme","Owner":{"Alias":"sfdc"}}"#,
+                r#"{"Contacts":null,"Name":"Globex","Owner":null}"#,
+            ]
+            .join("\n")
+        );
+        let raw = RenderOptions {
+            raw: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            render_jsonl(&records[1..], &raw),
+            r#"{"Contacts":null,"Name":"Globex","Owner":null,"attributes":{"type":"Account"}}"#
+        );
+    }
+
+    #[test]
+    fn test_render_markdown() {
+        let records = vec![
+            json!({
+                "attributes": { "type": "Account" },
+                "Name": "A|B",
+                "Description": "line 1\r\nline 2",
+                "NumberOfEmployees": 5,
+            }),
+            json!({
+                "attributes": { "type": "Account" },
+                "Name": "Globex",
+                "Description": null,
+                "NumberOfEmployees": 1200,
+            }),
+        ];
+
+        assert_eq!(
+            render_markdown(&records, &RenderOptions::default()),
+            [
+                "| Description | Name | NumberOfEmployees |",
+                "| --- | --- | ---: |",
+                "| line 1<br>line 2 | A\\|B | 5 |",
+                "|  | Globex | 1200 |",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn test_render_table_localized() {
+        let records = vec![json!({
+            "attributes": { "type": "Opportunity" },
+            "Amount": 1234567.5,
+            "CreatedDate": "2024-01-01T09:00:00.000+0000",
+            "Name": "2024-01-01T09:00:00.000+0000",
+        })];
+        let options = RenderOptions {
+            column_types: HashMap::from([(String::from("CreatedDate"), ColumnType::DateTime)]),
+            localize: Some(chrono_tz::Asia::Tokyo),
+            ..Default::default()
+        };
+
+        let table = render_table(&records, &options);
+        let row = table.lines().nth(3).unwrap();
+        assert_eq!(
+            row,
+            "| 1,234,567.5 | 2024-01-01 18:00:00 JST | 2024-01-01T09:00:00.000+0000 |"
+        );
+    }
+
+    #[test]
+    fn test_render_table_max_width() {
+        let records = vec![json!({
+            "attributes": { "type": "Account" },
+            "Description": "A long description\nof the account",
+            "Name": "Acme",
+        })];
+        let options = RenderOptions {
+            max_width: 11,
+            ..Default::default()
+        };
+        assert_eq!(
+            render_table(&records, &options).lines().nth(3).unwrap(),
+            "| A long des… | Acme |"
+        );
+
+        let options = RenderOptions {
+            max_width: 11,
+            wrap: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            render_table(&records, &options),
+            [
+                "+-------------+------+",
+                "| Description | Name |",
+                "+-------------+------+",
+                "| A long      | Acme |",
+                "| description |      |",
+                "| of the      |      |",
+                "| account     |      |",
+                "+-------------+------+",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn test_truncate_and_wrap() {
+        assert_eq!(truncate("商談テスト", 5), "商談…");
+        assert_eq!(truncate("Acme", 0), "Acme");
+        assert_eq!(wrap("abcdefghij klm", 4), vec!["abcd", "efgh", "ij", "klm"]);
+    }
+
+    #[test]
+    fn test_group_thousands() {
+        assert_eq!(group_thousands("1234567"), "1,234,567");
+        assert_eq!(group_thousands("-1000.25"), "-1,000.25");
+        assert_eq!(group_thousands("999"), "999");
+        assert_eq!(group_thousands("1e21"), "1e21");
+    }
+
+    #[test]
+    fn test_report_records() {
+        let response = json!({
+            "allData": true,
+            "reportMetadata": { "detailColumns": ["ACCOUNT.NAME", "AMOUNT"] },
+            "reportExtendedMetadata": {
+                "detailColumnInfo": {
+                    "ACCOUNT.NAME": { "label": "Account Name", "dataType": "string" },
+                    "AMOUNT": { "label": "Amount", "dataType": "currency" },
+                },
+            },
+            "factMap": {
+                "0!T": {
+                    "rows": [{ "dataCells": [{ "label": "Acme", "value": "001" }, { "label": "USD 1,000", "value": { "amount": 1000 } }] }],
+                    "aggregates": [{ "label": "1", "value": 1 }],
+                },
+                "T!T": { "aggregates": [{ "label": "2", "value": 2 }] },
+            },
+        });
+        let (columns, records) = report_records(&response);
+        assert_eq!(columns, vec!["Account Name", "Amount"]);
+        assert_eq!(
+            records,
+            vec![json!({ "Account Name": "Acme", "Amount": "USD 1,000" })]
+        );
+    }
+
+    #[test]
+    fn test_field_records() {
+        let fields = vec![
+            Field {
+                name: String::from("Name"),
+                label: String::from("Account Name"),
+                field_type: String::from("string"),
+                length: 255,
+                filterable: true,
+                ..Default::default()
+            },
+            Field {
+                name: String::from("OwnerId"),
+                label: String::from("Owner ID"),
+                field_type: String::from("reference"),
+                reference_to: vec![String::from("User")],
+                nillable: true,
+                ..Default::default()
+            },
+        ];
+        assert_eq!(
+            field_records(&fields),
+            vec![
+                json!({ "Name": "Name", "Label": "Account Name", "Type": "string", "Length": 255, "Nillable": false, "Filterable": true }),
+                json!({ "Name": "OwnerId", "Label": "Owner ID", "Type": "reference(User)", "Length": null, "Nillable": true, "Filterable": false }),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_plan_records() {
+        let response = json!({
+            "plans": [{
+                "cardinality": 12,
+                "fields": ["Email"],
+                "leadingOperationType": "Index",
+                "notes": [],
+                "relativeCost": 0.02,
+                "sobjectCardinality": 120000,
+                "sobjectType": "Lead",
+            }, {
+                "cardinality": 12,
+                "fields": [],
+                "leadingOperationType": "TableScan",
+                "notes": [{ "description": "Not considering filter for optimization because unindexed", "fields": ["IsDeleted"], "tableEnumOrId": "Lead" }],
+                "relativeCost": 2.8,
+                "sobjectCardinality": 120000,
+                "sobjectType": "Lead",
+            }],
+        });
+        let records = plan_records(&response);
+        assert_eq!(
+            records[0],
+            json!({
+                "LeadingOperationType": "Index",
+                "Cardinality": 12,
+                "SobjectCardinality": 120000,
+                "RelativeCost": 0.02,
+                "SobjectType": "Lead",
+                "Fields": "Email",
+                "Notes": "",
+            })
+        );
+        assert_eq!(
+            records[1]["Notes"],
+            "Not considering filter for optimization because unindexed"
+        );
+        assert!(plan_records(&json!({})).is_empty());
+    }
+
+    #[test]
+    fn test_group_by_type() {
+        let records = vec![
+            json!({ "attributes": { "type": "Account" }, "Name": "Acme" }),
+            json!({ "attributes": { "type": "Contact" }, "Name": "Jane Acme" }),
+            json!({ "attributes": { "type": "Account" }, "Name": "Acme Japan" }),
+        ];
+        let groups = group_by_type(&records);
+        assert_eq!(
+            groups
+                .iter()
+                .map(|(object, records)| (object.as_str(), records.len()))
+                .collect::<Vec<(&str, usize)>>(),
+            vec![("Account", 2), ("Contact", 1)]
+        );
+        assert_eq!(groups[0].1[1]["Name"], "Acme Japan");
+    }
+
+    #[test]
+    fn test_format_from_str() {
+        assert_eq!("table".parse::<Format>(), Ok(Format::Table));
+        assert!("xml".parse::<Format>().is_err());
+    }
+}
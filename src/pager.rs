@@ -0,0 +1,205 @@
+use crate::helper::DynError;
+use crate::output::{Format, RenderOptions};
+use crate::salesforce::Connection;
+use serde_json::Value;
+
+// lines kept free for the page footer and the prompt
+const RESERVED_LINES: usize = 3;
+
+// Holds the records of the last query so they can be browsed with :n / :p.
+// Further API pages are only fetched once the fetched records run out.
+pub struct Pager {
+    records: Vec<Value>,
+    total_size: u64,
+    next_records_url: Option<String>,
+    count_relationships: Vec<String>,
+    options: RenderOptions,
+    height: usize,
+    format: Format,
+    page_starts: Vec<usize>,
+}
+
+impl Pager {
+    pub fn new(
+        response: Value,
+        count_relationships: &[String],
+        options: &RenderOptions,
+        height: usize,
+        format: Format,
+    ) -> Self {
+        let mut pager = Pager {
+            records: Vec::new(),
+            total_size: response["totalSize"].as_u64().unwrap_or(0),
+            next_records_url: None,
+            count_relationships: count_relationships.to_vec(),
+            options: options.clone(),
+            height: height.saturating_sub(RESERVED_LINES).max(1),
+            format,
+            page_starts: vec![0],
+        };
+        pager.append(response);
+        pager
+    }
+
+    // Whether the whole result fits on one screen, in which case there is nothing to page.
+    pub fn fits_on_screen(&self) -> bool {
+        self.next_records_url.is_none() && self.page_end(0) == self.records.len()
+    }
+
+    pub fn current_page(&self) -> String {
+        let start = *self.page_starts.last().unwrap();
+        let end = self.page_end(start);
+        let options = RenderOptions {
+            record_offset: start,
+            ..self.options.clone()
+        };
+        let page = self.format.render(&self.records[start..end], &options);
+
+        let more = if end < self.records.len() || self.next_records_url.is_some() {
+            ":n next, "
+        } else {
+            ""
+        };
+        format!(
+            "{}\n-- records {}-{} of {} ({}:p previous) --",
+            page,
+            start + 1,
+            end,
+            self.total_size,
+            more
+        )
+    }
+
+    pub async fn next_page(&mut self, conn: &Connection) -> Result<Option<String>, DynError> {
+        let start = *self.page_starts.last().unwrap();
+        let end = self.page_end(start);
+
+        if end == self.records.len() {
+            match self.next_records_url.take() {
+                Some(url) => {
+                    let response = conn.next_records(&url, &self.count_relationships).await?;
+                    self.append(response);
+                }
+                None => return Ok(None),
+            }
+        }
+
+        self.page_starts.push(end);
+        Ok(Some(self.current_page()))
+    }
+
+    pub fn previous_page(&mut self) -> Option<String> {
+        if self.page_starts.len() == 1 {
+            return None;
+        }
+        self.page_starts.pop();
+        Some(self.current_page())
+    }
+
+    fn append(&mut self, mut response: Value) {
+        if let Some(records) = response["records"].as_array_mut() {
+            self.records.append(records);
+        }
+        self.next_records_url = response["nextRecordsUrl"].as_str().map(String::from);
+    }
+
+    // Fills the page with whole records until the screen height is reached.
+    // A page always shows at least one record, however tall it is.
+    fn page_end(&self, start: usize) -> usize {
+        let mut end = (start + 1).min(self.records.len());
+        while end < self.records.len()
+            && self
+                .format
+                .render(&self.records[start..=end], &self.options)
+                .lines()
+                .count()
+                <= self.height
+        {
+            end += 1;
+        }
+        end
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn response(count: usize, next_records_url: Option<&str>) -> Value {
+        let records: Vec<Value> = (0..count).map(|i| json!({ "Id": i })).collect();
+        json!({
+            "totalSize": 100,
+            "records": records,
+            "nextRecordsUrl": next_records_url,
+        })
+    }
+
+    #[test]
+    fn test_page_end() {
+        // each record takes 3 lines: {, "Id": n, }
+        let pager = Pager::new(
+            response(10, None),
+            &[],
+            &RenderOptions::default(),
+            14 + RESERVED_LINES,
+            Format::Json,
+        );
+        assert_eq!(pager.page_end(0), 4);
+        assert_eq!(pager.page_end(8), 10);
+        assert!(!pager.fits_on_screen());
+
+        let pager = Pager::new(
+            response(2, None),
+            &[],
+            &RenderOptions::default(),
+            14 + RESERVED_LINES,
+            Format::Json,
+        );
+        assert!(pager.fits_on_screen());
+
+        // case: more records are left on the server
+        let pager = Pager::new(
+            response(2, Some("/next")),
+            &[],
+            &RenderOptions::default(),
+            14 + RESERVED_LINES,
+            Format::Json,
+        );
+        assert!(!pager.fits_on_screen());
+    }
+
+    #[test]
+    fn test_page_end_table() {
+        // one line per record below the 3 header lines and above the bottom border
+        let pager = Pager::new(
+            response(20, None),
+            &[],
+            &RenderOptions::default(),
+            14 + RESERVED_LINES,
+            Format::Table,
+        );
+        assert_eq!(pager.page_end(0), 10);
+        assert_eq!(pager.page_end(10), 20);
+    }
+
+    #[test]
+    fn test_previous_page() {
+        let mut pager = Pager::new(
+            response(10, None),
+            &[],
+            &RenderOptions::default(),
+            14 + RESERVED_LINES,
+            Format::Json,
+        );
+        assert!(pager.previous_page().is_none());
+        assert!(pager
+            .current_page()
+            .ends_with("-- records 1-4 of 100 (:n next, :p previous) --"));
+
+        pager.page_starts.push(4);
+        assert!(pager.current_page().contains("-- records 5-8 of 100"));
+        assert!(pager.previous_page().is_some());
+        assert!(pager.current_page().contains("-- records 1-4 of 100"));
+    }
+}
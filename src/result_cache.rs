@@ -0,0 +1,74 @@
+use serde_json::Value;
+use std::collections::VecDeque;
+
+// Most responses kept, the least recently used one is dropped first.
+const MAX_RESULTS: usize = 10;
+
+// Responses of the queries run in the REPL, so that running the same SOQL again, e.g.
+// after changing the format, is answered without asking Salesforce.
+#[derive(Debug, Default)]
+pub struct ResultCache {
+    // the most recently used first
+    entries: VecDeque<(String, Value)>,
+}
+
+impl ResultCache {
+    // The same SOQL gives other records with all() and on the GraphQL backend.
+    pub fn key(soql: &str, all: bool, graphql: bool) -> String {
+        format!("{}:{}:{}", all, graphql, soql)
+    }
+
+    pub fn get(&mut self, key: &str) -> Option<Value> {
+        let at = self.entries.iter().position(|(k, _)| k == key)?;
+        let entry = self.entries.remove(at)?;
+        let response = entry.1.clone();
+        self.entries.push_front(entry);
+        Some(response)
+    }
+
+    pub fn insert(&mut self, key: String, response: Value) {
+        self.entries.retain(|(k, _)| *k != key);
+        self.entries.push_front((key, response));
+        self.entries.truncate(MAX_RESULTS);
+    }
+
+    // After changing records or switching orgs the responses are outdated.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_least_recently_used_is_dropped() {
+        let mut cache = ResultCache::default();
+        for i in 0..MAX_RESULTS {
+            cache.insert(i.to_string(), json!(i));
+        }
+        // 0 becomes the most recently used, so 1 is dropped instead
+        assert_eq!(cache.get("0"), Some(json!(0)));
+        cache.insert(String::from("new"), json!("new"));
+        assert_eq!(cache.get("1"), None);
+        assert_eq!(cache.get("0"), Some(json!(0)));
+        assert_eq!(cache.get("new"), Some(json!("new")));
+
+        cache.insert(String::from("0"), json!("fresh"));
+        assert_eq!(cache.get("0"), Some(json!("fresh")));
+        assert_eq!(cache.entries.len(), MAX_RESULTS);
+
+        cache.clear();
+        assert_eq!(cache.get("0"), None);
+    }
+
+    #[test]
+    fn test_key() {
+        assert_ne!(
+            ResultCache::key("SELECT Id FROM Account", true, false),
+            ResultCache::key("SELECT Id FROM Account", false, false)
+        );
+    }
+}
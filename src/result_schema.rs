@@ -0,0 +1,273 @@
+use crate::cache::Field;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColumnType {
+    Text,
+    Integer,
+    Decimal,
+    Boolean,
+    Date,
+    DateTime,
+    Time,
+}
+
+impl Display for ColumnType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ColumnType::Text => "text",
+            ColumnType::Integer => "integer",
+            ColumnType::Decimal => "decimal",
+            ColumnType::Boolean => "boolean",
+            ColumnType::Date => "date",
+            ColumnType::DateTime => "datetime",
+            ColumnType::Time => "time",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl ColumnType {
+    // Maps a describe field type. Compound and unknown types are exported as text.
+    pub fn from_field_type(field_type: &str) -> ColumnType {
+        match field_type {
+            "int" | "long" => ColumnType::Integer,
+            "double" | "currency" | "percent" => ColumnType::Decimal,
+            "boolean" => ColumnType::Boolean,
+            "date" => ColumnType::Date,
+            "datetime" => ColumnType::DateTime,
+            "time" => ColumnType::Time,
+            _ => ColumnType::Text,
+        }
+    }
+
+    // Used for columns without metadata, e.g. aggregates or uncached objects.
+    fn from_value(value: &Value) -> Option<ColumnType> {
+        match value {
+            Value::Null => None,
+            Value::Bool(_) => Some(ColumnType::Boolean),
+            Value::Number(n) if n.is_i64() || n.is_u64() => Some(ColumnType::Integer),
+            Value::Number(_) => Some(ColumnType::Decimal),
+            _ => Some(ColumnType::Text),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Column {
+    pub name: String,
+    pub column_type: ColumnType,
+}
+
+// Infers the columns of query result records. Parent records are flattened into
+// dotted columns (Account.Name) and typed by the describe metadata of the object in
+// their `attributes`. Columns missing from the metadata fall back to the first
+// non-null value, and to text when every value is null.
+// Columns are ordered like `fields`, the fields of select(). Columns that are not
+// selected fields, e.g. aggregates or subqueries, come after them.
+pub fn infer_columns(
+    records: &[Value],
+    object_fields: &HashMap<String, Vec<Field>>,
+    fields: &[String],
+) -> Vec<Column> {
+    let mut columns: Vec<(String, Option<ColumnType>)> = Vec::new();
+
+    for record in records {
+        if let Value::Object(record) = record {
+            collect_columns(record, "", object_fields, &mut columns);
+        }
+    }
+
+    // a parent that is null in later records is not a column of its own
+    let parents: Vec<String> = columns
+        .iter()
+        .filter_map(|(name, _)| name.rsplit_once('.').map(|(parent, _)| parent.to_string()))
+        .collect();
+
+    let mut columns: Vec<Column> = columns
+        .into_iter()
+        .filter(|(name, _)| !parents.contains(name))
+        .map(|(name, column_type)| Column {
+            name,
+            column_type: column_type.unwrap_or(ColumnType::Text),
+        })
+        .collect();
+    columns.sort_by_key(|column| {
+        fields
+            .iter()
+            .position(|field| field.eq_ignore_ascii_case(&column.name))
+            .unwrap_or(fields.len())
+    });
+    columns
+}
+
+fn collect_columns(
+    record: &Map<String, Value>,
+    prefix: &str,
+    object_fields: &HashMap<String, Vec<Field>>,
+    columns: &mut Vec<(String, Option<ColumnType>)>,
+) {
+    let fields = record
+        .get("attributes")
+        .and_then(|attributes| attributes["type"].as_str())
+        .and_then(|object| object_fields.get(object));
+
+    for (key, value) in record {
+        if key == "attributes" {
+            continue;
+        }
+        let name = format!("{}{}", prefix, key);
+
+        // a parent record, as opposed to a subquery result which has its own records
+        if let Value::Object(parent) = value {
+            if !parent.contains_key("records") {
+                // a parent that was null in earlier records is replaced by its fields
+                let at = columns.iter().position(|(n, _)| *n == name);
+                if let Some(at) = at {
+                    columns.remove(at);
+                }
+                let start = columns.len();
+                collect_columns(parent, &format!("{}.", name), object_fields, columns);
+                if let Some(at) = at {
+                    let parent_columns = columns.split_off(start);
+                    columns.splice(at..at, parent_columns);
+                }
+                continue;
+            }
+        }
+
+        let column_type = fields
+            .and_then(|fields| fields.iter().find(|f| f.name.eq_ignore_ascii_case(key)))
+            .map(|field| ColumnType::from_field_type(&field.field_type))
+            .or_else(|| ColumnType::from_value(value));
+
+        match columns.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, inferred)) => {
+                if inferred.is_none() {
+                    *inferred = column_type;
+                }
+            }
+            None => columns.push((name, column_type)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn object_fields() -> HashMap<String, Vec<Field>> {
+        let field = |name: &str, field_type: &str| Field {
+            name: name.to_string(),
+            field_type: field_type.to_string(),
+            ..Default::default()
+        };
+        HashMap::from([
+            (
+                String::from("Opportunity"),
+                vec![
+                    field("Id", "id"),
+                    field("Amount", "currency"),
+                    field("CloseDate", "date"),
+                    field("IsWon", "boolean"),
+                ],
+            ),
+            (
+                String::from("Account"),
+                vec![field("NumberOfEmployees", "int")],
+            ),
+        ])
+    }
+
+    #[test]
+    fn test_infer_columns() {
+        let records = vec![
+            json!({
+                "attributes": { "type": "Opportunity" },
+                "Id": "0065g00000AbCdE",
+                "Amount": 1000,
+                "CloseDate": "2024-01-01",
+                "IsWon": null,
+                "Account": null,
+            }),
+            json!({
+                "attributes": { "type": "Opportunity" },
+                "Id": "0065g00000AbCdF",
+                "Amount": null,
+                "CloseDate": null,
+                "IsWon": null,
+                "Account": {
+                    "attributes": { "type": "Account" },
+                    "NumberOfEmployees": 10,
+                    "Site": "Tokyo",
+                },
+            }),
+        ];
+
+        let columns: Vec<(String, String)> = infer_columns(&records, &object_fields(), &[])
+            .into_iter()
+            .map(|c| (c.name, c.column_type.to_string()))
+            .collect();
+        let expected = [
+            ("Account.NumberOfEmployees", "integer"),
+            ("Account.Site", "text"),
+            ("Amount", "decimal"),
+            ("CloseDate", "date"),
+            ("Id", "text"),
+            ("IsWon", "boolean"),
+        ];
+        assert_eq!(
+            columns,
+            expected
+                .iter()
+                .map(|(n, t)| (n.to_string(), t.to_string()))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_infer_columns_in_select_order() {
+        let records = vec![json!({
+            "attributes": { "type": "Opportunity" },
+            "Name": "Big deal",
+            "Account": { "attributes": { "type": "Account" }, "Name": "Acme" },
+            "Amount": 1000,
+            "Contacts": { "totalSize": 0, "records": [] },
+        })];
+        let fields = [
+            String::from("name"),
+            String::from("Amount"),
+            String::from("Account.Name"),
+        ];
+
+        let names: Vec<String> = infer_columns(&records, &object_fields(), &fields)
+            .into_iter()
+            .map(|c| c.name)
+            .collect();
+        assert_eq!(names, vec!["Name", "Amount", "Account.Name", "Contacts"]);
+    }
+
+    #[test]
+    fn test_infer_columns_without_metadata() {
+        let records = vec![
+            json!({ "attributes": { "type": "AggregateResult" }, "cnt": 3, "expr0": null }),
+            json!({ "attributes": { "type": "AggregateResult" }, "cnt": 4, "expr0": 1.5 }),
+        ];
+        assert_eq!(
+            infer_columns(&records, &object_fields(), &[]),
+            vec![
+                Column {
+                    name: String::from("cnt"),
+                    column_type: ColumnType::Integer,
+                },
+                Column {
+                    name: String::from("expr0"),
+                    column_type: ColumnType::Decimal,
+                },
+            ]
+        );
+    }
+}
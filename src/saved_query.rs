@@ -0,0 +1,212 @@
+use crate::helper::DynError;
+use crate::output::Format;
+use serde::Deserialize;
+use serde_yaml::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const EXTENSION: &str = "soqlgen";
+
+const FRONT_MATTER_DELIMITER: &str = "---";
+
+// A query saved as a .soqlgen file: YAML front-matter between `---` lines followed by
+// the method chain. `:name` in the query is replaced with the parameter of that name.
+//
+// ---
+// name: open opportunities
+// params:
+//   stage: Prospecting
+// ---
+// Opportunity.select(Id, Name).where(StageName = :stage)
+#[derive(Debug, Default, Deserialize)]
+pub struct SavedQuery {
+    pub name: Option<String>,
+    pub org: Option<String>,
+    #[serde(default)]
+    pub params: BTreeMap<String, Value>,
+    pub format: Option<String>,
+    #[serde(skip)]
+    pub query: String,
+}
+
+impl SavedQuery {
+    pub fn load(path: &Path) -> Result<SavedQuery, DynError> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        SavedQuery::parse(&content).map_err(|e| format!("{}: {}", path.display(), e).into())
+    }
+
+    pub fn parse(content: &str) -> Result<SavedQuery, DynError> {
+        let content = content.trim_start();
+        let mut saved_query = match content.strip_prefix(FRONT_MATTER_DELIMITER) {
+            Some(rest) => {
+                let (front_matter, query) = rest
+                    .split_once(&format!("\n{}", FRONT_MATTER_DELIMITER))
+                    .ok_or("the front-matter is not closed with ---")?;
+                let mut saved_query: SavedQuery = if front_matter.trim().is_empty() {
+                    SavedQuery::default()
+                } else {
+                    serde_yaml::from_str(front_matter)?
+                };
+                saved_query.query = query.to_string();
+                saved_query
+            }
+            None => SavedQuery {
+                query: content.to_string(),
+                ..Default::default()
+            },
+        };
+        saved_query.query = saved_query.query.trim().to_string();
+
+        saved_query.format()?;
+
+        Ok(saved_query)
+    }
+
+    pub fn format(&self) -> Result<Option<Format>, DynError> {
+        match &self.format {
+            Some(format) => Ok(Some(format.parse()?)),
+            None => Ok(None),
+        }
+    }
+
+    // Overrides are given as `name=value` and read as YAML, so numbers stay numbers.
+    pub fn set_params(&mut self, overrides: &[String]) -> Result<(), DynError> {
+        for param in overrides {
+            let (name, value) = param
+                .split_once('=')
+                .ok_or_else(|| format!("Invalid parameter {}: expected name=value", param))?;
+            let value = serde_yaml::from_str(value).unwrap_or(Value::String(value.to_string()));
+            self.params.insert(name.to_string(), value);
+        }
+        Ok(())
+    }
+
+    // Returns the query with its parameters substituted. Text inside string literals is
+    // left as written.
+    pub fn render(&self) -> Result<String, DynError> {
+        let mut rendered = String::new();
+        let mut chars = self.query.chars().peekable();
+        let mut in_string = false;
+
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' if in_string => {
+                    rendered.push(c);
+                    if let Some(escaped) = chars.next() {
+                        rendered.push(escaped);
+                    }
+                    continue;
+                }
+                '\'' => in_string = !in_string,
+                ':' if !in_string
+                    && chars.peek().is_some_and(|c| c.is_alphabetic() || *c == '_') =>
+                {
+                    let mut name = String::new();
+                    while let Some(c) = chars.peek() {
+                        if c.is_alphanumeric() || *c == '_' {
+                            name.push(*c);
+                            chars.next();
+                        } else {
+                            break;
+                        }
+                    }
+                    let value = self
+                        .params
+                        .get(&name)
+                        .ok_or_else(|| format!("Missing parameter: {}", name))?;
+                    rendered.push_str(&render_value(value)?);
+                    continue;
+                }
+                _ => {}
+            }
+            rendered.push(c);
+        }
+
+        Ok(rendered)
+    }
+}
+
+fn render_value(value: &Value) -> Result<String, DynError> {
+    match value {
+        Value::String(s) => Ok(format!(
+            "'{}'",
+            s.replace('\\', "\\\\").replace('\'', "\\'")
+        )),
+        Value::Number(n) => Ok(n.to_string()),
+        Value::Bool(b) => Ok(b.to_string()),
+        Value::Null => Ok(String::from("null")),
+        _ => Err("Parameters must be a string, number, boolean or null".into()),
+    }
+}
+
+// `name` is looked up as name.soqlgen in the current directory unless it is a path.
+pub fn saved_query_path(name: &str) -> PathBuf {
+    let path = PathBuf::from(name);
+    if path.extension().is_some_and(|e| e == EXTENSION) {
+        path
+    } else {
+        path.with_extension(EXTENSION)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let content = "---\nname: open opportunities\norg: prod\nparams:\n  stage: Prospecting\n  amount: 1000\n---\nOpportunity.where(StageName = :stage AND Amount > :amount)\n";
+        let saved_query = SavedQuery::parse(content).unwrap();
+
+        assert_eq!(saved_query.name.as_deref(), Some("open opportunities"));
+        assert_eq!(saved_query.org.as_deref(), Some("prod"));
+        assert_eq!(
+            saved_query.render().unwrap(),
+            "Opportunity.where(StageName = 'Prospecting' AND Amount > 1000)"
+        );
+
+        // case: no front-matter
+        let saved_query = SavedQuery::parse("Account.select(Id)").unwrap();
+        assert_eq!(saved_query.render().unwrap(), "Account.select(Id)");
+
+        assert!(SavedQuery::parse("---\nname: x\nAccount.select(Id)").is_err());
+        assert!(SavedQuery::parse("---\nformat: xlsx\n---\nAccount.select(Id)").is_err());
+        let saved_query = SavedQuery::parse("---\nformat: csv\n---\nAccount.select(Id)").unwrap();
+        assert_eq!(saved_query.format().unwrap(), Some(Format::Csv));
+    }
+
+    #[test]
+    fn test_render() {
+        let mut saved_query = SavedQuery::parse(
+            "---\nparams:\n  name: it's\n---\nAccount.where(Name = :name AND Note = 'a:b' AND CreatedDate = LAST_N_FISCAL_YEARS:2)",
+        )
+        .unwrap();
+        assert_eq!(
+            saved_query.render().unwrap(),
+            "Account.where(Name = 'it\\'s' AND Note = 'a:b' AND CreatedDate = LAST_N_FISCAL_YEARS:2)"
+        );
+
+        saved_query.set_params(&[String::from("name=10")]).unwrap();
+        assert!(saved_query
+            .render()
+            .unwrap()
+            .starts_with("Account.where(Name = 10 "));
+
+        saved_query.query = String::from("Account.where(Name = :missing)");
+        assert_eq!(
+            saved_query.render().unwrap_err().to_string(),
+            "Missing parameter: missing"
+        );
+    }
+
+    #[test]
+    fn test_saved_query_path() {
+        assert_eq!(saved_query_path("open"), PathBuf::from("open.soqlgen"));
+        assert_eq!(
+            saved_query_path("queries/open.soqlgen"),
+            PathBuf::from("queries/open.soqlgen")
+        );
+    }
+}
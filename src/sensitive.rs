@@ -0,0 +1,43 @@
+// Objects whose queries are audited or may fire platform events. Querying them in a
+// production org asks for confirmation first.
+const DEFAULT_SENSITIVE_OBJECTS: [&str; 3] = ["User", "LoginHistory", "SetupAuditTrail"];
+
+// `setting` is the comma separated value of SFDC_SENSITIVE_OBJECTS. An empty value
+// turns the confirmation off, an unset one keeps the defaults.
+pub fn sensitive_objects(setting: Option<&str>) -> Vec<String> {
+    match setting {
+        Some(setting) => setting
+            .split(',')
+            .map(|object| object.trim().to_string())
+            .filter(|object| !object.is_empty())
+            .collect(),
+        None => DEFAULT_SENSITIVE_OBJECTS
+            .iter()
+            .map(|object| object.to_string())
+            .collect(),
+    }
+}
+
+pub fn is_sensitive(object: &str, sensitive_objects: &[String]) -> bool {
+    sensitive_objects
+        .iter()
+        .any(|sensitive| sensitive.eq_ignore_ascii_case(object))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sensitive_objects() {
+        let defaults = sensitive_objects(None);
+        assert!(is_sensitive("user", &defaults));
+        assert!(!is_sensitive("Account", &defaults));
+
+        let configured = sensitive_objects(Some("Secret__c, LoginHistory,"));
+        assert_eq!(configured, vec!["Secret__c", "LoginHistory"]);
+        assert!(!is_sensitive("User", &configured));
+
+        assert!(sensitive_objects(Some("")).is_empty());
+    }
+}
@@ -0,0 +1,210 @@
+use std::{
+    error::Error,
+    fmt::{self, Display},
+    str::FromStr,
+};
+
+// Widest a table cell is by default, in terminal columns.
+const DEFAULT_MAX_WIDTH: usize = 50;
+
+// REPL options changed with `\set <name> <value>`.
+#[derive(Debug)]
+pub struct Settings {
+    // map method names of other query builders (order_by, take, ...) to ours
+    pub autocorrect: bool,
+    // datetimes in the local timezone and numbers with thousands separators
+    pub localize: bool,
+    // widest a table cell may be, 0 for no limit
+    pub max_width: usize,
+    // wrap long table cells instead of cutting them
+    pub wrap: bool,
+    // keep the attributes of records in JSON output
+    pub raw: bool,
+    // show each record as a block of `Field: value` lines instead of a table row
+    pub expanded: bool,
+    // jq expression applied to every response before it is formatted, from --filter
+    pub filter: Option<String>,
+    // run every query as a Bulk API 2.0 job, from --bulk
+    pub bulk: bool,
+    // the API queries run on, from --backend
+    pub backend: Backend,
+    // print the API usage of the org after every request, from --verbose
+    pub verbose: bool,
+    // complete objects that cannot be queried too, e.g. feeds and share tables
+    pub all_objects: bool,
+    // answer a query run again from the responses of the session
+    pub cache: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Backend {
+    // SOQL through the REST API
+    #[default]
+    Rest,
+    // the GraphQL API, which pages records by cursor
+    Graphql,
+}
+
+impl Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Backend::Rest => write!(f, "rest"),
+            Backend::Graphql => write!(f, "graphql"),
+        }
+    }
+}
+
+impl FromStr for Backend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "rest" => Ok(Backend::Rest),
+            "graphql" => Ok(Backend::Graphql),
+            _ => Err(format!("Unknown backend: {}, expected rest or graphql", s)),
+        }
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            autocorrect: false,
+            localize: false,
+            max_width: DEFAULT_MAX_WIDTH,
+            wrap: false,
+            raw: false,
+            expanded: false,
+            filter: None,
+            bulk: false,
+            backend: Backend::Rest,
+            verbose: false,
+            all_objects: false,
+            cache: true,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SettingError {
+    UnknownSetting(String),
+    // the setting and the values it accepts
+    InvalidValue(String, String),
+}
+
+impl Display for SettingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SettingError::UnknownSetting(name) => write!(f, "Unknown setting: {}", name),
+            SettingError::InvalidValue(name, expected) => {
+                write!(f, "Invalid value for {}: expected {}", name, expected)
+            }
+        }
+    }
+}
+
+impl Error for SettingError {}
+
+impl Settings {
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), SettingError> {
+        match name {
+            "autocorrect" => self.autocorrect = on_off(name, value)?,
+            "localize" => self.localize = on_off(name, value)?,
+            "maxwidth" => {
+                self.max_width = value.parse().map_err(|_| {
+                    SettingError::InvalidValue(
+                        name.to_string(),
+                        String::from("a number of columns, 0 for no limit"),
+                    )
+                })?
+            }
+            "wrap" => self.wrap = on_off(name, value)?,
+            "raw" => self.raw = on_off(name, value)?,
+            "expanded" => self.expanded = on_off(name, value)?,
+            "verbose" => self.verbose = on_off(name, value)?,
+            "allobjects" => self.all_objects = on_off(name, value)?,
+            "cache" => self.cache = on_off(name, value)?,
+            "backend" => {
+                self.backend = value.parse().map_err(|_| {
+                    SettingError::InvalidValue(name.to_string(), String::from("rest or graphql"))
+                })?
+            }
+            _ => return Err(SettingError::UnknownSetting(name.to_string())),
+        }
+        Ok(())
+    }
+
+    // `name value` lines for `\set` without arguments.
+    pub fn list(&self) -> Vec<String> {
+        vec![
+            format!("autocorrect {}", show_on_off(self.autocorrect)),
+            format!("localize {}", show_on_off(self.localize)),
+            format!("maxwidth {}", self.max_width),
+            format!("wrap {}", show_on_off(self.wrap)),
+            format!("raw {}", show_on_off(self.raw)),
+            format!("expanded {}", show_on_off(self.expanded)),
+            format!("backend {}", self.backend),
+            format!("verbose {}", show_on_off(self.verbose)),
+            format!("allobjects {}", show_on_off(self.all_objects)),
+            format!("cache {}", show_on_off(self.cache)),
+        ]
+    }
+}
+
+fn on_off(name: &str, value: &str) -> Result<bool, SettingError> {
+    match value {
+        "on" => Ok(true),
+        "off" => Ok(false),
+        _ => Err(SettingError::InvalidValue(
+            name.to_string(),
+            String::from("on or off"),
+        )),
+    }
+}
+
+fn show_on_off(value: bool) -> &'static str {
+    if value {
+        "on"
+    } else {
+        "off"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set() {
+        let mut settings = Settings::default();
+        settings.set("autocorrect", "on").unwrap();
+        assert!(settings.autocorrect);
+        settings.set("maxwidth", "80").unwrap();
+        settings.set("backend", "graphql").unwrap();
+        assert_eq!(
+            settings.list(),
+            vec![
+                "autocorrect on",
+                "localize off",
+                "maxwidth 80",
+                "wrap off",
+                "raw off",
+                "expanded off",
+                "backend graphql",
+                "verbose off",
+                "allobjects off",
+                "cache on"
+            ]
+        );
+
+        assert_eq!(
+            settings.set("autocorrect", "yes").unwrap_err().to_string(),
+            "Invalid value for autocorrect: expected on or off"
+        );
+        assert!(settings.set("backend", "soap").is_err());
+        assert_eq!(
+            settings.set("color", "on"),
+            Err(SettingError::UnknownSetting(String::from("color")))
+        );
+    }
+}